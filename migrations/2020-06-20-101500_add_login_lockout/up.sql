@@ -0,0 +1,12 @@
+ALTER TABLE users ADD COLUMN failed_login_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN locked_until TIMESTAMP;
+ALTER TABLE users ADD COLUMN admin BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE login_attempts (
+  id SERIAL PRIMARY KEY,
+  email VARCHAR(100) NOT NULL,
+  ip_address VARCHAR NOT NULL,
+  succeeded BOOLEAN NOT NULL,
+  created_at TIMESTAMP NOT NULL
+);
+CREATE INDEX login_attempts_ip_address_created_at_idx ON login_attempts (ip_address, created_at);
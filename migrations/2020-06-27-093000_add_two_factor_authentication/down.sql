@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE backup_codes;
+
+ALTER TABLE users DROP COLUMN totp_last_step;
+ALTER TABLE users DROP COLUMN totp_enabled;
+ALTER TABLE users DROP COLUMN totp_secret;
@@ -0,0 +1,13 @@
+CREATE TABLE audit_events (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  company VARCHAR(100) NOT NULL,
+  operation VARCHAR NOT NULL,
+  entity VARCHAR NOT NULL,
+  entity_id INTEGER NOT NULL,
+  before JSONB,
+  after JSONB,
+  created_at TIMESTAMP NOT NULL
+);
+CREATE INDEX audit_events_company_created_at_idx ON audit_events (company, created_at);
+CREATE INDEX audit_events_entity_idx ON audit_events (entity, entity_id);
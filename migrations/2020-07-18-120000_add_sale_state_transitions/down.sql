@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE sale_state_transitions;
+DROP TYPE sale_event;
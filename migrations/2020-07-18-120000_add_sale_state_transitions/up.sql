@@ -0,0 +1,12 @@
+CREATE TYPE sale_event AS ENUM ('approve', 'cancel', 'partially_pay', 'pay');
+
+CREATE TABLE sale_state_transitions (
+  id SERIAL PRIMARY KEY,
+  sale_id INTEGER NOT NULL REFERENCES sales(id) ON DELETE CASCADE,
+  from_state sale_state NOT NULL,
+  to_state sale_state NOT NULL,
+  event sale_event NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  created_at TIMESTAMP NOT NULL
+);
+CREATE INDEX sale_state_transitions_sale_id_idx ON sale_state_transitions (sale_id);
@@ -0,0 +1,12 @@
+CREATE TABLE categories (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  parent_id INTEGER REFERENCES categories(id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  CHECK (name <> ''),
+  CHECK (parent_id <> id)
+);
+CREATE INDEX categories_parent_id_idx ON categories (parent_id);
+
+ALTER TABLE products ADD COLUMN category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL;
+CREATE INDEX products_category_id_idx ON products (category_id);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE product_barcodes;
+DROP TYPE barcode_symbology;
+
+DROP INDEX products_user_id_sku_idx;
+ALTER TABLE products DROP COLUMN sku;
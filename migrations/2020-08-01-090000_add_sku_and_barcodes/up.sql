@@ -0,0 +1,15 @@
+ALTER TABLE products ADD COLUMN sku VARCHAR;
+CREATE UNIQUE INDEX products_user_id_sku_idx ON products (user_id, sku);
+
+CREATE TYPE barcode_symbology AS ENUM ('ean13', 'upc_a', 'code128');
+
+CREATE TABLE product_barcodes (
+  id SERIAL PRIMARY KEY,
+  product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  code VARCHAR NOT NULL,
+  symbology barcode_symbology NOT NULL,
+  CHECK (code <> ''),
+  UNIQUE (user_id, code)
+);
+CREATE INDEX product_barcodes_product_id_idx ON product_barcodes (product_id);
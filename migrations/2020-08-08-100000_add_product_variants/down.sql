@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE product_variant_values;
+DROP TABLE product_options;
+
+DROP INDEX products_parent_id_idx;
+ALTER TABLE products DROP COLUMN parent_id;
@@ -0,0 +1,22 @@
+ALTER TABLE products ADD COLUMN parent_id INTEGER REFERENCES products(id) ON DELETE CASCADE;
+ALTER TABLE products ADD CONSTRAINT products_parent_id_check CHECK (parent_id <> id);
+CREATE INDEX products_parent_id_idx ON products (parent_id);
+
+CREATE TABLE product_options (
+  id SERIAL PRIMARY KEY,
+  product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  option_values TEXT[] NOT NULL,
+  position INTEGER NOT NULL DEFAULT 0,
+  CHECK (name <> ''),
+  UNIQUE (product_id, name)
+);
+
+CREATE TABLE product_variant_values (
+  id SERIAL PRIMARY KEY,
+  variant_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  product_option_id INTEGER NOT NULL REFERENCES product_options(id) ON DELETE CASCADE,
+  value VARCHAR NOT NULL,
+  UNIQUE (variant_id, product_option_id)
+);
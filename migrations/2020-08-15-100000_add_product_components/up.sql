@@ -0,0 +1,11 @@
+CREATE TABLE product_components (
+  id SERIAL PRIMARY KEY,
+  kit_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  component_id INTEGER NOT NULL REFERENCES products(id) ON DELETE RESTRICT,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  quantity FLOAT NOT NULL,
+  CHECK (quantity > 0),
+  CHECK (kit_id <> component_id),
+  UNIQUE (kit_id, component_id)
+);
+CREATE INDEX product_components_component_id_idx ON product_components (component_id);
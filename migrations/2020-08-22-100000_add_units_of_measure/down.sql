@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE sale_products DROP COLUMN unit_id;
+DROP TABLE product_units;
+ALTER TABLE products DROP COLUMN unit_id;
+DROP TABLE units;
@@ -0,0 +1,23 @@
+CREATE TABLE units (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  CHECK (name <> ''),
+  UNIQUE (user_id, name)
+);
+
+ALTER TABLE products ADD COLUMN unit_id INTEGER REFERENCES units(id) ON DELETE RESTRICT;
+
+CREATE TABLE product_units (
+  id SERIAL PRIMARY KEY,
+  product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  unit_id INTEGER NOT NULL REFERENCES units(id) ON DELETE RESTRICT,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  factor FLOAT NOT NULL,
+  for_sale BOOLEAN NOT NULL DEFAULT TRUE,
+  for_purchase BOOLEAN NOT NULL DEFAULT TRUE,
+  CHECK (factor > 0),
+  UNIQUE (product_id, unit_id)
+);
+
+ALTER TABLE sale_products ADD COLUMN unit_id INTEGER REFERENCES units(id) ON DELETE RESTRICT;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE sale_products DROP COLUMN lot_id;
+DROP TABLE lots;
+ALTER TABLE products DROP COLUMN track_lots;
@@ -0,0 +1,15 @@
+ALTER TABLE products ADD COLUMN track_lots BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE lots (
+  id SERIAL PRIMARY KEY,
+  product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  lot_number VARCHAR NOT NULL,
+  expires_on DATE,
+  stock FLOAT NOT NULL DEFAULT 0,
+  CHECK (lot_number <> ''),
+  UNIQUE (product_id, lot_number)
+);
+CREATE INDEX lots_expires_on_idx ON lots (user_id, expires_on);
+
+ALTER TABLE sale_products ADD COLUMN lot_id INTEGER REFERENCES lots(id) ON DELETE RESTRICT;
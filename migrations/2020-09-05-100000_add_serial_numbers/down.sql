@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE serial_numbers;
+ALTER TABLE products DROP COLUMN serialized;
@@ -0,0 +1,14 @@
+ALTER TABLE products ADD COLUMN serialized BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE serial_numbers (
+  id SERIAL PRIMARY KEY,
+  product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  serial VARCHAR NOT NULL,
+  sale_product_id INTEGER REFERENCES sale_products(id) ON DELETE SET NULL,
+  received_at TIMESTAMP NOT NULL,
+  CHECK (serial <> ''),
+  UNIQUE (product_id, serial)
+);
+CREATE INDEX serial_numbers_serial_idx ON serial_numbers (user_id, serial);
+CREATE INDEX serial_numbers_sale_product_id_idx ON serial_numbers (sale_product_id);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE stock_movements;
+DROP TYPE stock_movement_kind;
+ALTER TABLE sales DROP COLUMN location_id;
+DROP TABLE product_stocks;
+DROP TABLE locations;
@@ -0,0 +1,35 @@
+CREATE TABLE locations (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  CHECK (name <> ''),
+  UNIQUE (user_id, name)
+);
+
+CREATE TABLE product_stocks (
+  id SERIAL PRIMARY KEY,
+  product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  location_id INTEGER NOT NULL REFERENCES locations(id) ON DELETE RESTRICT,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  stock FLOAT NOT NULL DEFAULT 0,
+  UNIQUE (product_id, location_id)
+);
+CREATE INDEX product_stocks_location_id_idx ON product_stocks (location_id);
+
+ALTER TABLE sales ADD COLUMN location_id INTEGER REFERENCES locations(id) ON DELETE RESTRICT;
+
+CREATE TYPE stock_movement_kind AS ENUM ('receipt', 'sale', 'sale_cancellation');
+
+CREATE TABLE stock_movements (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  location_id INTEGER REFERENCES locations(id) ON DELETE RESTRICT,
+  lot_id INTEGER REFERENCES lots(id) ON DELETE SET NULL,
+  quantity FLOAT NOT NULL,
+  kind stock_movement_kind NOT NULL,
+  sale_id INTEGER REFERENCES sales(id) ON DELETE SET NULL,
+  created_at TIMESTAMP NOT NULL
+);
+CREATE INDEX stock_movements_product_id_idx ON stock_movements (product_id, created_at);
+CREATE INDEX stock_movements_sale_id_idx ON stock_movements (sale_id);
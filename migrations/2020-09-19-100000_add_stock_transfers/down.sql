@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM stock_movements WHERE transfer_id IS NOT NULL;
+ALTER TABLE stock_movements DROP COLUMN transfer_id;
+
+ALTER TYPE stock_movement_kind RENAME TO stock_movement_kind_old;
+CREATE TYPE stock_movement_kind AS ENUM ('receipt', 'sale', 'sale_cancellation');
+ALTER TABLE stock_movements
+  ALTER COLUMN kind TYPE stock_movement_kind USING kind::text::stock_movement_kind;
+DROP TYPE stock_movement_kind_old;
+
+DROP TABLE transfer_products;
+DROP TABLE transfers;
+DROP TYPE transfer_state;
@@ -0,0 +1,37 @@
+CREATE TYPE transfer_state AS ENUM ('draft', 'in_transit', 'received');
+
+CREATE TABLE transfers (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  source_location_id INTEGER NOT NULL REFERENCES locations(id) ON DELETE RESTRICT,
+  destination_location_id INTEGER NOT NULL REFERENCES locations(id) ON DELETE RESTRICT,
+  state transfer_state NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  sent_at TIMESTAMP,
+  received_at TIMESTAMP,
+  CHECK (source_location_id <> destination_location_id)
+);
+CREATE INDEX transfers_user_id_idx ON transfers (user_id, state);
+
+CREATE TABLE transfer_products (
+  id SERIAL PRIMARY KEY,
+  transfer_id INTEGER NOT NULL REFERENCES transfers(id) ON DELETE CASCADE,
+  product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE RESTRICT,
+  lot_id INTEGER REFERENCES lots(id) ON DELETE RESTRICT,
+  quantity FLOAT NOT NULL,
+  received_quantity FLOAT,
+  CHECK (quantity > 0),
+  CHECK (received_quantity >= 0)
+);
+CREATE INDEX transfer_products_transfer_id_idx ON transfer_products (transfer_id);
+
+-- ADD VALUE can't run inside the migration transaction before PostgreSQL 12
+ALTER TYPE stock_movement_kind RENAME TO stock_movement_kind_old;
+CREATE TYPE stock_movement_kind AS ENUM (
+  'receipt', 'sale', 'sale_cancellation', 'transfer_out', 'transfer_in', 'transfer_discrepancy'
+);
+ALTER TABLE stock_movements
+  ALTER COLUMN kind TYPE stock_movement_kind USING kind::text::stock_movement_kind;
+DROP TYPE stock_movement_kind_old;
+
+ALTER TABLE stock_movements ADD COLUMN transfer_id INTEGER REFERENCES transfers(id) ON DELETE SET NULL;
@@ -0,0 +1,16 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM stock_movements WHERE kind = 'adjustment';
+ALTER TABLE stock_movements DROP COLUMN stock_count_id, DROP COLUMN reason;
+
+ALTER TYPE stock_movement_kind RENAME TO stock_movement_kind_old;
+CREATE TYPE stock_movement_kind AS ENUM (
+  'receipt', 'sale', 'sale_cancellation', 'transfer_out', 'transfer_in', 'transfer_discrepancy'
+);
+ALTER TABLE stock_movements
+  ALTER COLUMN kind TYPE stock_movement_kind USING kind::text::stock_movement_kind;
+DROP TYPE stock_movement_kind_old;
+
+DROP TABLE stock_count_lines;
+DROP TABLE stock_counts;
+DROP TYPE adjustment_reason;
+DROP TYPE stock_count_state;
@@ -0,0 +1,37 @@
+CREATE TYPE stock_count_state AS ENUM ('open', 'confirmed', 'cancelled');
+CREATE TYPE adjustment_reason AS ENUM ('count_correction', 'damage', 'theft', 'expiry', 'found', 'correction');
+
+CREATE TABLE stock_counts (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  location_id INTEGER REFERENCES locations(id) ON DELETE RESTRICT,
+  state stock_count_state NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  confirmed_at TIMESTAMP
+);
+CREATE INDEX stock_counts_user_id_idx ON stock_counts (user_id, state);
+
+CREATE TABLE stock_count_lines (
+  id SERIAL PRIMARY KEY,
+  stock_count_id INTEGER NOT NULL REFERENCES stock_counts(id) ON DELETE CASCADE,
+  product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  expected FLOAT NOT NULL,
+  counted FLOAT,
+  reason adjustment_reason,
+  counted_at TIMESTAMP,
+  CHECK (counted >= 0),
+  UNIQUE (stock_count_id, product_id)
+);
+
+ALTER TYPE stock_movement_kind RENAME TO stock_movement_kind_old;
+CREATE TYPE stock_movement_kind AS ENUM (
+  'receipt', 'sale', 'sale_cancellation', 'transfer_out', 'transfer_in', 'transfer_discrepancy',
+  'adjustment'
+);
+ALTER TABLE stock_movements
+  ALTER COLUMN kind TYPE stock_movement_kind USING kind::text::stock_movement_kind;
+DROP TYPE stock_movement_kind_old;
+
+ALTER TABLE stock_movements
+  ADD COLUMN reason adjustment_reason,
+  ADD COLUMN stock_count_id INTEGER REFERENCES stock_counts(id) ON DELETE SET NULL;
@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE product_stocks
+  DROP COLUMN min_stock,
+  DROP COLUMN reorder_point,
+  DROP COLUMN reorder_quantity;
+
+ALTER TABLE products
+  DROP COLUMN min_stock,
+  DROP COLUMN reorder_point,
+  DROP COLUMN reorder_quantity,
+  DROP COLUMN supplier_id;
+
+DROP TABLE suppliers;
@@ -0,0 +1,21 @@
+CREATE TABLE suppliers (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  lead_time_days INTEGER NOT NULL DEFAULT 0,
+  CHECK (name <> ''),
+  CHECK (lead_time_days >= 0),
+  UNIQUE (user_id, name)
+);
+
+ALTER TABLE products
+  ADD COLUMN min_stock FLOAT,
+  ADD COLUMN reorder_point FLOAT,
+  ADD COLUMN reorder_quantity FLOAT,
+  ADD COLUMN supplier_id INTEGER REFERENCES suppliers(id) ON DELETE SET NULL;
+CREATE INDEX products_supplier_id_idx ON products (supplier_id);
+
+ALTER TABLE product_stocks
+  ADD COLUMN min_stock FLOAT,
+  ADD COLUMN reorder_point FLOAT,
+  ADD COLUMN reorder_quantity FLOAT;
@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER tsvectorupdateproducts ON products;
+CREATE TRIGGER tsvectorupdateproducts BEFORE INSERT OR UPDATE
+ON products FOR EACH ROW EXECUTE PROCEDURE
+tsvector_update_trigger(text_searchable_product_col, 'pg_catalog.english', name, description);
+
+DROP FUNCTION products_tsvector_update();
+DROP FUNCTION product_search_query(INTEGER, TEXT);
+DROP FUNCTION product_search_config(INTEGER);
+
+UPDATE products SET text_searchable_product_col =
+    to_tsvector('english', name || ' ' || coalesce(description, ''));
+
+ALTER TABLE users DROP COLUMN search_config;
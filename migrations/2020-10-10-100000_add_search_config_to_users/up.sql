@@ -0,0 +1,24 @@
+ALTER TABLE users ADD COLUMN search_config VARCHAR NOT NULL DEFAULT 'english';
+
+-- Text search configuration of the company owning the products of a user.
+CREATE FUNCTION product_search_config(owner_id INTEGER) RETURNS regconfig AS $$
+  SELECT COALESCE((SELECT search_config FROM users WHERE id = owner_id), 'english')::regconfig
+$$ LANGUAGE sql STABLE;
+
+CREATE FUNCTION product_search_query(owner_id INTEGER, query TEXT) RETURNS tsquery AS $$
+  SELECT plainto_tsquery(product_search_config(owner_id), query)
+$$ LANGUAGE sql STABLE;
+
+CREATE FUNCTION products_tsvector_update() RETURNS trigger AS $$
+BEGIN
+  NEW.text_searchable_product_col := to_tsvector(
+    product_search_config(NEW.user_id),
+    NEW.name || ' ' || COALESCE(NEW.description, '')
+  );
+  RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER tsvectorupdateproducts ON products;
+CREATE TRIGGER tsvectorupdateproducts BEFORE INSERT OR UPDATE
+ON products FOR EACH ROW EXECUTE PROCEDURE products_tsvector_update();
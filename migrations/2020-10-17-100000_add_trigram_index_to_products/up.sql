@@ -0,0 +1,4 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX products_name_trgm_idx ON products USING GIN (name gin_trgm_ops);
+CREATE INDEX products_sku_trgm_idx ON products USING GIN (sku gin_trgm_ops);
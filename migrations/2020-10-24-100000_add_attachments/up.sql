@@ -0,0 +1,18 @@
+CREATE TABLE attachments (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  product_id INTEGER REFERENCES products(id) ON DELETE CASCADE,
+  sale_id INTEGER REFERENCES sales(id) ON DELETE CASCADE,
+  filename VARCHAR NOT NULL,
+  content_type VARCHAR NOT NULL,
+  size INTEGER NOT NULL,
+  storage_key VARCHAR NOT NULL,
+  url VARCHAR NOT NULL,
+  thumbnail_key VARCHAR,
+  thumbnail_url VARCHAR,
+  created_at TIMESTAMP NOT NULL,
+  CHECK ((product_id IS NULL) <> (sale_id IS NULL)),
+  CHECK (size > 0)
+);
+CREATE INDEX attachments_product_id_idx ON attachments (product_id);
+CREATE INDEX attachments_sale_id_idx ON attachments (sale_id);
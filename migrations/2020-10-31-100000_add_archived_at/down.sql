@@ -0,0 +1,6 @@
+ALTER TABLE sale_products DROP CONSTRAINT sale_products_product_id_fkey;
+ALTER TABLE sale_products ADD CONSTRAINT sale_products_product_id_fkey
+  FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE;
+
+ALTER TABLE prices DROP COLUMN archived_at;
+ALTER TABLE products DROP COLUMN archived_at;
@@ -0,0 +1,7 @@
+ALTER TABLE products ADD COLUMN archived_at TIMESTAMP;
+ALTER TABLE prices ADD COLUMN archived_at TIMESTAMP;
+
+-- Deleting a product must not take lines out of past sales.
+ALTER TABLE sale_products DROP CONSTRAINT sale_products_product_id_fkey;
+ALTER TABLE sale_products ADD CONSTRAINT sale_products_product_id_fkey
+  FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE RESTRICT;
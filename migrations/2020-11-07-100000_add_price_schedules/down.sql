@@ -0,0 +1,5 @@
+DROP TABLE scheduled_prices;
+
+ALTER TABLE prices DROP CONSTRAINT prices_active_check;
+ALTER TABLE prices DROP COLUMN active_to;
+ALTER TABLE prices DROP COLUMN active_from;
@@ -0,0 +1,15 @@
+ALTER TABLE prices ADD COLUMN active_from DATE;
+ALTER TABLE prices ADD COLUMN active_to DATE;
+ALTER TABLE prices ADD CONSTRAINT prices_active_check CHECK (active_to >= active_from);
+
+CREATE TABLE scheduled_prices (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  price_product_id INTEGER NOT NULL REFERENCES prices_products(id) ON DELETE CASCADE,
+  amount INTEGER NOT NULL,
+  starts_on DATE NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  applied_at TIMESTAMP,
+  UNIQUE (price_product_id, starts_on)
+);
+CREATE INDEX scheduled_prices_due_idx ON scheduled_prices (starts_on) WHERE applied_at IS NULL;
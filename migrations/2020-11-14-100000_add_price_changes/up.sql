@@ -0,0 +1,10 @@
+CREATE TABLE price_changes (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  price_id INTEGER NOT NULL REFERENCES prices(id) ON DELETE CASCADE,
+  product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  old_amount INTEGER,
+  new_amount INTEGER,
+  changed_at TIMESTAMP NOT NULL
+);
+CREATE INDEX price_changes_product_id_idx ON price_changes (product_id, price_id, changed_at);
@@ -0,0 +1,6 @@
+DROP TABLE price_tiers;
+DROP TABLE customers;
+DROP TABLE customer_groups;
+
+DROP INDEX prices_default_idx;
+ALTER TABLE prices DROP COLUMN is_default;
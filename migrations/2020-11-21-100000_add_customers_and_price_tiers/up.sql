@@ -0,0 +1,30 @@
+ALTER TABLE prices ADD COLUMN is_default BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE UNIQUE INDEX prices_default_idx ON prices (user_id) WHERE is_default;
+
+CREATE TABLE customer_groups (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  price_id INTEGER REFERENCES prices(id) ON DELETE SET NULL
+);
+CREATE INDEX customer_groups_user_id_idx ON customer_groups (user_id);
+
+CREATE TABLE customers (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  email VARCHAR,
+  customer_group_id INTEGER REFERENCES customer_groups(id) ON DELETE SET NULL,
+  price_id INTEGER REFERENCES prices(id) ON DELETE SET NULL
+);
+CREATE INDEX customers_user_id_idx ON customers (user_id);
+
+CREATE TABLE price_tiers (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  price_product_id INTEGER NOT NULL REFERENCES prices_products(id) ON DELETE CASCADE,
+  min_quantity FLOAT NOT NULL,
+  amount INTEGER NOT NULL,
+  UNIQUE (price_product_id, min_quantity),
+  CHECK (min_quantity > 0)
+);
@@ -0,0 +1,7 @@
+ALTER TABLE sales ADD COLUMN created_at TIMESTAMP;
+UPDATE sales SET created_at = COALESCE(
+  (SELECT MIN(created_at) FROM sale_state_transitions WHERE sale_id = sales.id),
+  sale_date::timestamp
+);
+ALTER TABLE sales ALTER COLUMN created_at SET NOT NULL;
+ALTER TABLE sales ALTER COLUMN created_at SET DEFAULT LOCALTIMESTAMP;
@@ -0,0 +1 @@
+DROP FUNCTION product_available_stock(INTEGER);
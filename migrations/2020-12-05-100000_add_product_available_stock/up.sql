@@ -0,0 +1,24 @@
+-- Kits have no stock of their own, as many can be assembled as the scarcest
+-- component allows. Other products just report their stock.
+CREATE FUNCTION product_available_stock(INTEGER) RETURNS FLOAT8 AS $$
+  WITH RECURSIVE tree AS (
+    SELECT $1 AS product_id, 1::float8 AS quantity
+    UNION ALL
+    SELECT product_components.component_id, tree.quantity * product_components.quantity
+    FROM product_components
+    INNER JOIN tree ON product_components.kit_id = tree.product_id
+  ), leaves AS (
+    SELECT product_id, SUM(quantity) AS quantity FROM tree
+    WHERE NOT EXISTS (
+      SELECT 1 FROM product_components WHERE product_components.kit_id = tree.product_id
+    )
+    GROUP BY product_id
+  )
+  SELECT CASE
+    WHEN EXISTS (SELECT 1 FROM product_components WHERE product_components.kit_id = $1)
+      THEN COALESCE(MIN(GREATEST(FLOOR(products.stock / leaves.quantity), 0)), 0)
+    ELSE (SELECT stock FROM products WHERE id = $1)
+  END
+  FROM leaves
+  INNER JOIN products ON products.id = leaves.product_id
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+UPDATE serial_numbers SET sale_product_id = sale_products.id
+FROM sale_products
+INNER JOIN sales ON sales.id = sale_products.sale_id
+WHERE sales.state = 'draft'
+  AND serial_numbers.product_id = sale_products.product_id
+  AND serial_numbers.serial = ANY(sale_products.serials);
+ALTER TABLE sale_products DROP COLUMN serials;
+ALTER TABLE serial_numbers DROP COLUMN location_id;
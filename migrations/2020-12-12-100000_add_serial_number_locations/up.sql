@@ -0,0 +1,15 @@
+ALTER TABLE serial_numbers ADD COLUMN location_id INTEGER REFERENCES locations(id) ON DELETE SET NULL;
+
+-- Draft lines keep the serials they ask for, they are assigned on approval
+ALTER TABLE sale_products ADD COLUMN serials VARCHAR[];
+UPDATE sale_products SET serials = (
+  SELECT array_agg(serial ORDER BY serial) FROM serial_numbers
+  WHERE serial_numbers.sale_product_id = sale_products.id
+)
+WHERE EXISTS (SELECT 1 FROM serial_numbers WHERE serial_numbers.sale_product_id = sale_products.id);
+UPDATE serial_numbers SET sale_product_id = NULL
+WHERE sale_product_id IN (
+  SELECT sale_products.id FROM sale_products
+  INNER JOIN sales ON sales.id = sale_products.sale_id
+  WHERE sales.state = 'draft'
+);
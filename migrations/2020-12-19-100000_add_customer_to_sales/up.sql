@@ -0,0 +1,2 @@
+ALTER TABLE sales ADD COLUMN customer_id INTEGER REFERENCES customers(id) ON DELETE SET NULL;
+CREATE INDEX sales_customer_id_idx ON sales (customer_id);
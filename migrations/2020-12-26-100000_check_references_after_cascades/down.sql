@@ -0,0 +1,16 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE sale_products DROP CONSTRAINT sale_products_product_id_fkey;
+ALTER TABLE sale_products ADD CONSTRAINT sale_products_product_id_fkey
+  FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE RESTRICT;
+ALTER TABLE transfers DROP CONSTRAINT transfers_source_location_id_fkey;
+ALTER TABLE transfers ADD CONSTRAINT transfers_source_location_id_fkey
+  FOREIGN KEY (source_location_id) REFERENCES locations(id) ON DELETE RESTRICT;
+ALTER TABLE transfers DROP CONSTRAINT transfers_destination_location_id_fkey;
+ALTER TABLE transfers ADD CONSTRAINT transfers_destination_location_id_fkey
+  FOREIGN KEY (destination_location_id) REFERENCES locations(id) ON DELETE RESTRICT;
+ALTER TABLE transfer_products DROP CONSTRAINT transfer_products_product_id_fkey;
+ALTER TABLE transfer_products ADD CONSTRAINT transfer_products_product_id_fkey
+  FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE RESTRICT;
+ALTER TABLE transfer_products DROP CONSTRAINT transfer_products_lot_id_fkey;
+ALTER TABLE transfer_products ADD CONSTRAINT transfer_products_lot_id_fkey
+  FOREIGN KEY (lot_id) REFERENCES lots(id) ON DELETE RESTRICT;
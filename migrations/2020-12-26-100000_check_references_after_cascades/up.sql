@@ -0,0 +1,19 @@
+-- These references were checked as soon as a row was deleted, before the
+-- cascades deleting its referencing rows had run, so deleting an account
+-- failed once it had sales or transfers. They are checked at commit now,
+-- deleting a sold product or a location alone is still refused.
+ALTER TABLE sale_products DROP CONSTRAINT sale_products_product_id_fkey;
+ALTER TABLE sale_products ADD CONSTRAINT sale_products_product_id_fkey
+  FOREIGN KEY (product_id) REFERENCES products(id) DEFERRABLE INITIALLY DEFERRED;
+ALTER TABLE transfers DROP CONSTRAINT transfers_source_location_id_fkey;
+ALTER TABLE transfers ADD CONSTRAINT transfers_source_location_id_fkey
+  FOREIGN KEY (source_location_id) REFERENCES locations(id) DEFERRABLE INITIALLY DEFERRED;
+ALTER TABLE transfers DROP CONSTRAINT transfers_destination_location_id_fkey;
+ALTER TABLE transfers ADD CONSTRAINT transfers_destination_location_id_fkey
+  FOREIGN KEY (destination_location_id) REFERENCES locations(id) DEFERRABLE INITIALLY DEFERRED;
+ALTER TABLE transfer_products DROP CONSTRAINT transfer_products_product_id_fkey;
+ALTER TABLE transfer_products ADD CONSTRAINT transfer_products_product_id_fkey
+  FOREIGN KEY (product_id) REFERENCES products(id) DEFERRABLE INITIALLY DEFERRED;
+ALTER TABLE transfer_products DROP CONSTRAINT transfer_products_lot_id_fkey;
+ALTER TABLE transfer_products ADD CONSTRAINT transfer_products_lot_id_fkey
+  FOREIGN KEY (lot_id) REFERENCES lots(id) DEFERRABLE INITIALLY DEFERRED;
@@ -0,0 +1,34 @@
+//! Grants the administrator role to an account, administrators can unlock
+//! the accounts of their company. `--revoke` takes it away. The database
+//! comes from the environment or `.env`.
+use std::env;
+use std::process;
+
+use ::mystore_lib::db_connection::establish_connection;
+use ::mystore_lib::models::user::User;
+
+fn main() {
+    dotenv::dotenv().ok();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (email, admin) = match args.as_slice() {
+        [email] => (email, true),
+        [email, flag] if flag == "--revoke" => (email, false),
+        _ => exit_with("Usage: grant_admin <email> [--revoke]"),
+    };
+
+    let connection = establish_connection()
+        .get()
+        .unwrap_or_else(|error| exit_with(&error.to_string()));
+
+    match User::set_admin(email, admin, &connection) {
+        Ok(user) if user.admin => println!("{} is now an administrator", user.email),
+        Ok(user) => println!("{} is no longer an administrator", user.email),
+        Err(error) => exit_with(&error.to_string()),
+    }
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
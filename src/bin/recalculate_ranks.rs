@@ -0,0 +1,37 @@
+//! Recalculates `product_rank` from recent sales, see
+//! `mystore_lib::models::product_rank`. Settings come from the
+//! environment or `.env`; `--user-id <id>` limits it to one company.
+use std::env;
+use std::process;
+
+use ::mystore_lib::db_connection::establish_connection;
+use ::mystore_lib::models::product_rank::{recalculate, RankSettings};
+
+fn main() {
+    dotenv::dotenv().ok();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let user_id = match args.as_slice() {
+        [] => None,
+        [flag, value] if flag == "--user-id" => match value.parse::<i32>() {
+            Ok(user_id) => Some(user_id),
+            Err(_) => exit_with(&format!("Invalid user id: {}", value)),
+        },
+        _ => exit_with("Usage: recalculate_ranks [--user-id <id>]"),
+    };
+
+    let settings = RankSettings::from_env().unwrap_or_else(|error| exit_with(&error));
+    let connection = establish_connection()
+        .get()
+        .unwrap_or_else(|error| exit_with(&error.to_string()));
+
+    match recalculate(&connection, &settings, user_id) {
+        Ok(updated) => println!("Recalculated the rank of {} products", updated),
+        Err(error) => exit_with(&error.to_string()),
+    }
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
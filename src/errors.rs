@@ -1,5 +1,6 @@
 use std::fmt;
 use bcrypt::BcryptError;
+use chrono::NaiveDateTime;
 use diesel::result;
 
 #[derive(Debug)]
@@ -8,6 +9,10 @@ pub enum MyStoreError {
     DBError(result::Error),
     PasswordNotMatch(String),
     WrongPassword(String),
+    AccountLocked(NaiveDateTime),
+    TooManyAttempts(String),
+    Unauthorized(String),
+    TwoFactorError(String),
     PGConnectionError
 }
 
@@ -30,6 +35,12 @@ impl fmt::Display for MyStoreError {
             MyStoreError::DBError(error) => write!(f, "{}", error),
             MyStoreError::PasswordNotMatch(error) => write!(f, "{}", error),
             MyStoreError::WrongPassword(error) => write!(f, "{}", error),
+            MyStoreError::AccountLocked(until) => {
+                write!(f, "Account temporarily locked until {}", until)
+            }
+            MyStoreError::TooManyAttempts(error) => write!(f, "{}", error),
+            MyStoreError::Unauthorized(error) => write!(f, "{}", error),
+            MyStoreError::TwoFactorError(error) => write!(f, "{}", error),
             MyStoreError::PGConnectionError => write!(f, "error obtaining a db connection")
         }
     }
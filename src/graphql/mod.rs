@@ -9,8 +9,8 @@ use schema::Schema;
 use std::sync::Arc;
 
 use crate::db_connection::PgPool;
-use crate::handlers::LoggedUser;
-use crate::models::create_context;
+use crate::handlers::GraphqlUser;
+use crate::models::{create_context, Context};
 use crate::serde::ser::Error as SerdeError;
 
 #[get("/graphiql")]
@@ -25,13 +25,16 @@ pub async fn graphiql() -> HttpResponse {
 pub async fn graphql(
     st: web::Data<Arc<Schema>>,
     data: web::Json<GraphQLRequest>,
-    user: LoggedUser,
+    GraphqlUser(user): GraphqlUser,
     pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, Error> {
     let user = web::block(move || {
         let pg_pool = pool.get().map_err(|e| serde_json::Error::custom(e))?;
 
-        let ctx = create_context(user.id, pg_pool);
+        let ctx = Context {
+            scopes: user.scopes,
+            ..create_context(user.id, pg_pool)
+        };
 
         let res = data.execute(&st, &ctx);
         Ok::<_, serde_json::error::Error>(serde_json::to_string(&res)?)
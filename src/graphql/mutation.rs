@@ -1,9 +1,31 @@
+use crate::models::api_key::{ApiKey, CreatedApiKey, FormApiKey, WRITE_SCOPE};
+use crate::models::attachment::Attachment;
+use crate::models::category::{Category, FormCategory};
+use crate::models::customer::{Customer, CustomerGroup, FormCustomer, FormCustomerGroup};
+use crate::models::location::{FormLocation, FormProductStockThresholds, Location, ProductStock};
+use crate::models::lot::{FormLot, Lot};
 use crate::models::price::FormPriceProductsToUpdate;
 use crate::models::price::{FormPrice, Price};
+use crate::models::price_tier::{FormPriceTiers, PriceTier};
 use crate::models::product::{FormProduct, FullProduct, Product};
+use crate::models::product_barcode::{FormProductBarcode, ProductBarcode};
+use crate::models::product_component::{FormProductComponent, ProductComponent};
+use crate::models::product_variant::{FormProductOption, ProductOption};
 use crate::models::sale::{FormSale, FullSale, Sale};
 use crate::models::sale_product::FormSaleProducts;
 use crate::models::sale_state::Event;
+use crate::models::scheduled_price::{FormScheduledPrice, ScheduledPrice};
+use crate::models::search_config::SearchConfig;
+use crate::models::serial_number::SerialNumber;
+use crate::models::stock_count::{FormStockCountLine, FullStockCount, StockCount, StockCountLine};
+use crate::models::stock_movement::{
+    AdjustmentReason, FormStockAdjustment, FormStockReceipt, StockMovement,
+};
+use crate::models::supplier::{FormSupplier, Supplier};
+use crate::models::transfer::{
+    FormReceivedTransferProduct, FormTransfer, FormTransferProduct, FullTransfer, Transfer,
+};
+use crate::models::unit::{FormProductUnit, FormUnit, ProductUnit, Unit};
 use crate::models::Context;
 use juniper::FieldResult;
 
@@ -18,6 +40,7 @@ impl Mutation {
         form: FormSale,
         form_sale_products: FormSaleProducts,
     ) -> FieldResult<FullSale> {
+        context.require_scope(WRITE_SCOPE)?;
         Sale::create(context, form, form_sale_products)
     }
 
@@ -26,29 +49,35 @@ impl Mutation {
         form: FormSale,
         form_sale_products: FormSaleProducts,
     ) -> FieldResult<FullSale> {
+        context.require_scope(WRITE_SCOPE)?;
         Sale::update(context, form, form_sale_products)
     }
 
     fn approveSale(context: &Context, sale_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
         Sale::set_state(context, sale_id, Event::Approve)
     }
 
     fn cancelSale(context: &Context, sale_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
         //TODO: perform credit note or debit note
         Sale::set_state(context, sale_id, Event::Cancel)
     }
 
     fn paySale(context: &Context, sale_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
         //TODO: perform collection
         Sale::set_state(context, sale_id, Event::Pay)
     }
 
     fn partiallyPaySale(context: &Context, sale_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
         //TODO: perform collection
         Sale::set_state(context, sale_id, Event::PartiallyPay)
     }
 
     fn destroySale(context: &Context, sale_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
         Sale::destroy(context, sale_id)
     }
 
@@ -57,6 +86,7 @@ impl Mutation {
         form: FormProduct,
         form_price_products: FormPriceProductsToUpdate,
     ) -> FieldResult<FullProduct> {
+        context.require_scope(WRITE_SCOPE)?;
         Product::create(context, form, form_price_products)
     }
 
@@ -65,22 +95,371 @@ impl Mutation {
         form: FormProduct,
         form_price_products: FormPriceProductsToUpdate,
     ) -> FieldResult<FullProduct> {
+        context.require_scope(WRITE_SCOPE)?;
         Product::update(context, form, form_price_products)
     }
 
     fn destroyProduct(context: &Context, product_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
         Product::destroy(context, product_id)
     }
 
+    fn archiveProduct(context: &Context, product_id: i32) -> FieldResult<FullProduct> {
+        context.require_scope(WRITE_SCOPE)?;
+        Product::archive(context, product_id)
+    }
+
+    fn unarchiveProduct(context: &Context, product_id: i32) -> FieldResult<FullProduct> {
+        context.require_scope(WRITE_SCOPE)?;
+        Product::unarchive(context, product_id)
+    }
+
+    fn createProductBarcode(
+        context: &Context,
+        form: FormProductBarcode,
+    ) -> FieldResult<ProductBarcode> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductBarcode::create(context, form)
+    }
+
+    fn destroyProductBarcode(context: &Context, product_barcode_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductBarcode::destroy(context, product_barcode_id)
+    }
+
+    fn createProductComponent(
+        context: &Context,
+        form: FormProductComponent,
+    ) -> FieldResult<ProductComponent> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductComponent::create(context, form)
+    }
+
+    fn updateProductComponent(
+        context: &Context,
+        form: FormProductComponent,
+    ) -> FieldResult<ProductComponent> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductComponent::update(context, form)
+    }
+
+    fn destroyProductComponent(context: &Context, product_component_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductComponent::destroy(context, product_component_id)
+    }
+
+    fn createUnit(context: &Context, form: FormUnit) -> FieldResult<Unit> {
+        context.require_scope(WRITE_SCOPE)?;
+        Unit::create(context, form)
+    }
+
+    fn updateUnit(context: &Context, form: FormUnit) -> FieldResult<Unit> {
+        context.require_scope(WRITE_SCOPE)?;
+        Unit::update(context, form)
+    }
+
+    fn destroyUnit(context: &Context, unit_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        Unit::destroy(context, unit_id)
+    }
+
+    fn createProductUnit(context: &Context, form: FormProductUnit) -> FieldResult<ProductUnit> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductUnit::create(context, form)
+    }
+
+    fn updateProductUnit(context: &Context, form: FormProductUnit) -> FieldResult<ProductUnit> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductUnit::update(context, form)
+    }
+
+    fn destroyProductUnit(context: &Context, product_unit_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductUnit::destroy(context, product_unit_id)
+    }
+
+    fn createLocation(context: &Context, form: FormLocation) -> FieldResult<Location> {
+        context.require_scope(WRITE_SCOPE)?;
+        Location::create(context, form)
+    }
+
+    fn updateLocation(context: &Context, form: FormLocation) -> FieldResult<Location> {
+        context.require_scope(WRITE_SCOPE)?;
+        Location::update(context, form)
+    }
+
+    fn destroyLocation(context: &Context, location_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        Location::destroy(context, location_id)
+    }
+
+    fn updateSearchConfig(context: &Context, name: String) -> FieldResult<SearchConfig> {
+        context.require_scope(WRITE_SCOPE)?;
+        SearchConfig::update(context, name)
+    }
+
+    fn updateProductStockThresholds(
+        context: &Context,
+        form: FormProductStockThresholds,
+    ) -> FieldResult<ProductStock> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductStock::update_thresholds(context, form)
+    }
+
+    fn createSupplier(context: &Context, form: FormSupplier) -> FieldResult<Supplier> {
+        context.require_scope(WRITE_SCOPE)?;
+        Supplier::create(context, form)
+    }
+
+    fn updateSupplier(context: &Context, form: FormSupplier) -> FieldResult<Supplier> {
+        context.require_scope(WRITE_SCOPE)?;
+        Supplier::update(context, form)
+    }
+
+    fn destroySupplier(context: &Context, supplier_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        Supplier::destroy(context, supplier_id)
+    }
+
+    fn createCustomer(context: &Context, form: FormCustomer) -> FieldResult<Customer> {
+        context.require_scope(WRITE_SCOPE)?;
+        Customer::create(context, form)
+    }
+
+    fn updateCustomer(context: &Context, form: FormCustomer) -> FieldResult<Customer> {
+        context.require_scope(WRITE_SCOPE)?;
+        Customer::update(context, form)
+    }
+
+    fn destroyCustomer(context: &Context, customer_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        Customer::destroy(context, customer_id)
+    }
+
+    fn createCustomerGroup(
+        context: &Context,
+        form: FormCustomerGroup,
+    ) -> FieldResult<CustomerGroup> {
+        context.require_scope(WRITE_SCOPE)?;
+        CustomerGroup::create(context, form)
+    }
+
+    fn updateCustomerGroup(
+        context: &Context,
+        form: FormCustomerGroup,
+    ) -> FieldResult<CustomerGroup> {
+        context.require_scope(WRITE_SCOPE)?;
+        CustomerGroup::update(context, form)
+    }
+
+    fn destroyCustomerGroup(context: &Context, customer_group_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        CustomerGroup::destroy(context, customer_group_id)
+    }
+
+    fn destroyAttachment(context: &Context, attachment_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        Attachment::destroy(context, attachment_id)
+    }
+
+    fn adjustStock(context: &Context, form: FormStockAdjustment) -> FieldResult<StockMovement> {
+        context.require_scope(WRITE_SCOPE)?;
+        StockMovement::adjust(context, form)
+    }
+
+    fn receiveStock(context: &Context, form: FormStockReceipt) -> FieldResult<StockMovement> {
+        context.require_scope(WRITE_SCOPE)?;
+        StockMovement::receive(context, form)
+    }
+
+    fn createStockCount(
+        context: &Context,
+        location_id: Option<i32>,
+        product_ids: Option<Vec<i32>>,
+    ) -> FieldResult<FullStockCount> {
+        context.require_scope(WRITE_SCOPE)?;
+        StockCount::create(context, location_id, product_ids)
+    }
+
+    fn countStock(context: &Context, form: FormStockCountLine) -> FieldResult<StockCountLine> {
+        context.require_scope(WRITE_SCOPE)?;
+        StockCount::count(context, form)
+    }
+
+    fn confirmStockCount(context: &Context, stock_count_id: i32) -> FieldResult<FullStockCount> {
+        context.require_scope(WRITE_SCOPE)?;
+        StockCount::confirm(context, stock_count_id)
+    }
+
+    fn cancelStockCount(context: &Context, stock_count_id: i32) -> FieldResult<FullStockCount> {
+        context.require_scope(WRITE_SCOPE)?;
+        StockCount::cancel(context, stock_count_id)
+    }
+
+    fn createTransfer(
+        context: &Context,
+        form: FormTransfer,
+        lines: Vec<FormTransferProduct>,
+    ) -> FieldResult<FullTransfer> {
+        context.require_scope(WRITE_SCOPE)?;
+        Transfer::create(context, form, lines)
+    }
+
+    fn updateTransfer(
+        context: &Context,
+        form: FormTransfer,
+        lines: Vec<FormTransferProduct>,
+    ) -> FieldResult<FullTransfer> {
+        context.require_scope(WRITE_SCOPE)?;
+        Transfer::update(context, form, lines)
+    }
+
+    fn destroyTransfer(context: &Context, transfer_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        Transfer::destroy(context, transfer_id)
+    }
+
+    fn sendTransfer(context: &Context, transfer_id: i32) -> FieldResult<FullTransfer> {
+        context.require_scope(WRITE_SCOPE)?;
+        Transfer::send(context, transfer_id)
+    }
+
+    fn receiveTransfer(
+        context: &Context,
+        transfer_id: i32,
+        received: Vec<FormReceivedTransferProduct>,
+    ) -> FieldResult<FullTransfer> {
+        context.require_scope(WRITE_SCOPE)?;
+        Transfer::receive(context, transfer_id, received)
+    }
+
+    fn createLot(context: &Context, form: FormLot) -> FieldResult<Lot> {
+        context.require_scope(WRITE_SCOPE)?;
+        Lot::create(context, form)
+    }
+
+    fn updateLot(context: &Context, form: FormLot) -> FieldResult<Lot> {
+        context.require_scope(WRITE_SCOPE)?;
+        Lot::update(context, form)
+    }
+
+    fn destroyLot(context: &Context, lot_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        Lot::destroy(context, lot_id)
+    }
+
+    fn receiveSerialNumbers(
+        context: &Context,
+        product_id: i32,
+        location_id: Option<i32>,
+        serials: Vec<String>,
+    ) -> FieldResult<Vec<SerialNumber>> {
+        context.require_scope(WRITE_SCOPE)?;
+        SerialNumber::receive(context, product_id, location_id, serials)
+    }
+
+    fn destroySerialNumber(
+        context: &Context,
+        serial_number_id: i32,
+        reason: AdjustmentReason,
+    ) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        SerialNumber::destroy(context, serial_number_id, reason)
+    }
+
+    fn createProductOption(
+        context: &Context,
+        form: FormProductOption,
+    ) -> FieldResult<ProductOption> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductOption::create(context, form)
+    }
+
+    fn updateProductOption(
+        context: &Context,
+        form: FormProductOption,
+    ) -> FieldResult<ProductOption> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductOption::update(context, form)
+    }
+
+    fn destroyProductOption(context: &Context, product_option_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductOption::destroy(context, product_option_id)
+    }
+
+    fn generateProductVariants(
+        context: &Context,
+        product_id: i32,
+    ) -> FieldResult<Vec<FullProduct>> {
+        context.require_scope(WRITE_SCOPE)?;
+        ProductOption::generate_variants(context, product_id)
+    }
+
     fn createPrice(context: &Context, form: FormPrice) -> FieldResult<Price> {
+        context.require_scope(WRITE_SCOPE)?;
         Price::create(context, form)
     }
 
     fn updatePrice(context: &Context, form: FormPrice) -> FieldResult<Price> {
+        context.require_scope(WRITE_SCOPE)?;
         Price::update(context, form)
     }
 
     fn destroyPrice(context: &Context, price_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
         Price::destroy(context, price_id)
     }
+
+    fn archivePrice(context: &Context, price_id: i32) -> FieldResult<Price> {
+        context.require_scope(WRITE_SCOPE)?;
+        Price::archive(context, price_id)
+    }
+
+    fn unarchivePrice(context: &Context, price_id: i32) -> FieldResult<Price> {
+        context.require_scope(WRITE_SCOPE)?;
+        Price::unarchive(context, price_id)
+    }
+
+    fn schedulePrice(context: &Context, form: FormScheduledPrice) -> FieldResult<ScheduledPrice> {
+        context.require_scope(WRITE_SCOPE)?;
+        ScheduledPrice::create(context, form)
+    }
+
+    fn cancelScheduledPrice(context: &Context, scheduled_price_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        ScheduledPrice::cancel(context, scheduled_price_id)
+    }
+
+    fn setPriceTiers(context: &Context, form: FormPriceTiers) -> FieldResult<Vec<PriceTier>> {
+        context.require_scope(WRITE_SCOPE)?;
+        PriceTier::set_tiers(context, form)
+    }
+
+    fn createCategory(context: &Context, form: FormCategory) -> FieldResult<Category> {
+        context.require_scope(WRITE_SCOPE)?;
+        Category::create(context, form)
+    }
+
+    fn updateCategory(
+        context: &Context,
+        form: FormCategory,
+        to_top_level: Option<bool>,
+    ) -> FieldResult<Category> {
+        context.require_scope(WRITE_SCOPE)?;
+        Category::update(context, form, to_top_level.unwrap_or(false))
+    }
+
+    fn destroyCategory(context: &Context, category_id: i32) -> FieldResult<bool> {
+        context.require_scope(WRITE_SCOPE)?;
+        Category::destroy(context, category_id)
+    }
+
+    fn createApiKey(context: &Context, form: FormApiKey) -> FieldResult<CreatedApiKey> {
+        ApiKey::create(context, form)
+    }
+
+    fn revokeApiKey(context: &Context, api_key_id: i32) -> FieldResult<bool> {
+        ApiKey::revoke(context, api_key_id)
+    }
 }
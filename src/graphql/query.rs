@@ -1,7 +1,35 @@
+use crate::models::api_key::{ApiKey, ListApiKey, READ_SCOPE};
+use crate::models::attachment::{Attachment, ListAttachment};
+use crate::models::audit_event::{AuditEvent, ListAuditEvent, SearchAuditEvent};
+use crate::models::category::{Category, CategorySales, ListCategory};
+use crate::models::customer::{Customer, CustomerGroup, ListCustomer, ListCustomerGroup};
+use crate::models::location::{ListLocation, Location, ProductStock};
+use crate::models::lot::{FullLot, ListLot, Lot};
 use crate::models::price::{Price, ListPrice};
-use crate::models::product::{FullProduct, ListProduct, Product};
+use crate::models::price_change::PriceChange;
+use crate::models::price_tier::{PriceTier, ResolvedPrice};
+use crate::models::product::{FullProduct, ListProduct, Product, ProductSales, ProductSuggestion};
+use crate::models::product_barcode::{ListProductBarcode, ProductBarcode};
+use crate::models::product_component::{ListProductComponent, ProductComponent};
+use crate::models::product_search::ProductFilter;
+use crate::models::product_variant::{ListProductOption, ProductOption, ProductVariantValue};
+use crate::models::reorder::{LowStockProduct, ReorderSuggestion, SupplierReorder};
 use crate::models::sale::{FormSale, FullSale, ListSale, Sale};
+use crate::models::sale_state::SaleState;
+use crate::models::sale_state_transition::{SaleStateTransition, StateDurationReport};
+use crate::models::search_config::SearchConfig;
+use crate::models::serial_number::{ListSerialNumber, SerialNumber, SerialNumberLocation};
+use crate::models::stock_count::{
+    FullStockCount, ListStockCount, StockCount, StockCountState, StockCountVariance,
+};
+use crate::models::stock_movement::StockMovement;
+use crate::models::supplier::{ListSupplier, Supplier};
+use crate::models::transfer::{
+    FullTransfer, InTransitStock, ListTransfer, Transfer, TransferDiscrepancy, TransferState,
+};
+use crate::models::unit::{ListProductUnit, ListUnit, ProductUnit, Unit};
 use crate::models::Context;
+use chrono::NaiveDate;
 use juniper::FieldResult;
 
 pub struct Query;
@@ -11,35 +39,348 @@ pub struct Query;
 )]
 impl Query {
     fn dashboard(context: &Context) -> FieldResult<String> {
+        context.require_scope(READ_SCOPE)?;
         Ok("DashBoard".to_string())
     }
 
     fn listSale(context: &Context, search: Option<FormSale>, limit: i32) -> FieldResult<ListSale> {
+        context.require_scope(READ_SCOPE)?;
         Sale::list(context, search, limit)
     }
 
     fn showSale(context: &Context, sale_id: i32) -> FieldResult<FullSale> {
+        context.require_scope(READ_SCOPE)?;
         Sale::show(context, sale_id)
     }
 
+    fn saleStateDuration(
+        context: &Context,
+        from_state: SaleState,
+        to_state: SaleState,
+    ) -> FieldResult<StateDurationReport> {
+        context.require_scope(READ_SCOPE)?;
+        SaleStateTransition::average_duration(context, from_state, to_state)
+    }
+
     fn listProduct(
         context: &Context,
         search: String,
         limit: i32,
         rank: f64,
+        category_id: Option<i32>,
+        filter: Option<ProductFilter>,
     ) -> FieldResult<ListProduct> {
-        Product::list(context, search, limit, rank)
+        context.require_scope(READ_SCOPE)?;
+        Product::list(context, search, limit, rank, category_id, filter)
     }
 
     fn showProduct(context: &Context, product_id: i32) -> FieldResult<FullProduct> {
+        context.require_scope(READ_SCOPE)?;
         Product::show(context, product_id)
     }
 
-    fn ListPrice(context: &Context) -> FieldResult<ListPrice> {
-        Price::list(context)
+    fn suggestProducts(
+        context: &Context,
+        prefix: String,
+        limit: i32,
+    ) -> FieldResult<Vec<ProductSuggestion>> {
+        context.require_scope(READ_SCOPE)?;
+        Product::suggest(context, prefix, limit)
+    }
+
+    fn productByBarcode(context: &Context, code: String) -> FieldResult<FullProduct> {
+        context.require_scope(READ_SCOPE)?;
+        Product::find_by_barcode(context, code)
+    }
+
+    fn listProductBarcode(context: &Context, product_id: i32) -> FieldResult<ListProductBarcode> {
+        context.require_scope(READ_SCOPE)?;
+        ProductBarcode::list(context, product_id)
+    }
+
+    fn listProductComponent(context: &Context, kit_id: i32) -> FieldResult<ListProductComponent> {
+        context.require_scope(READ_SCOPE)?;
+        ProductComponent::list(context, kit_id)
+    }
+
+    fn availableStock(context: &Context, product_id: i32) -> FieldResult<f64> {
+        context.require_scope(READ_SCOPE)?;
+        ProductComponent::available_stock(context, product_id)
+    }
+
+    fn listUnit(context: &Context) -> FieldResult<ListUnit> {
+        context.require_scope(READ_SCOPE)?;
+        Unit::list(context)
+    }
+
+    fn listProductUnit(context: &Context, product_id: i32) -> FieldResult<ListProductUnit> {
+        context.require_scope(READ_SCOPE)?;
+        ProductUnit::list(context, product_id)
+    }
+
+    fn convertUnits(
+        context: &Context,
+        product_id: i32,
+        amount: f64,
+        from_unit_id: Option<i32>,
+        to_unit_id: Option<i32>,
+    ) -> FieldResult<f64> {
+        context.require_scope(READ_SCOPE)?;
+        ProductUnit::convert(context, product_id, amount, from_unit_id, to_unit_id)
+    }
+
+    fn listLocation(context: &Context) -> FieldResult<ListLocation> {
+        context.require_scope(READ_SCOPE)?;
+        Location::list(context)
+    }
+
+    fn locationStock(context: &Context, location_id: i32) -> FieldResult<Vec<ProductStock>> {
+        context.require_scope(READ_SCOPE)?;
+        ProductStock::list(context, location_id)
+    }
+
+    fn listStockMovement(
+        context: &Context,
+        product_id: i32,
+        limit: i32,
+    ) -> FieldResult<Vec<StockMovement>> {
+        context.require_scope(READ_SCOPE)?;
+        StockMovement::list(context, product_id, limit)
+    }
+
+    fn searchConfig(context: &Context) -> FieldResult<SearchConfig> {
+        context.require_scope(READ_SCOPE)?;
+        SearchConfig::show(context)
+    }
+
+    fn listSearchConfig(context: &Context) -> FieldResult<Vec<SearchConfig>> {
+        context.require_scope(READ_SCOPE)?;
+        SearchConfig::list(context)
+    }
+
+    fn listAttachment(
+        context: &Context,
+        product_id: Option<i32>,
+        sale_id: Option<i32>,
+    ) -> FieldResult<ListAttachment> {
+        context.require_scope(READ_SCOPE)?;
+        Attachment::list(context, product_id, sale_id)
+    }
+
+    fn listSupplier(context: &Context) -> FieldResult<ListSupplier> {
+        context.require_scope(READ_SCOPE)?;
+        Supplier::list(context)
+    }
+
+    fn listCustomer(context: &Context) -> FieldResult<ListCustomer> {
+        context.require_scope(READ_SCOPE)?;
+        Customer::list(context)
+    }
+
+    fn listCustomerGroup(context: &Context) -> FieldResult<ListCustomerGroup> {
+        context.require_scope(READ_SCOPE)?;
+        CustomerGroup::list(context)
+    }
+
+    fn lowStockProducts(
+        context: &Context,
+        location_id: Option<i32>,
+    ) -> FieldResult<Vec<LowStockProduct>> {
+        context.require_scope(READ_SCOPE)?;
+        LowStockProduct::list(context, location_id)
+    }
+
+    fn reorderSuggestions(
+        context: &Context,
+        location_id: Option<i32>,
+        days: i32,
+    ) -> FieldResult<Vec<SupplierReorder>> {
+        context.require_scope(READ_SCOPE)?;
+        ReorderSuggestion::list(context, location_id, days)
+    }
+
+    fn listStockCount(
+        context: &Context,
+        state: Option<StockCountState>,
+        limit: i32,
+    ) -> FieldResult<ListStockCount> {
+        context.require_scope(READ_SCOPE)?;
+        StockCount::list(context, state, limit)
+    }
+
+    fn showStockCount(context: &Context, stock_count_id: i32) -> FieldResult<FullStockCount> {
+        context.require_scope(READ_SCOPE)?;
+        StockCount::show(context, stock_count_id)
+    }
+
+    fn stockCountVariance(
+        context: &Context,
+        stock_count_id: i32,
+    ) -> FieldResult<Vec<StockCountVariance>> {
+        context.require_scope(READ_SCOPE)?;
+        StockCount::variance(context, stock_count_id)
+    }
+
+    fn listTransfer(
+        context: &Context,
+        state: Option<TransferState>,
+        limit: i32,
+    ) -> FieldResult<ListTransfer> {
+        context.require_scope(READ_SCOPE)?;
+        Transfer::list(context, state, limit)
+    }
+
+    fn showTransfer(context: &Context, transfer_id: i32) -> FieldResult<FullTransfer> {
+        context.require_scope(READ_SCOPE)?;
+        Transfer::show(context, transfer_id)
+    }
+
+    fn inTransitStock(
+        context: &Context,
+        location_id: Option<i32>,
+    ) -> FieldResult<Vec<InTransitStock>> {
+        context.require_scope(READ_SCOPE)?;
+        Transfer::in_transit(context, location_id)
+    }
+
+    fn transferDiscrepancies(
+        context: &Context,
+        limit: i32,
+    ) -> FieldResult<Vec<TransferDiscrepancy>> {
+        context.require_scope(READ_SCOPE)?;
+        Transfer::discrepancies(context, limit)
+    }
+
+    fn listLot(context: &Context, product_id: i32) -> FieldResult<ListLot> {
+        context.require_scope(READ_SCOPE)?;
+        Lot::list(context, product_id)
+    }
+
+    fn suggestLot(context: &Context, product_id: i32, amount: f64) -> FieldResult<Option<Lot>> {
+        context.require_scope(READ_SCOPE)?;
+        Lot::suggest(context, product_id, amount)
+    }
+
+    fn expiringLots(context: &Context, days: i32) -> FieldResult<Vec<FullLot>> {
+        context.require_scope(READ_SCOPE)?;
+        Lot::expiring(context, days)
+    }
+
+    fn lotSales(context: &Context, lot_id: i32) -> FieldResult<Vec<Sale>> {
+        context.require_scope(READ_SCOPE)?;
+        Lot::sales(context, lot_id)
+    }
+
+    fn listSerialNumber(
+        context: &Context,
+        product_id: i32,
+        in_stock: bool,
+    ) -> FieldResult<ListSerialNumber> {
+        context.require_scope(READ_SCOPE)?;
+        SerialNumber::list(context, product_id, in_stock)
+    }
+
+    fn locateSerialNumber(
+        context: &Context,
+        serial: String,
+    ) -> FieldResult<Vec<SerialNumberLocation>> {
+        context.require_scope(READ_SCOPE)?;
+        SerialNumber::locate(context, serial)
+    }
+
+    fn listProductOption(context: &Context, product_id: i32) -> FieldResult<ListProductOption> {
+        context.require_scope(READ_SCOPE)?;
+        ProductOption::list(context, product_id)
+    }
+
+    fn listProductVariant(context: &Context, product_id: i32) -> FieldResult<ListProduct> {
+        context.require_scope(READ_SCOPE)?;
+        Product::variants(context, product_id)
+    }
+
+    fn listProductVariantValue(
+        context: &Context,
+        variant_id: i32,
+    ) -> FieldResult<Vec<ProductVariantValue>> {
+        context.require_scope(READ_SCOPE)?;
+        ProductVariantValue::list(context, variant_id)
+    }
+
+    fn productSales(
+        context: &Context,
+        roll_up_variants: bool,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> FieldResult<Vec<ProductSales>> {
+        context.require_scope(READ_SCOPE)?;
+        Product::sales_report(context, roll_up_variants, from, to)
+    }
+
+    fn listCategory(context: &Context) -> FieldResult<ListCategory> {
+        context.require_scope(READ_SCOPE)?;
+        Category::list(context)
+    }
+
+    fn categorySales(
+        context: &Context,
+        parent_id: Option<i32>,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> FieldResult<Vec<CategorySales>> {
+        context.require_scope(READ_SCOPE)?;
+        Category::sales_report(context, parent_id, from, to)
+    }
+
+    fn ListPrice(context: &Context, archived: Option<bool>) -> FieldResult<ListPrice> {
+        context.require_scope(READ_SCOPE)?;
+        Price::list(context, archived.unwrap_or(false))
     }
 
     fn findPrice(context: &Context, price_id: i32) -> FieldResult<Price> {
+        context.require_scope(READ_SCOPE)?;
         Price::find(context, price_id)
     }
+
+    fn priceHistory(
+        context: &Context,
+        product_id: i32,
+        price_id: Option<i32>,
+    ) -> FieldResult<Vec<PriceChange>> {
+        context.require_scope(READ_SCOPE)?;
+        PriceChange::list(context, product_id, price_id)
+    }
+
+    fn listPriceTier(
+        context: &Context,
+        product_id: i32,
+        price_id: Option<i32>,
+    ) -> FieldResult<Vec<PriceTier>> {
+        context.require_scope(READ_SCOPE)?;
+        PriceTier::list(context, product_id, price_id)
+    }
+
+    fn resolvePrice(
+        context: &Context,
+        product_id: i32,
+        customer_id: Option<i32>,
+        quantity: f64,
+        date: Option<NaiveDate>,
+    ) -> FieldResult<ResolvedPrice> {
+        context.require_scope(READ_SCOPE)?;
+        PriceTier::resolve(context, product_id, customer_id, quantity, date)
+    }
+
+    fn auditLog(
+        context: &Context,
+        search: Option<SearchAuditEvent>,
+        limit: i32,
+        offset: i32,
+    ) -> FieldResult<ListAuditEvent> {
+        context.require_scope(READ_SCOPE)?;
+        AuditEvent::list(context, search, limit, offset)
+    }
+
+    fn listApiKey(context: &Context) -> FieldResult<ListApiKey> {
+        ApiKey::list(context)
+    }
 }
@@ -0,0 +1,165 @@
+use actix_multipart::Multipart;
+use actix_web::error::{
+    BlockingError, ErrorBadRequest, ErrorInternalServerError, ErrorNotFound, ErrorPayloadTooLarge,
+};
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{post, web, Error, HttpResponse};
+use diesel::Connection;
+use futures_util::StreamExt;
+
+use crate::db_connection::PgPool;
+use crate::handlers::LoggedUser;
+use crate::models::attachment::{self, Attachment};
+use crate::models::create_context;
+use crate::storage;
+
+/// Multipart upload of one or more `file` parts, attached to the product
+/// in the `product_id` part or the sale in the `sale_id` one. Either all
+/// the files are attached or none. Besides the size of each file, the
+/// number of files and the size of the whole request are limited.
+#[post("/attachments")]
+pub async fn upload(
+    mut payload: Multipart,
+    user: LoggedUser,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let max_bytes = attachment::max_bytes();
+    let max_files = attachment::max_files();
+    let max_request_bytes = attachment::max_request_bytes();
+    let mut request_bytes = 0;
+    let mut product_id = None;
+    let mut sale_id = None;
+    let mut files = vec![];
+
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let disposition = field
+            .content_disposition()
+            .ok_or_else(|| ErrorBadRequest("Missing content disposition"))?;
+        let name = disposition.get_name().unwrap_or_default().to_string();
+        let filename = disposition.get_filename().unwrap_or_default().to_string();
+        if name == "file" && files.len() == max_files {
+            return Err(ErrorPayloadTooLarge(format!(
+                "No more than {} files can be uploaded at once",
+                max_files
+            )));
+        }
+
+        let mut bytes = vec![];
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk?;
+            if bytes.len() + chunk.len() > max_bytes {
+                return Err(ErrorPayloadTooLarge(format!(
+                    "Files can't be larger than {} bytes",
+                    max_bytes
+                )));
+            }
+            request_bytes += chunk.len();
+            if request_bytes > max_request_bytes {
+                return Err(ErrorPayloadTooLarge(format!(
+                    "Uploads can't be larger than {} bytes",
+                    max_request_bytes
+                )));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        match name.as_str() {
+            "product_id" => product_id = Some(parse_id(&bytes)?),
+            "sale_id" => sale_id = Some(parse_id(&bytes)?),
+            "file" => files.push((filename, bytes)),
+            _ => return Err(ErrorBadRequest(format!("Unexpected field {}", name))),
+        }
+    }
+    if files.is_empty() {
+        return Err(ErrorBadRequest("No file was uploaded"));
+    }
+
+    let attachments = web::block(move || {
+        let pg_pool = pool.get().map_err(|error| error.to_string())?;
+        let context = create_context(user.id, pg_pool);
+        let storage = storage::from_env().map_err(|error| error.to_string())?;
+
+        let mut created = vec![];
+        let result = context.conn.transaction(|| {
+            for (filename, bytes) in files {
+                created.push(Attachment::create(
+                    &context,
+                    storage.as_ref(),
+                    product_id,
+                    sale_id,
+                    filename,
+                    &bytes,
+                )?);
+            }
+            Ok::<_, juniper::FieldError>(())
+        });
+        match result {
+            Ok(()) => Ok(created),
+            Err(error) => {
+                Attachment::remove_files(storage.as_ref(), &created);
+                Err(error.message().to_string())
+            }
+        }
+    })
+    .await
+    .map_err(|error| match error {
+        BlockingError::Error(message) => ErrorBadRequest(message),
+        BlockingError::Canceled => ErrorInternalServerError("The upload was cancelled"),
+    })?;
+
+    Ok(HttpResponse::Ok().json(attachments))
+}
+
+/// Serves a stored file, an attachment or its thumbnail, from the URL it
+/// was given. Files are only served to the user they belong to, anybody
+/// else gets a not found.
+pub async fn download(
+    key: web::Path<String>,
+    user: LoggedUser,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let key = key.into_inner();
+    let stored_key = key.clone();
+    let file = web::block(move || {
+        let pg_pool = pool.get().map_err(|error| error.to_string())?;
+        let context = create_context(user.id, pg_pool);
+        let attachment = match Attachment::find_by_key(&context, &stored_key) {
+            Ok(attachment) => attachment,
+            Err(_) => return Ok(None),
+        };
+        let storage = storage::from_env().map_err(|error| error.to_string())?;
+        let bytes = storage
+            .get(&stored_key)
+            .map_err(|error| error.to_string())?;
+        Ok(Some((attachment, bytes)))
+    })
+    .await
+    .map_err(|error| match error {
+        BlockingError::Error(message) => ErrorInternalServerError(message),
+        BlockingError::Canceled => ErrorInternalServerError("The download was cancelled"),
+    })?;
+    let (attachment, bytes) = file.ok_or_else(|| ErrorNotFound("Attachment not found"))?;
+
+    // Thumbnails are always PNG, whatever the attachment is.
+    let content_type = if attachment.storage_key == key {
+        attachment.content_type
+    } else {
+        "image/png".to_string()
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .set(ContentDisposition {
+            disposition: DispositionType::Inline,
+            parameters: vec![DispositionParam::Filename(attachment.filename)],
+        })
+        .body(bytes))
+}
+
+fn parse_id(bytes: &[u8]) -> Result<i32, Error> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .ok_or_else(|| ErrorBadRequest("Invalid id"))
+}
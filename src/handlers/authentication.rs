@@ -1,30 +1,67 @@
 use actix_identity::Identity;
-use actix_web::HttpResponse;
+use actix_web::http::StatusCode;
 use actix_web::{delete, post, web};
+use actix_web::{HttpRequest, HttpResponse};
 use csrf_token::CsrfTokenGenerator;
 use hex;
 
 use crate::db_connection::PgPool;
 use crate::errors::MyStoreError;
-use crate::handlers::pg_pool_handler;
-use crate::models::user::AuthUser;
-use crate::utils::jwt::create_token;
+use crate::handlers::{pg_pool_handler, LoggedUser};
+use crate::models::user::{AuthUser, UnlockUser, User};
+use crate::utils::jwt::{create_challenge_token, create_token};
+
+#[derive(Serialize)]
+pub struct PendingChallenge {
+    pub challenge: String,
+}
+
+pub fn auth_error_response(e: MyStoreError) -> HttpResponse {
+    match e {
+        MyStoreError::DBError(diesel::result::Error::NotFound) => {
+            HttpResponse::NotFound().json(e.to_string())
+        }
+        MyStoreError::WrongPassword(_) | MyStoreError::Unauthorized(_) => {
+            HttpResponse::Unauthorized().json(e.to_string())
+        }
+        MyStoreError::AccountLocked(_) => {
+            HttpResponse::build(StatusCode::LOCKED).json(e.to_string())
+        }
+        MyStoreError::TooManyAttempts(_) => {
+            HttpResponse::build(StatusCode::TOO_MANY_REQUESTS).json(e.to_string())
+        }
+        MyStoreError::TwoFactorError(_) => HttpResponse::BadRequest().json(e.to_string()),
+        _ => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
 
 #[post("/login")]
 pub async fn login(
+    req: HttpRequest,
     auth_user: web::Json<AuthUser>,
     id: Identity,
     pool: web::Data<PgPool>,
     generator: web::Data<CsrfTokenGenerator>,
 ) -> Result<HttpResponse, HttpResponse> {
     let pg_pool = pg_pool_handler(pool)?;
-    let user = auth_user.login(&pg_pool).map_err(|e| match e {
-        MyStoreError::DBError(diesel::result::Error::NotFound) => {
-            HttpResponse::NotFound().json(e.to_string())
-        }
-        _ => HttpResponse::InternalServerError().json(e.to_string()),
-    })?;
+    let ip_address = peer_ip_address(&req);
+    let user = auth_user
+        .login(&pg_pool, &ip_address)
+        .map_err(auth_error_response)?;
+
+    if user.totp_enabled {
+        let challenge = create_challenge_token(user.id)?;
+        return Ok(HttpResponse::Accepted().json(PendingChallenge { challenge }));
+    }
+
+    Ok(start_session(user, id, &generator)?)
+}
 
+pub fn start_session(
+    user: User,
+    id: Identity,
+    generator: &CsrfTokenGenerator,
+) -> Result<HttpResponse, HttpResponse> {
     let token = create_token(user.id, &user.email, &user.company)?;
     id.remember(token);
     let response = HttpResponse::Ok()
@@ -33,8 +70,26 @@ pub async fn login(
     Ok(response)
 }
 
+pub fn peer_ip_address(req: &HttpRequest) -> String {
+    req.peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
 #[delete("/logout")]
 pub async fn logout(id: Identity) -> Result<HttpResponse, HttpResponse> {
     id.forget();
     Ok(HttpResponse::Ok().json("success"))
 }
+
+#[post("/unlock")]
+pub async fn unlock(
+    unlock_user: web::Json<UnlockUser>,
+    user: LoggedUser,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, HttpResponse> {
+    let pg_pool = pg_pool_handler(pool)?;
+    User::unlock(user.id, &unlock_user.email, &pg_pool)
+        .map(|user| HttpResponse::Ok().json(user))
+        .map_err(auth_error_response)
+}
@@ -1,9 +1,12 @@
 #[macro_use]
 pub mod register;
+pub mod attachment;
 pub mod authentication;
+pub mod two_factor;
 
 use actix_identity::Identity;
-use actix_web::error::{ErrorBadRequest, ErrorUnauthorized};
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorUnauthorized};
+use actix_web::http::header::{HeaderValue, AUTHORIZATION};
 use actix_web::{dev, FromRequest, HttpRequest};
 use actix_web::{web, Error, Result};
 use chrono::Duration;
@@ -12,6 +15,8 @@ use futures_util::future::{err, ok, Ready};
 use hex;
 
 use crate::db_connection::{PgPool, PgPooledConnection};
+use crate::errors::MyStoreError;
+use crate::models::api_key::ApiKey;
 use crate::utils::jwt::{decode_token, SlimUser};
 
 pub type LoggedUser = SlimUser;
@@ -21,6 +26,7 @@ pub fn pg_pool_handler(pool: web::Data<PgPool>) -> Result<PgPooledConnection> {
         .map_err(|e| actix_web::error::ErrorInternalServerError(e))
 }
 
+/// Session users only, REST handlers don't take API keys.
 impl FromRequest for LoggedUser {
     type Error = Error;
     type Config = ();
@@ -34,7 +40,32 @@ impl FromRequest for LoggedUser {
     }
 }
 
+/// User of the graphql endpoint, logged in or presenting an API key.
+pub struct GraphqlUser(pub LoggedUser);
+
+impl FromRequest for GraphqlUser {
+    type Error = Error;
+    type Config = ();
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        // API keys don't travel in a cookie, so they don't need the csrf check.
+        let user = match req.headers().get(AUTHORIZATION) {
+            Some(authorization) => get_api_key_user(req, authorization),
+            None => get_token(req, payload),
+        };
+        match user {
+            Ok(user) => ok(GraphqlUser(user)),
+            Err(error) => err(error),
+        }
+    }
+}
+
 fn get_token(req: &HttpRequest, payload: &mut dev::Payload) -> Result<LoggedUser, Error> {
+    if req.headers().contains_key(AUTHORIZATION) {
+        return Err(ErrorUnauthorized("API keys are only accepted on /graphql"));
+    }
+
     let generator = CsrfTokenGenerator::new(
         dotenv!("CSRF_TOKEN_KEY").as_bytes().to_vec(),
         Duration::hours(1),
@@ -62,3 +93,30 @@ fn get_token(req: &HttpRequest, payload: &mut dev::Payload) -> Result<LoggedUser
         Err(ErrorUnauthorized("can't obtain token"))
     }
 }
+
+fn get_api_key_user(req: &HttpRequest, authorization: &HeaderValue) -> Result<LoggedUser, Error> {
+    let authorization = authorization
+        .to_str()
+        .map_err(|_| ErrorBadRequest("Invalid authorization header"))?;
+    if !authorization.starts_with("Bearer ") {
+        return Err(ErrorBadRequest("Only bearer authorization is supported"));
+    }
+    let key = authorization["Bearer ".len()..].trim();
+
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .ok_or(ErrorInternalServerError("No database pool configured"))?;
+    let pg_pool = pool.get().map_err(|e| ErrorInternalServerError(e))?;
+
+    let (api_key, user) = ApiKey::authenticate(key, &pg_pool).map_err(|error| match error {
+        MyStoreError::AccountLocked(_) => ErrorUnauthorized(error.to_string()),
+        _ => ErrorUnauthorized("Invalid api key"),
+    })?;
+
+    Ok(LoggedUser {
+        id: user.id,
+        email: user.email,
+        company: user.company,
+        scopes: Some(api_key.scopes),
+    })
+}
@@ -0,0 +1,62 @@
+use actix_identity::Identity;
+use actix_web::{delete, post, web};
+use actix_web::{HttpRequest, HttpResponse};
+use csrf_token::CsrfTokenGenerator;
+
+use crate::db_connection::PgPool;
+use crate::handlers::authentication::{auth_error_response, peer_ip_address, start_session};
+use crate::handlers::{pg_pool_handler, LoggedUser};
+use crate::models::user::{TotpCode, TwoFactorChallenge, User};
+use crate::utils::jwt::decode_challenge_token;
+
+#[post("/login/verify")]
+pub async fn verify(
+    req: HttpRequest,
+    two_factor_challenge: web::Json<TwoFactorChallenge>,
+    id: Identity,
+    pool: web::Data<PgPool>,
+    generator: web::Data<CsrfTokenGenerator>,
+) -> Result<HttpResponse, HttpResponse> {
+    let pg_pool = pg_pool_handler(pool)?;
+    let user_id = decode_challenge_token(&two_factor_challenge.challenge)?;
+    let user = two_factor_challenge
+        .verify(user_id, &pg_pool, &peer_ip_address(&req))
+        .map_err(auth_error_response)?;
+
+    start_session(user, id, &generator)
+}
+
+#[post("/two_factor/enroll")]
+pub async fn enroll(
+    user: LoggedUser,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, HttpResponse> {
+    let pg_pool = pg_pool_handler(pool)?;
+    User::enroll_totp(user.id, &pg_pool)
+        .map(|enrollment| HttpResponse::Ok().json(enrollment))
+        .map_err(auth_error_response)
+}
+
+#[post("/two_factor/confirm")]
+pub async fn confirm(
+    totp_code: web::Json<TotpCode>,
+    user: LoggedUser,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, HttpResponse> {
+    let pg_pool = pg_pool_handler(pool)?;
+    User::confirm_totp(user.id, &totp_code.code, &pg_pool)
+        .map(|backup_codes| HttpResponse::Ok().json(backup_codes))
+        .map_err(auth_error_response)
+}
+
+#[delete("/two_factor")]
+pub async fn disable(
+    totp_code: web::Json<TotpCode>,
+    user: LoggedUser,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, HttpResponse> {
+    let pg_pool = pg_pool_handler(pool)?;
+    User::disable_totp(user.id, &totp_code.code, &pg_pool)
+        .map(|_| HttpResponse::Ok().json("success"))
+        .map_err(auth_error_response)
+}
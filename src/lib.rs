@@ -32,4 +32,5 @@ pub mod models;
 pub mod handlers;
 pub mod errors;
 pub mod utils;
+pub mod storage;
 pub mod graphql;
\ No newline at end of file
@@ -9,19 +9,33 @@ use actix_cors::Cors;
 use actix_identity::{CookieIdentityPolicy, IdentityService};
 use actix_web::http::header;
 use actix_web::middleware::Logger;
-use actix_web::{App, HttpServer};
+use actix_web::{web, App, HttpServer};
 use chrono::Duration;
 use csrf_token::CsrfTokenGenerator;
 
 use ::mystore_lib::graphql::{graphql,graphiql};
 use ::mystore_lib::graphql::schema::create_schema;
-use ::mystore_lib::handlers::authentication::{login, logout};
+use ::mystore_lib::handlers::attachment;
+use ::mystore_lib::handlers::authentication::{login, logout, unlock};
 use ::mystore_lib::handlers::register::register;
+use ::mystore_lib::handlers::two_factor;
+use ::mystore_lib::models::product_rank::{self, RankSettings};
+use ::mystore_lib::models::scheduled_price::ScheduledPrice;
+use ::mystore_lib::storage::LocalStorage;
 
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_LOG", "actix_web=debug");
     env_logger::init();
+    dotenv::dotenv().ok();
+
+    let rank_settings = RankSettings::from_env()
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error))?;
+    product_rank::schedule(establish_connection(), rank_settings);
+    ScheduledPrice::schedule(establish_connection());
+
+    let local_storage = LocalStorage::from_env();
+    std::fs::create_dir_all(&local_storage.root)?;
 
     let csrf_token_header = header::HeaderName::from_lowercase(b"x-csrf-token").unwrap();
 
@@ -61,8 +75,18 @@ async fn main() -> std::io::Result<()> {
             .service(register)
             .service(login)
             .service(logout)
+            .service(unlock)
+            .service(two_factor::verify)
+            .service(two_factor::enroll)
+            .service(two_factor::confirm)
+            .service(two_factor::disable)
+            .service(attachment::upload)
             .service(graphql)
             .service(graphiql)
+            .route(
+                &format!("{}/{{key:.*}}", local_storage.base_url.trim_end_matches('/')),
+                web::get().to(attachment::download),
+            )
     })
     .bind("127.0.0.1:8088")?
     .run()
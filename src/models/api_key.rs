@@ -0,0 +1,192 @@
+use chrono::{Local, NaiveDateTime};
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::errors::MyStoreError;
+use crate::models::audit_event::AuditEvent;
+use crate::models::user::User;
+use crate::models::Context;
+use crate::schema::api_keys;
+use crate::schema::api_keys::dsl;
+use crate::schema::users;
+
+pub const READ_SCOPE: &str = "read";
+pub const WRITE_SCOPE: &str = "write";
+const VALID_SCOPES: [&str; 2] = [READ_SCOPE, WRITE_SCOPE];
+
+const KEY_PREFIX: &str = "msk_";
+const KEY_LENGTH: usize = 40;
+// Characters of the key kept in clear so users can tell their keys apart.
+const DISPLAYED_PREFIX_LENGTH: usize = 12;
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListApiKey {
+    pub data: Vec<ApiKey>,
+}
+
+/// Keys belong to an account rather than to the free text `company` of its
+/// user, every record of the store is scoped to the account, so that is the
+/// company a key acts for.
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "api_keys"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Key for machine to machine access")]
+pub struct ApiKey {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub prefix: String,
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+#[graphql(description = "The plain key is only shown once, when it is created")]
+pub struct CreatedApiKey {
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+pub struct FormApiKey {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "api_keys"]
+struct NewApiKey {
+    user_id: i32,
+    name: String,
+    prefix: String,
+    key_hash: String,
+    scopes: Vec<String>,
+    created_at: NaiveDateTime,
+}
+
+impl ApiKey {
+    pub fn list(context: &Context) -> FieldResult<ListApiKey> {
+        let connection: &PgConnection = &context.conn;
+        context.require_session()?;
+
+        Ok(ListApiKey {
+            data: dsl::api_keys
+                .filter(dsl::user_id.eq(context.user_id))
+                .order(dsl::created_at.desc())
+                .load::<ApiKey>(connection)?,
+        })
+    }
+
+    pub fn create(context: &Context, form: FormApiKey) -> FieldResult<CreatedApiKey> {
+        let connection: &PgConnection = &context.conn;
+        context.require_session()?;
+
+        if let Some(scope) = form
+            .scopes
+            .iter()
+            .find(|scope| !VALID_SCOPES.contains(&scope.as_str()))
+        {
+            return Err(format!("Unknown scope {}", scope).into());
+        }
+
+        let key = format!(
+            "{}{}",
+            KEY_PREFIX,
+            rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(KEY_LENGTH)
+                .collect::<String>()
+        );
+
+        connection.transaction(|| {
+            let api_key = diesel::insert_into(api_keys::table)
+                .values(NewApiKey {
+                    user_id: context.user_id,
+                    name: form.name,
+                    prefix: key[..DISPLAYED_PREFIX_LENGTH].to_string(),
+                    key_hash: hash_key(&key),
+                    scopes: form.scopes,
+                    created_at: Local::now().naive_local(),
+                })
+                .get_result::<ApiKey>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createApiKey",
+                "api_key",
+                api_key.id,
+                None::<&ApiKey>,
+                Some(&api_key),
+            )?;
+            Ok(CreatedApiKey { api_key, key })
+        })
+    }
+
+    pub fn revoke(context: &Context, api_key_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+        context.require_session()?;
+
+        connection.transaction(|| {
+            let revoked = diesel::update(
+                dsl::api_keys
+                    .filter(dsl::user_id.eq(context.user_id))
+                    .filter(dsl::revoked_at.is_null())
+                    .find(api_key_id),
+            )
+            .set(dsl::revoked_at.eq(Local::now().naive_local()))
+            .get_results::<ApiKey>(connection)?;
+
+            if let Some(api_key) = revoked.first() {
+                let before = ApiKey {
+                    revoked_at: None,
+                    ..api_key.clone()
+                };
+                AuditEvent::record(
+                    context,
+                    "revokeApiKey",
+                    "api_key",
+                    api_key.id,
+                    Some(&before),
+                    Some(api_key),
+                )?;
+            }
+            Ok(revoked.len() == 1)
+        })
+    }
+
+    /// Finds the owner of a key that hasn't been revoked and records its use.
+    /// Keys stop working while their owner's account is locked.
+    pub fn authenticate(
+        key: &str,
+        connection: &PgConnection,
+    ) -> Result<(ApiKey, User), MyStoreError> {
+        let api_key = dsl::api_keys
+            .filter(dsl::key_hash.eq(hash_key(key)))
+            .filter(dsl::revoked_at.is_null())
+            .first::<ApiKey>(connection)?;
+
+        let user = users::table
+            .find(api_key.user_id)
+            .first::<User>(connection)?;
+        user.check_not_locked(Local::now().naive_local())?;
+
+        diesel::update(dsl::api_keys.find(api_key.id))
+            .set(dsl::last_used_at.eq(Local::now().naive_local()))
+            .execute(connection)?;
+        Ok((api_key, user))
+    }
+}
+
+// Keys are long random strings, a plain digest is enough to keep them safe at
+// rest and lets us look them up directly.
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
@@ -0,0 +1,383 @@
+use chrono::{Local, NaiveDateTime};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+};
+use image::io::Reader;
+use image::{ImageFormat, ImageOutputFormat};
+use juniper::FieldResult;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::env;
+use std::io::Cursor;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::product::Product;
+use crate::models::sale::Sale;
+use crate::models::Context;
+use crate::schema::{attachments, products, sales};
+use crate::storage::{self, Storage};
+
+/// Largest upload accepted when `ATTACHMENT_MAX_BYTES` is not set, 10 MiB.
+const DEFAULT_MAX_BYTES: usize = 10 * 1024 * 1024;
+/// Files accepted in one upload when `ATTACHMENT_MAX_FILES` is not set.
+const DEFAULT_MAX_FILES: usize = 10;
+/// Largest request accepted when `ATTACHMENT_MAX_REQUEST_BYTES` is not
+/// set, 50 MiB.
+const DEFAULT_MAX_REQUEST_BYTES: usize = 50 * 1024 * 1024;
+/// Images with more pixels aren't decoded, a small file can unpack into
+/// gigabytes of pixels.
+const MAX_IMAGE_PIXELS: u64 = 50_000_000;
+/// Thumbnails fit in a square of this many pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+const KEY_LENGTH: usize = 32;
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListAttachment {
+    pub data: Vec<Attachment>,
+}
+
+#[derive(
+    Identifiable, Queryable, Associations, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(Product)]
+#[belongs_to(Sale)]
+#[table_name = "attachments"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Image or document attached to a product or a sale")]
+pub struct Attachment {
+    pub id: i32,
+    pub user_id: i32,
+    pub product_id: Option<i32>,
+    pub sale_id: Option<i32>,
+    pub filename: String,
+    pub content_type: String,
+    #[graphql(description = "Size in bytes")]
+    pub size: i32,
+    #[graphql(skip)]
+    pub storage_key: String,
+    pub url: String,
+    #[graphql(skip)]
+    pub thumbnail_key: Option<String>,
+    #[graphql(description = "Reduced copy of images, missing for documents")]
+    pub thumbnail_url: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "attachments"]
+struct NewAttachment {
+    user_id: i32,
+    product_id: Option<i32>,
+    sale_id: Option<i32>,
+    filename: String,
+    content_type: String,
+    size: i32,
+    storage_key: String,
+    url: String,
+    thumbnail_key: Option<String>,
+    thumbnail_url: Option<String>,
+    created_at: NaiveDateTime,
+}
+
+/// Upload size limit, from `ATTACHMENT_MAX_BYTES`.
+pub fn max_bytes() -> usize {
+    env::var("ATTACHMENT_MAX_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_BYTES)
+}
+
+/// Limit of files in one upload, from `ATTACHMENT_MAX_FILES`.
+pub fn max_files() -> usize {
+    env::var("ATTACHMENT_MAX_FILES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_FILES)
+}
+
+/// Limit of the whole upload request, from `ATTACHMENT_MAX_REQUEST_BYTES`.
+pub fn max_request_bytes() -> usize {
+    env::var("ATTACHMENT_MAX_REQUEST_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_REQUEST_BYTES)
+}
+
+/// Only images and PDF documents are accepted. The type is taken from the
+/// content itself, whatever the client says it is.
+fn detect_content_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"%PDF-") {
+        return Some("application/pdf");
+    }
+    match image::guess_format(bytes) {
+        Ok(ImageFormat::Png) => Some("image/png"),
+        Ok(ImageFormat::Jpeg) => Some("image/jpeg"),
+        Ok(ImageFormat::Gif) => Some("image/gif"),
+        Ok(ImageFormat::WebP) => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn extension(content_type: &str) -> &'static str {
+    match content_type {
+        "application/pdf" => "pdf",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        _ => "webp",
+    }
+}
+
+/// The dimensions are read from the header first, the image is only
+/// decoded when it's small enough.
+fn thumbnail(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    fn unreadable<E>(_: E) -> String {
+        "The image can't be read".to_string()
+    }
+    let reader = || Reader::new(Cursor::new(bytes)).with_guessed_format();
+
+    let (width, height) = reader()
+        .map_err(unreadable)?
+        .into_dimensions()
+        .map_err(unreadable)?;
+    if u64::from(width) * u64::from(height) > MAX_IMAGE_PIXELS {
+        return Err(format!(
+            "Images can't have more than {} pixels",
+            MAX_IMAGE_PIXELS
+        ));
+    }
+
+    let mut thumbnail = Vec::new();
+    reader()
+        .map_err(unreadable)?
+        .decode()
+        .map_err(unreadable)?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut thumbnail, ImageOutputFormat::Png)
+        .map_err(unreadable)?;
+    Ok(thumbnail)
+}
+
+impl Attachment {
+    pub fn list(
+        context: &Context,
+        product_id: Option<i32>,
+        sale_id: Option<i32>,
+    ) -> FieldResult<ListAttachment> {
+        let connection: &PgConnection = &context.conn;
+
+        let mut query = attachments::table
+            .filter(attachments::user_id.eq(context.user_id))
+            .into_boxed();
+        if let Some(product_id) = product_id {
+            query = query.filter(attachments::product_id.eq(product_id));
+        }
+        if let Some(sale_id) = sale_id {
+            query = query.filter(attachments::sale_id.eq(sale_id));
+        }
+
+        Ok(ListAttachment {
+            data: query
+                .order(attachments::id.asc())
+                .load::<Attachment>(connection)?,
+        })
+    }
+
+    /// Stores the file and, for images, a thumbnail of it. The attachment
+    /// belongs either to a product or to a sale of the company.
+    pub fn create(
+        context: &Context,
+        storage: &dyn Storage,
+        product_id: Option<i32>,
+        sale_id: Option<i32>,
+        filename: String,
+        bytes: &[u8],
+    ) -> FieldResult<Attachment> {
+        let connection: &PgConnection = &context.conn;
+
+        match (product_id, sale_id) {
+            (Some(product_id), None) => {
+                products::table
+                    .select(products::id)
+                    .filter(products::user_id.eq(context.user_id))
+                    .find(product_id)
+                    .first::<i32>(connection)?;
+            }
+            (None, Some(sale_id)) => {
+                sales::table
+                    .select(sales::id)
+                    .filter(sales::user_id.eq(context.user_id))
+                    .find(sale_id)
+                    .first::<i32>(connection)?;
+            }
+            _ => return Err("An attachment belongs either to a product or to a sale".into()),
+        }
+
+        if bytes.is_empty() {
+            return Err("The file is empty".into());
+        }
+        if bytes.len() > max_bytes() {
+            return Err(format!("Files can't be larger than {} bytes", max_bytes()).into());
+        }
+        let content_type = detect_content_type(bytes)
+            .ok_or("Only PNG, JPEG, GIF and WebP images or PDF documents can be attached")?;
+        let thumbnail = if content_type.starts_with("image/") {
+            Some(thumbnail(bytes)?)
+        } else {
+            None
+        };
+
+        let name: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(KEY_LENGTH)
+            .collect();
+        let storage_key = format!("{}/{}.{}", context.user_id, name, extension(content_type));
+        let url = storage.put(&storage_key, content_type, bytes)?;
+        let (thumbnail_key, thumbnail_url) = match thumbnail {
+            Some(thumbnail) => {
+                let thumbnail_key = format!("{}/{}_thumbnail.png", context.user_id, name);
+                match storage.put(&thumbnail_key, "image/png", &thumbnail) {
+                    Ok(thumbnail_url) => (Some(thumbnail_key), Some(thumbnail_url)),
+                    Err(error) => {
+                        Attachment::remove_keys(storage, &[storage_key]);
+                        return Err(error.into());
+                    }
+                }
+            }
+            None => (None, None),
+        };
+
+        let filename = filename.trim().chars().take(255).collect::<String>();
+        let new_attachment = NewAttachment {
+            user_id: context.user_id,
+            product_id,
+            sale_id,
+            filename: if filename.is_empty() {
+                "attachment".to_string()
+            } else {
+                filename
+            },
+            content_type: content_type.to_string(),
+            size: bytes.len() as i32,
+            storage_key,
+            url,
+            thumbnail_key,
+            thumbnail_url,
+            created_at: Local::now().naive_local(),
+        };
+
+        let stored_keys =
+            Attachment::keys_of(&new_attachment.storage_key, &new_attachment.thumbnail_key);
+        let result = connection.transaction(|| {
+            let attachment = diesel::insert_into(attachments::table)
+                .values(new_attachment)
+                .get_result::<Attachment>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createAttachment",
+                "attachment",
+                attachment.id,
+                None::<&Attachment>,
+                Some(&attachment),
+            )?;
+            Ok(attachment)
+        });
+        if result.is_err() {
+            Attachment::remove_keys(storage, &stored_keys);
+        }
+        result
+    }
+
+    pub fn find(context: &Context, attachment_id: i32) -> FieldResult<Attachment> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(attachments::table
+            .filter(attachments::user_id.eq(context.user_id))
+            .find(attachment_id)
+            .first(connection)?)
+    }
+
+    /// The attachment a stored file belongs to, the file itself or its
+    /// thumbnail.
+    pub fn find_by_key(context: &Context, key: &str) -> FieldResult<Attachment> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(attachments::table
+            .filter(attachments::user_id.eq(context.user_id))
+            .filter(
+                attachments::storage_key
+                    .eq(key)
+                    .or(attachments::thumbnail_key.eq(key)),
+            )
+            .first(connection)?)
+    }
+
+    pub fn destroy(context: &Context, attachment_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        let storage = storage::from_env()?;
+        let before = connection.transaction(|| {
+            let before = Attachment::find(context, attachment_id)?;
+
+            diesel::delete(attachments::table.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyAttachment",
+                "attachment",
+                attachment_id,
+                Some(&before),
+                None::<&Attachment>,
+            )?;
+            Ok::<_, juniper::FieldError>(before)
+        })?;
+
+        Attachment::remove_files(storage.as_ref(), &[before]);
+        Ok(true)
+    }
+
+    /// Attachments of a product or a sale about to be deleted, their rows
+    /// go away with it but the files have to be removed afterwards.
+    pub fn of_owner(
+        context: &Context,
+        product_id: Option<i32>,
+        sale_id: Option<i32>,
+    ) -> FieldResult<Vec<Attachment>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(attachments::table
+            .filter(attachments::user_id.eq(context.user_id))
+            .filter(
+                attachments::product_id
+                    .eq(product_id)
+                    .or(attachments::sale_id.eq(sale_id)),
+            )
+            .load::<Attachment>(connection)?)
+    }
+
+    /// Files left behind are only wasted space, so failures are logged
+    /// instead of undoing what was already deleted.
+    pub fn remove_files(storage: &dyn Storage, attachments: &[Attachment]) {
+        for attachment in attachments {
+            Attachment::remove_keys(
+                storage,
+                &Attachment::keys_of(&attachment.storage_key, &attachment.thumbnail_key),
+            );
+        }
+    }
+
+    fn keys_of(storage_key: &str, thumbnail_key: &Option<String>) -> Vec<String> {
+        let mut keys = vec![storage_key.to_string()];
+        keys.extend(thumbnail_key.clone());
+        keys
+    }
+
+    fn remove_keys(storage: &dyn Storage, keys: &[String]) {
+        for key in keys {
+            if let Err(error) = storage.delete(key) {
+                log::error!("Couldn't remove stored file {}: {}", key, error);
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::{Local, NaiveDateTime};
+use diesel::{pg::Pg, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::models::Context;
+use crate::schema::audit_events;
+use crate::schema::audit_events::dsl;
+use crate::schema::users;
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+#[graphql(scalar = juniper::DefaultScalarValue)]
+pub struct ListAuditEvent {
+    pub data: Vec<AuditEvent>,
+    pub total: i32,
+}
+
+#[derive(Identifiable, Queryable, Debug, Clone, PartialEq)]
+#[table_name = "audit_events"]
+pub struct AuditEvent {
+    pub id: i32,
+    pub user_id: i32,
+    pub company: String,
+    pub operation: String,
+    pub entity: String,
+    pub entity_id: i32,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: NaiveDateTime,
+}
+
+#[juniper::object(description = "Change performed through a mutation")]
+impl AuditEvent {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn user_id(&self) -> i32 {
+        self.user_id
+    }
+
+    fn company(&self) -> &str {
+        &self.company
+    }
+
+    fn operation(&self) -> &str {
+        &self.operation
+    }
+
+    fn entity(&self) -> &str {
+        &self.entity
+    }
+
+    fn entity_id(&self) -> i32 {
+        self.entity_id
+    }
+
+    #[graphql(description = "JSON snapshot of the entity before the change")]
+    fn before(&self) -> Option<String> {
+        self.before.as_ref().map(|snapshot| snapshot.to_string())
+    }
+
+    #[graphql(description = "JSON snapshot of the entity after the change")]
+    fn after(&self) -> Option<String> {
+        self.after.as_ref().map(|snapshot| snapshot.to_string())
+    }
+
+    fn created_at(&self) -> NaiveDateTime {
+        self.created_at
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "audit_events"]
+struct NewAuditEvent<'a> {
+    user_id: i32,
+    company: String,
+    operation: &'a str,
+    entity: &'a str,
+    entity_id: i32,
+    before: Option<Value>,
+    after: Option<Value>,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+#[graphql(description = "Filters for the audit log")]
+pub struct SearchAuditEvent {
+    pub entity: Option<String>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+impl AuditEvent {
+    /// Meant to be called inside the transaction performing the change, so
+    /// the event is only stored when the change is.
+    pub fn record<B: Serialize, A: Serialize>(
+        context: &Context,
+        operation: &str,
+        entity: &str,
+        entity_id: i32,
+        before: Option<&B>,
+        after: Option<&A>,
+    ) -> FieldResult<()> {
+        let connection: &PgConnection = &context.conn;
+
+        let user_company = users::table
+            .select(users::company)
+            .find(context.user_id)
+            .first::<String>(connection)?;
+
+        diesel::insert_into(audit_events::table)
+            .values(NewAuditEvent {
+                user_id: context.user_id,
+                company: user_company,
+                operation,
+                entity,
+                entity_id,
+                before: before.map(serde_json::to_value).transpose()?,
+                after: after.map(serde_json::to_value).transpose()?,
+                created_at: Local::now().naive_local(),
+            })
+            .execute(connection)?;
+        Ok(())
+    }
+
+    /// Changes made by the logged in account, the one every record in the
+    /// store belongs to.
+    pub fn list(
+        context: &Context,
+        search: Option<SearchAuditEvent>,
+        limit: i32,
+        offset: i32,
+    ) -> FieldResult<ListAuditEvent> {
+        let connection: &PgConnection = &context.conn;
+
+        let searching_records = || {
+            let mut query = audit_events::table
+                .filter(dsl::user_id.eq(context.user_id))
+                .into_boxed::<Pg>();
+
+            if let Some(audit_event) = search.clone() {
+                if let Some(search_entity) = audit_event.entity {
+                    query = query.filter(dsl::entity.eq(search_entity));
+                }
+                if let Some(from) = audit_event.from {
+                    query = query.filter(dsl::created_at.ge(from));
+                }
+                if let Some(to) = audit_event.to {
+                    query = query.filter(dsl::created_at.le(to));
+                }
+            }
+            query
+        };
+
+        let total: i64 = searching_records().count().get_result(connection)?;
+        let data = searching_records()
+            .order(dsl::created_at.desc())
+            .limit(i64::from(limit))
+            .offset(i64::from(offset))
+            .load::<AuditEvent>(connection)?;
+
+        Ok(ListAuditEvent {
+            data,
+            total: total as i32,
+        })
+    }
+}
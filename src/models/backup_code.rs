@@ -0,0 +1,103 @@
+use bcrypt::{hash, verify};
+use chrono::{Local, NaiveDateTime};
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use crate::errors::MyStoreError;
+use crate::schema::backup_codes;
+use crate::schema::backup_codes::dsl;
+
+const BACKUP_CODES_PER_USER: usize = 10;
+const BACKUP_CODE_LENGTH: usize = 10;
+// Backup codes are random rather than chosen by people, a low cost is enough
+// and keeps checking a code against every stored hash fast.
+const BACKUP_CODE_COST: u32 = 4;
+
+#[derive(Debug, Queryable)]
+pub struct BackupCode {
+    pub id: i32,
+    pub user_id: i32,
+    pub code_hash: String,
+    pub used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "backup_codes"]
+pub struct NewBackupCode {
+    pub user_id: i32,
+    pub code_hash: String,
+}
+
+impl BackupCode {
+    /// Replaces every backup code of the user, the plain codes are only
+    /// available through the returned vector.
+    pub fn regenerate(
+        param_user_id: i32,
+        connection: &PgConnection,
+    ) -> Result<Vec<String>, MyStoreError> {
+        let codes: Vec<String> = (0..BACKUP_CODES_PER_USER)
+            .map(|_| {
+                rand::thread_rng()
+                    .sample_iter(&Alphanumeric)
+                    .take(BACKUP_CODE_LENGTH)
+                    .collect::<String>()
+                    .to_lowercase()
+            })
+            .collect();
+
+        let mut new_codes = vec![];
+        for code in &codes {
+            new_codes.push(NewBackupCode {
+                user_id: param_user_id,
+                code_hash: hash(code, BACKUP_CODE_COST)?,
+            });
+        }
+
+        connection.transaction::<_, MyStoreError, _>(|| {
+            diesel::delete(dsl::backup_codes.filter(dsl::user_id.eq(param_user_id)))
+                .execute(connection)?;
+            diesel::insert_into(backup_codes::table)
+                .values(&new_codes)
+                .execute(connection)?;
+            Ok(())
+        })?;
+
+        Ok(codes)
+    }
+
+    /// Marks the matching unused code as spent, returns false when none matches.
+    pub fn consume(
+        param_user_id: i32,
+        code: &str,
+        connection: &PgConnection,
+    ) -> Result<bool, MyStoreError> {
+        let unused_codes = dsl::backup_codes
+            .filter(dsl::user_id.eq(param_user_id))
+            .filter(dsl::used_at.is_null())
+            .load::<BackupCode>(connection)?;
+
+        let code = code.trim().to_lowercase();
+        for backup_code in unused_codes {
+            if verify(&code, &backup_code.code_hash)? {
+                // Another login may have spent the code since it was loaded,
+                // only the one that marks it as used gets in.
+                let updated_rows = diesel::update(
+                    dsl::backup_codes
+                        .find(backup_code.id)
+                        .filter(dsl::used_at.is_null()),
+                )
+                .set(dsl::used_at.eq(Local::now().naive_local()))
+                .execute(connection)?;
+                return Ok(updated_rows == 1);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn delete_all(param_user_id: i32, connection: &PgConnection) -> Result<(), MyStoreError> {
+        diesel::delete(dsl::backup_codes.filter(dsl::user_id.eq(param_user_id)))
+            .execute(connection)?;
+        Ok(())
+    }
+}
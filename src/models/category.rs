@@ -0,0 +1,292 @@
+use chrono::NaiveDate;
+use diesel::sql_types::{Date, Float8, Integer, Nullable};
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::Context;
+use crate::schema::categories;
+use crate::schema::categories::dsl;
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListCategory {
+    pub data: Vec<Category>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "categories"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Category of products, categories can be nested")]
+pub struct Category {
+    pub id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub user_id: i32,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "categories"]
+pub struct FormCategory {
+    pub id: Option<i32>,
+    pub name: Option<String>,
+    pub parent_id: Option<i32>,
+    pub user_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+#[graphql(description = "Sales of a category, including its subcategories")]
+pub struct CategorySales {
+    pub category_id: i32,
+    pub name: String,
+    pub amount: f64,
+    pub total: f64,
+}
+
+#[derive(QueryableByName)]
+struct CategorySalesRow {
+    #[sql_type = "Integer"]
+    category_id: i32,
+    #[sql_type = "diesel::sql_types::Text"]
+    name: String,
+    #[sql_type = "Float8"]
+    amount: f64,
+    #[sql_type = "Float8"]
+    total: f64,
+}
+
+#[derive(QueryableByName)]
+struct CategoryId {
+    #[sql_type = "Integer"]
+    id: i32,
+}
+
+impl Category {
+    pub fn list(context: &Context) -> FieldResult<ListCategory> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(ListCategory {
+            data: dsl::categories
+                .filter(dsl::user_id.eq(context.user_id))
+                .order(dsl::name.asc())
+                .load::<Category>(connection)?,
+        })
+    }
+
+    pub fn create(context: &Context, form: FormCategory) -> FieldResult<Category> {
+        let connection: &PgConnection = &context.conn;
+
+        if let Some(param_parent_id) = form.parent_id {
+            Category::find(context, param_parent_id)?;
+        }
+
+        let new_category = FormCategory {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let category = diesel::insert_into(categories::table)
+                .values(new_category)
+                .get_result::<Category>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createCategory",
+                "category",
+                category.id,
+                None::<&Category>,
+                Some(&category),
+            )?;
+            Ok(category)
+        })
+    }
+
+    /// A missing `parent_id` leaves the category where it is, `to_top_level`
+    /// takes it out of its parent.
+    pub fn update(
+        context: &Context,
+        form: FormCategory,
+        to_top_level: bool,
+    ) -> FieldResult<Category> {
+        let connection: &PgConnection = &context.conn;
+
+        let category_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+
+        if to_top_level && form.parent_id.is_some() {
+            return Err("A category can't be given a parent and moved to the top level".into());
+        }
+        if let Some(param_parent_id) = form.parent_id {
+            if Category::descendant_ids(context, category_id)?.contains(&param_parent_id) {
+                return Err("A category can't be moved inside itself".into());
+            }
+            Category::find(context, param_parent_id)?;
+        }
+
+        let category_to_replace = FormCategory {
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let before = Category::find(context, category_id)?;
+
+            let category = diesel::update(
+                dsl::categories
+                    .filter(dsl::user_id.eq(context.user_id))
+                    .find(category_id),
+            )
+            .set(category_to_replace)
+            .get_result::<Category>(connection)?;
+            let category = if to_top_level {
+                diesel::update(dsl::categories.find(category.id))
+                    .set(dsl::parent_id.eq(None::<i32>))
+                    .get_result::<Category>(connection)?
+            } else {
+                category
+            };
+
+            AuditEvent::record(
+                context,
+                "updateCategory",
+                "category",
+                category_id,
+                Some(&before),
+                Some(&category),
+            )?;
+            Ok(category)
+        })
+    }
+
+    pub fn find(context: &Context, category_id: i32) -> FieldResult<Category> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(dsl::categories
+            .filter(dsl::user_id.eq(context.user_id))
+            .find(category_id)
+            .first(connection)?)
+    }
+
+    /// Categories with subcategories are kept, they have to be moved or
+    /// deleted first.
+    pub fn destroy(context: &Context, category_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Category::find(context, category_id)?;
+
+            let subcategories: i64 = dsl::categories
+                .filter(dsl::parent_id.eq(category_id))
+                .count()
+                .get_result(connection)?;
+            if subcategories > 0 {
+                return Err(format!(
+                    "{} has subcategories, move or delete them first",
+                    before.name
+                )
+                .into());
+            }
+
+            diesel::delete(
+                dsl::categories
+                    .filter(dsl::user_id.eq(context.user_id))
+                    .find(category_id),
+            )
+            .execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyCategory",
+                "category",
+                category_id,
+                Some(&before),
+                None::<&Category>,
+            )?;
+            Ok(true)
+        })
+    }
+
+    /// The category itself and every category nested below it.
+    pub fn descendant_ids(context: &Context, category_id: i32) -> FieldResult<Vec<i32>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(diesel::sql_query(
+            "WITH RECURSIVE tree AS ( \
+               SELECT id FROM categories WHERE id = $1 AND user_id = $2 \
+               UNION ALL \
+               SELECT categories.id FROM categories \
+               INNER JOIN tree ON categories.parent_id = tree.id \
+             ) \
+             SELECT id FROM tree",
+        )
+        .bind::<Integer, _>(category_id)
+        .bind::<Integer, _>(context.user_id)
+        .load::<CategoryId>(connection)?
+        .into_iter()
+        .map(|category| category.id)
+        .collect())
+    }
+
+    /// Sold quantities and totals for each child of `parent_id`, or for the
+    /// top level categories when it's missing. Draft and cancelled sales
+    /// are left out.
+    pub fn sales_report(
+        context: &Context,
+        parent_id: Option<i32>,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> FieldResult<Vec<CategorySales>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(diesel::sql_query(
+            "WITH RECURSIVE tree AS ( \
+               SELECT id, id AS root_id FROM categories \
+               WHERE user_id = $1 AND parent_id IS NOT DISTINCT FROM $2 \
+               UNION ALL \
+               SELECT categories.id, tree.root_id FROM categories \
+               INNER JOIN tree ON categories.parent_id = tree.id \
+             ) \
+             SELECT categories.id AS category_id, categories.name AS name, \
+               COALESCE(SUM(sold.amount), 0)::float8 AS amount, \
+               COALESCE(SUM(sold.total), 0)::float8 AS total \
+             FROM categories \
+             INNER JOIN tree ON tree.root_id = categories.id \
+             LEFT JOIN products ON products.category_id = tree.id \
+             LEFT JOIN ( \
+               SELECT sale_products.product_id, sale_products.amount, sale_products.total \
+               FROM sale_products \
+               INNER JOIN sales ON sales.id = sale_products.sale_id \
+               WHERE sales.user_id = $1 \
+                 AND sales.state IN ('approved', 'partially_payed', 'payed') \
+                 AND ($3::date IS NULL OR sales.sale_date >= $3) \
+                 AND ($4::date IS NULL OR sales.sale_date <= $4) \
+             ) sold ON sold.product_id = products.id \
+             GROUP BY categories.id, categories.name \
+             ORDER BY total DESC",
+        )
+        .bind::<Integer, _>(context.user_id)
+        .bind::<Nullable<Integer>, _>(parent_id)
+        .bind::<Nullable<Date>, _>(from)
+        .bind::<Nullable<Date>, _>(to)
+        .load::<CategorySalesRow>(connection)?
+        .into_iter()
+        .map(|row| CategorySales {
+            category_id: row.category_id,
+            name: row.name,
+            amount: row.amount,
+            total: row.total,
+        })
+        .collect())
+    }
+}
@@ -0,0 +1,297 @@
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::price::Price;
+use crate::models::Context;
+use crate::schema::{customer_groups, customers};
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListCustomer {
+    pub data: Vec<Customer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListCustomerGroup {
+    pub data: Vec<CustomerGroup>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "customers"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Customer products are sold to")]
+pub struct Customer {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub email: Option<String>,
+    pub customer_group_id: Option<i32>,
+    #[graphql(description = "Price list of the customer, the one of its group when missing")]
+    pub price_id: Option<i32>,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "customers"]
+pub struct FormCustomer {
+    pub id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub customer_group_id: Option<i32>,
+    pub price_id: Option<i32>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "customer_groups"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Customers sharing a price list, wholesalers for instance")]
+pub struct CustomerGroup {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub price_id: Option<i32>,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "customer_groups"]
+pub struct FormCustomerGroup {
+    pub id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub name: Option<String>,
+    pub price_id: Option<i32>,
+}
+
+impl Customer {
+    pub fn list(context: &Context) -> FieldResult<ListCustomer> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(ListCustomer {
+            data: customers::table
+                .filter(customers::user_id.eq(context.user_id))
+                .order(customers::name.asc())
+                .load::<Customer>(connection)?,
+        })
+    }
+
+    pub fn create(context: &Context, form: FormCustomer) -> FieldResult<Customer> {
+        let connection: &PgConnection = &context.conn;
+
+        Customer::check_references(context, &form)?;
+        let new_customer = FormCustomer {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let customer = diesel::insert_into(customers::table)
+                .values(new_customer)
+                .get_result::<Customer>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createCustomer",
+                "customer",
+                customer.id,
+                None::<&Customer>,
+                Some(&customer),
+            )?;
+            Ok(customer)
+        })
+    }
+
+    pub fn update(context: &Context, form: FormCustomer) -> FieldResult<Customer> {
+        let connection: &PgConnection = &context.conn;
+
+        let customer_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+
+        Customer::check_references(context, &form)?;
+        let customer_to_replace = FormCustomer {
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let before = Customer::find(context, customer_id)?;
+
+            let customer = diesel::update(customers::table.find(before.id))
+                .set(customer_to_replace)
+                .get_result::<Customer>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updateCustomer",
+                "customer",
+                customer_id,
+                Some(&before),
+                Some(&customer),
+            )?;
+            Ok(customer)
+        })
+    }
+
+    pub fn find(context: &Context, customer_id: i32) -> FieldResult<Customer> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(customers::table
+            .filter(customers::user_id.eq(context.user_id))
+            .find(customer_id)
+            .first(connection)?)
+    }
+
+    pub fn destroy(context: &Context, customer_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Customer::find(context, customer_id)?;
+
+            diesel::delete(customers::table.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyCustomer",
+                "customer",
+                customer_id,
+                Some(&before),
+                None::<&Customer>,
+            )?;
+            Ok(true)
+        })
+    }
+
+    fn check_references(context: &Context, form: &FormCustomer) -> FieldResult<()> {
+        if let Some(customer_group_id) = form.customer_group_id {
+            CustomerGroup::find(context, customer_group_id)?;
+        }
+        if let Some(price_id) = form.price_id {
+            Price::find(context, price_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl CustomerGroup {
+    pub fn list(context: &Context) -> FieldResult<ListCustomerGroup> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(ListCustomerGroup {
+            data: customer_groups::table
+                .filter(customer_groups::user_id.eq(context.user_id))
+                .order(customer_groups::name.asc())
+                .load::<CustomerGroup>(connection)?,
+        })
+    }
+
+    pub fn create(context: &Context, form: FormCustomerGroup) -> FieldResult<CustomerGroup> {
+        let connection: &PgConnection = &context.conn;
+
+        if let Some(price_id) = form.price_id {
+            Price::find(context, price_id)?;
+        }
+        let new_customer_group = FormCustomerGroup {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let customer_group = diesel::insert_into(customer_groups::table)
+                .values(new_customer_group)
+                .get_result::<CustomerGroup>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createCustomerGroup",
+                "customer_group",
+                customer_group.id,
+                None::<&CustomerGroup>,
+                Some(&customer_group),
+            )?;
+            Ok(customer_group)
+        })
+    }
+
+    pub fn update(context: &Context, form: FormCustomerGroup) -> FieldResult<CustomerGroup> {
+        let connection: &PgConnection = &context.conn;
+
+        let customer_group_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+
+        if let Some(price_id) = form.price_id {
+            Price::find(context, price_id)?;
+        }
+        let customer_group_to_replace = FormCustomerGroup {
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let before = CustomerGroup::find(context, customer_group_id)?;
+
+            let customer_group = diesel::update(customer_groups::table.find(before.id))
+                .set(customer_group_to_replace)
+                .get_result::<CustomerGroup>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updateCustomerGroup",
+                "customer_group",
+                customer_group_id,
+                Some(&before),
+                Some(&customer_group),
+            )?;
+            Ok(customer_group)
+        })
+    }
+
+    pub fn find(context: &Context, customer_group_id: i32) -> FieldResult<CustomerGroup> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(customer_groups::table
+            .filter(customer_groups::user_id.eq(context.user_id))
+            .find(customer_group_id)
+            .first(connection)?)
+    }
+
+    /// Customers of the group are left without one.
+    pub fn destroy(context: &Context, customer_group_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = CustomerGroup::find(context, customer_group_id)?;
+
+            diesel::delete(customer_groups::table.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyCustomerGroup",
+                "customer_group",
+                customer_group_id,
+                Some(&before),
+                None::<&CustomerGroup>,
+            )?;
+            Ok(true)
+        })
+    }
+}
@@ -0,0 +1,252 @@
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
+};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::product::Product;
+use crate::models::Context;
+use crate::schema::locations;
+use crate::schema::product_stocks;
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListLocation {
+    pub data: Vec<Location>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "locations"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Shop or warehouse keeping its own stock")]
+pub struct Location {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "locations"]
+pub struct FormLocation {
+    pub id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub name: Option<String>,
+}
+
+#[derive(
+    Identifiable, Associations, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(Product)]
+#[belongs_to(Location)]
+#[table_name = "product_stocks"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Stock of a product in one location")]
+pub struct ProductStock {
+    pub id: i32,
+    pub product_id: i32,
+    pub location_id: i32,
+    pub user_id: i32,
+    pub stock: f64,
+    pub min_stock: Option<f64>,
+    pub reorder_point: Option<f64>,
+    pub reorder_quantity: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+#[graphql(description = "Reorder thresholds of a product in one location")]
+pub struct FormProductStockThresholds {
+    pub product_id: i32,
+    pub location_id: i32,
+    pub min_stock: Option<f64>,
+    pub reorder_point: Option<f64>,
+    pub reorder_quantity: Option<f64>,
+}
+
+impl Location {
+    pub fn list(context: &Context) -> FieldResult<ListLocation> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(ListLocation {
+            data: locations::table
+                .filter(locations::user_id.eq(context.user_id))
+                .order(locations::name.asc())
+                .load::<Location>(connection)?,
+        })
+    }
+
+    pub fn create(context: &Context, form: FormLocation) -> FieldResult<Location> {
+        let connection: &PgConnection = &context.conn;
+
+        let new_location = FormLocation {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let location = diesel::insert_into(locations::table)
+                .values(new_location)
+                .get_result::<Location>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createLocation",
+                "location",
+                location.id,
+                None::<&Location>,
+                Some(&location),
+            )?;
+            Ok(location)
+        })
+    }
+
+    pub fn update(context: &Context, form: FormLocation) -> FieldResult<Location> {
+        let connection: &PgConnection = &context.conn;
+
+        let location_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+
+        let location_to_replace = FormLocation {
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let before = Location::find(context, location_id)?;
+
+            let location = diesel::update(locations::table.find(before.id))
+                .set(location_to_replace)
+                .get_result::<Location>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updateLocation",
+                "location",
+                location_id,
+                Some(&before),
+                Some(&location),
+            )?;
+            Ok(location)
+        })
+    }
+
+    pub fn find(context: &Context, location_id: i32) -> FieldResult<Location> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(locations::table
+            .filter(locations::user_id.eq(context.user_id))
+            .find(location_id)
+            .first(connection)?)
+    }
+
+    /// Locations still holding stock or referenced by sales can't be
+    /// removed, the database refuses it.
+    pub fn destroy(context: &Context, location_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Location::find(context, location_id)?;
+
+            diesel::delete(
+                product_stocks::table
+                    .filter(product_stocks::location_id.eq(before.id))
+                    .filter(product_stocks::stock.eq(0.0)),
+            )
+            .execute(connection)?;
+            diesel::delete(locations::table.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyLocation",
+                "location",
+                location_id,
+                Some(&before),
+                None::<&Location>,
+            )?;
+            Ok(true)
+        })
+    }
+}
+
+impl ProductStock {
+    pub fn list(context: &Context, location_id: i32) -> FieldResult<Vec<ProductStock>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(product_stocks::table
+            .filter(product_stocks::user_id.eq(context.user_id))
+            .filter(product_stocks::location_id.eq(location_id))
+            .load::<ProductStock>(connection)?)
+    }
+
+    /// Stock of a product in a location, none when it never had any.
+    pub fn stock(context: &Context, product_id: i32, location_id: i32) -> FieldResult<f64> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(product_stocks::table
+            .select(product_stocks::stock)
+            .filter(product_stocks::user_id.eq(context.user_id))
+            .filter(product_stocks::product_id.eq(product_id))
+            .filter(product_stocks::location_id.eq(location_id))
+            .first::<f64>(connection)
+            .optional()?
+            .unwrap_or(0.0))
+    }
+
+    /// Sets the thresholds of the product in the location, missing values
+    /// clear them.
+    pub fn update_thresholds(
+        context: &Context,
+        form: FormProductStockThresholds,
+    ) -> FieldResult<ProductStock> {
+        let connection: &PgConnection = &context.conn;
+
+        Product::show(context, form.product_id)?;
+        Location::find(context, form.location_id)?;
+
+        connection.transaction(|| {
+            let before = product_stocks::table
+                .filter(product_stocks::product_id.eq(form.product_id))
+                .filter(product_stocks::location_id.eq(form.location_id))
+                .first::<ProductStock>(connection)
+                .optional()?;
+
+            let product_stock = diesel::insert_into(product_stocks::table)
+                .values((
+                    product_stocks::product_id.eq(form.product_id),
+                    product_stocks::location_id.eq(form.location_id),
+                    product_stocks::user_id.eq(context.user_id),
+                    product_stocks::stock.eq(0.0),
+                    product_stocks::min_stock.eq(form.min_stock),
+                    product_stocks::reorder_point.eq(form.reorder_point),
+                    product_stocks::reorder_quantity.eq(form.reorder_quantity),
+                ))
+                .on_conflict((product_stocks::product_id, product_stocks::location_id))
+                .do_update()
+                .set((
+                    product_stocks::min_stock.eq(form.min_stock),
+                    product_stocks::reorder_point.eq(form.reorder_point),
+                    product_stocks::reorder_quantity.eq(form.reorder_quantity),
+                ))
+                .get_result::<ProductStock>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updateProductStockThresholds",
+                "product_stock",
+                product_stock.id,
+                before.as_ref(),
+                Some(&product_stock),
+            )?;
+            Ok(product_stock)
+        })
+    }
+}
@@ -0,0 +1,56 @@
+use chrono::{Local, NaiveDateTime};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+
+use crate::errors::MyStoreError;
+use crate::schema::login_attempts;
+use crate::schema::login_attempts::dsl;
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct LoginAttempt {
+    pub id: i32,
+    pub email: String,
+    pub ip_address: String,
+    pub succeeded: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "login_attempts"]
+pub struct NewLoginAttempt<'a> {
+    pub email: &'a str,
+    pub ip_address: &'a str,
+    pub succeeded: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl LoginAttempt {
+    pub fn record(
+        param_email: &str,
+        param_ip_address: &str,
+        param_succeeded: bool,
+        connection: &PgConnection,
+    ) -> Result<(), MyStoreError> {
+        diesel::insert_into(login_attempts::table)
+            .values(NewLoginAttempt {
+                email: param_email,
+                ip_address: param_ip_address,
+                succeeded: param_succeeded,
+                created_at: Local::now().naive_local(),
+            })
+            .execute(connection)?;
+        Ok(())
+    }
+
+    pub fn failures_from_ip_since(
+        param_ip_address: &str,
+        since: NaiveDateTime,
+        connection: &PgConnection,
+    ) -> Result<i64, MyStoreError> {
+        Ok(dsl::login_attempts
+            .filter(dsl::ip_address.eq(param_ip_address))
+            .filter(dsl::succeeded.eq(false))
+            .filter(dsl::created_at.gt(since))
+            .count()
+            .get_result(connection)?)
+    }
+}
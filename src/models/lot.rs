@@ -0,0 +1,246 @@
+use chrono::{Duration, Local, NaiveDate};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, PgConnection,
+    QueryDsl, RunQueryDsl,
+};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::product::{Product, PRODUCT_COLUMNS};
+use crate::models::sale::Sale;
+use crate::models::Context;
+use crate::schema;
+use crate::schema::lots;
+use crate::schema::lots::dsl;
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListLot {
+    pub data: Vec<Lot>,
+}
+
+#[derive(
+    Identifiable, Associations, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(Product)]
+#[table_name = "lots"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Batch of a product sharing a lot number and expiry date")]
+pub struct Lot {
+    pub id: i32,
+    pub product_id: i32,
+    pub user_id: i32,
+    pub lot_number: String,
+    pub expires_on: Option<NaiveDate>,
+    #[graphql(description = "Changed by receipts and adjustments posted to the lot")]
+    pub stock: f64,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+pub struct FullLot {
+    pub lot: Lot,
+    pub product: Product,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "lots"]
+pub struct FormLot {
+    pub id: Option<i32>,
+    pub product_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub lot_number: Option<String>,
+    pub expires_on: Option<NaiveDate>,
+}
+
+impl Lot {
+    pub fn list(context: &Context, param_product_id: i32) -> FieldResult<ListLot> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(ListLot {
+            data: dsl::lots
+                .filter(dsl::user_id.eq(context.user_id))
+                .filter(dsl::product_id.eq(param_product_id))
+                .order((dsl::expires_on.asc(), dsl::id.asc()))
+                .load::<Lot>(connection)?,
+        })
+    }
+
+    /// Lots start empty, stock gets in with `StockMovement::receive`.
+    pub fn create(context: &Context, form: FormLot) -> FieldResult<Lot> {
+        let connection: &PgConnection = &context.conn;
+
+        let param_product_id = form
+            .product_id
+            .ok_or(diesel::result::Error::QueryBuilderError(
+                "missing product_id".into(),
+            ))?;
+        if !Product::show(context, param_product_id)?.product.track_lots {
+            return Err(format!("Product {} doesn't track lots", param_product_id).into());
+        }
+
+        let new_lot = FormLot {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let lot = diesel::insert_into(lots::table)
+                .values(new_lot)
+                .get_result::<Lot>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createLot",
+                "lot",
+                lot.id,
+                None::<&Lot>,
+                Some(&lot),
+            )?;
+            Ok(lot)
+        })
+    }
+
+    pub fn update(context: &Context, form: FormLot) -> FieldResult<Lot> {
+        let connection: &PgConnection = &context.conn;
+
+        let lot_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+
+        let lot_to_replace = FormLot {
+            product_id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let before = Lot::find(context, lot_id)?;
+
+            let lot = diesel::update(dsl::lots.find(before.id))
+                .set(lot_to_replace)
+                .get_result::<Lot>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updateLot",
+                "lot",
+                lot_id,
+                Some(&before),
+                Some(&lot),
+            )?;
+            Ok(lot)
+        })
+    }
+
+    pub fn find(context: &Context, lot_id: i32) -> FieldResult<Lot> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(dsl::lots
+            .filter(dsl::user_id.eq(context.user_id))
+            .find(lot_id)
+            .first(connection)?)
+    }
+
+    pub fn destroy(context: &Context, lot_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Lot::find(context, lot_id)?;
+            if before.stock != 0.0 {
+                return Err(format!(
+                    "Lot {} still has stock, adjust it out first",
+                    before.lot_number
+                )
+                .into());
+            }
+
+            diesel::delete(dsl::lots.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyLot",
+                "lot",
+                lot_id,
+                Some(&before),
+                None::<&Lot>,
+            )?;
+            Ok(true)
+        })
+    }
+
+    /// First expired, first out: the unexpired lot closest to expiring that
+    /// still has `amount` in stock. Lots without expiry date go last.
+    pub fn suggest(
+        context: &Context,
+        param_product_id: i32,
+        amount: f64,
+    ) -> FieldResult<Option<Lot>> {
+        let connection: &PgConnection = &context.conn;
+        let today = Local::today().naive_local();
+
+        Ok(dsl::lots
+            .filter(dsl::user_id.eq(context.user_id))
+            .filter(dsl::product_id.eq(param_product_id))
+            .filter(dsl::stock.ge(amount))
+            .filter(dsl::expires_on.is_null().or(dsl::expires_on.ge(today)))
+            .order((dsl::expires_on.asc(), dsl::id.asc()))
+            .first::<Lot>(connection)
+            .optional()?)
+    }
+
+    /// Lots with stock left that expire within `days`, already expired
+    /// ones included.
+    pub fn expiring(context: &Context, days: i32) -> FieldResult<Vec<FullLot>> {
+        let connection: &PgConnection = &context.conn;
+        let limit_date = Local::today().naive_local() + Duration::days(i64::from(days));
+
+        Ok(dsl::lots
+            .inner_join(schema::products::table)
+            .select((
+                (
+                    dsl::id,
+                    dsl::product_id,
+                    dsl::user_id,
+                    dsl::lot_number,
+                    dsl::expires_on,
+                    dsl::stock,
+                ),
+                PRODUCT_COLUMNS,
+            ))
+            .filter(dsl::user_id.eq(context.user_id))
+            .filter(dsl::stock.gt(0.0))
+            .filter(dsl::expires_on.le(limit_date))
+            .order(dsl::expires_on.asc())
+            .load::<(Lot, Product)>(connection)?
+            .into_iter()
+            .map(|(lot, product)| FullLot { lot, product })
+            .collect())
+    }
+
+    /// Sales with a line taken from the lot, to trace where a batch went.
+    pub fn sales(context: &Context, lot_id: i32) -> FieldResult<Vec<Sale>> {
+        let connection: &PgConnection = &context.conn;
+
+        let lot = Lot::find(context, lot_id)?;
+        Ok(schema::sales::table
+            .filter(schema::sales::user_id.eq(context.user_id))
+            .filter(
+                schema::sales::id.eq_any(
+                    schema::sale_products::table
+                        .select(schema::sale_products::sale_id)
+                        .filter(schema::sale_products::lot_id.eq(lot.id)),
+                ),
+            )
+            .order(schema::sales::sale_date.desc())
+            .load::<Sale>(connection)?)
+    }
+}
@@ -1,11 +1,39 @@
+pub mod api_key;
+pub mod attachment;
+pub mod audit_event;
+pub mod backup_code;
+pub mod category;
+pub mod customer;
+pub mod location;
+pub mod login_attempt;
+pub mod lot;
 pub mod price;
+pub mod price_change;
+pub mod price_tier;
 pub mod product;
+pub mod product_barcode;
+pub mod product_component;
+pub mod product_rank;
+pub mod product_search;
+pub mod product_variant;
+pub mod reorder;
 pub mod sale;
 pub mod sale_product;
 pub mod sale_state;
+pub mod sale_state_transition;
+pub mod scheduled_price;
+pub mod search_config;
+pub mod serial_number;
+pub mod stock_count;
+pub mod stock_movement;
+pub mod supplier;
+pub mod transfer;
+pub mod unit;
 pub mod user;
 
 use crate::db_connection::PgPooledConnection;
+use crate::errors::MyStoreError;
+use juniper::FieldResult;
 use std::sync::Arc;
 
 pub fn show_query<T>(query: &T)
@@ -18,13 +46,38 @@ where
 pub struct Context {
     pub user_id: i32,
     pub conn: Arc<PgPooledConnection>,
+    // Scopes granted to the API key used for the request, `None` when the
+    // request comes from a logged in user.
+    pub scopes: Option<Vec<String>>,
 }
 
 impl juniper::Context for Context {}
 
+impl Context {
+    pub fn require_scope(&self, scope: &str) -> FieldResult<()> {
+        match &self.scopes {
+            Some(scopes) if !scopes.iter().any(|granted| granted == scope) => {
+                Err(MyStoreError::Unauthorized(format!("Missing {} scope", scope)).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn require_session(&self) -> FieldResult<()> {
+        match self.scopes {
+            Some(_) => Err(MyStoreError::Unauthorized(
+                "API keys can't perform this operation".to_string(),
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
 pub fn create_context(logged_user_id: i32, pg_pool: PgPooledConnection) -> Context {
     Context {
         user_id: logged_user_id,
         conn: Arc::new(pg_pool),
+        scopes: None,
     }
 }
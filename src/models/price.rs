@@ -1,8 +1,15 @@
-use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
+};
 use itertools::Itertools;
 use juniper::FieldResult;
+use std::collections::HashMap;
 
+use crate::models::audit_event::AuditEvent;
+use crate::models::price_change::PriceChange;
 use crate::models::product::Product;
+use crate::models::scheduled_price::ScheduledPrice;
 use crate::models::Context;
 use crate::schema::prices;
 use crate::schema::prices::dsl::*;
@@ -20,6 +27,14 @@ pub struct Price {
     pub id: i32,
     pub name: String,
     pub user_id: i32,
+    #[graphql(description = "When set the price list is hidden from listings")]
+    pub archived_at: Option<NaiveDateTime>,
+    #[graphql(description = "First day the price list applies, always when missing")]
+    pub active_from: Option<NaiveDate>,
+    #[graphql(description = "Last day the price list applies, forever when missing")]
+    pub active_to: Option<NaiveDate>,
+    #[graphql(description = "Used for customers without a price list of their own or group")]
+    pub is_default: bool,
 }
 
 #[derive(
@@ -37,6 +52,9 @@ pub struct FormPrice {
     pub id: Option<i32>,
     pub name: Option<String>,
     pub user_id: Option<i32>,
+    pub active_from: Option<NaiveDate>,
+    pub active_to: Option<NaiveDate>,
+    pub is_default: Option<bool>,
 }
 
 #[derive(Identifiable, Associations, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -56,6 +74,49 @@ pub struct PriceProduct {
 pub struct FullPriceProduct {
     pub price_product: PriceProduct,
     pub price: Price,
+    #[graphql(description = "Amount that applies today, missing when the price list doesn't")]
+    pub effective_amount: Option<i32>,
+    #[graphql(description = "Price changes prepared for the coming days")]
+    pub scheduled_prices: Vec<ScheduledPrice>,
+}
+
+impl FullPriceProduct {
+    /// `scheduled` comes from `ScheduledPrice::by_price_product`. A change
+    /// that is due but not applied yet already counts as the amount.
+    pub fn new(
+        price_product: PriceProduct,
+        price: Price,
+        scheduled: &HashMap<i32, Vec<ScheduledPrice>>,
+    ) -> FullPriceProduct {
+        let today = Local::today().naive_local();
+        let scheduled_prices = scheduled
+            .get(&price_product.id)
+            .cloned()
+            .unwrap_or_default();
+
+        let effective_amount = if price.is_active_on(today) {
+            scheduled_prices
+                .iter()
+                .filter(|scheduled_price| {
+                    scheduled_price.applied_at.is_none() && scheduled_price.starts_on <= today
+                })
+                .last()
+                .map(|scheduled_price| scheduled_price.amount)
+                .or(price_product.amount)
+        } else {
+            None
+        };
+
+        FullPriceProduct {
+            price_product,
+            price,
+            effective_amount,
+            scheduled_prices: scheduled_prices
+                .into_iter()
+                .filter(|scheduled_price| scheduled_price.starts_on > today)
+                .collect(),
+        }
+    }
 }
 
 #[derive(Insertable, Deserialize, Serialize, AsChangeset, Debug, Clone, PartialEq)]
@@ -94,12 +155,23 @@ impl PriceProductToUpdate {
                 if price_product_to_update.to_delete
                     && price_product_to_update.price_product.id.is_some()
                 {
-                    diesel::delete(
+                    let deleted = diesel::delete(
                         prices_products::table
                             .filter(prices_products::user_id.eq(context.user_id))
                             .find(price_product_to_update.price_product.id.unwrap()),
                     )
-                    .execute(connection)?;
+                    .get_result::<PriceProduct>(connection)
+                    .optional()?;
+                    if let Some(deleted) = deleted {
+                        PriceChange::record(
+                            connection,
+                            context.user_id,
+                            deleted.price_id,
+                            deleted.product_id,
+                            deleted.amount,
+                            None,
+                        )?;
+                    }
                 } else {
                     records_to_keep.push(price_product_to_update)
                 }
@@ -114,7 +186,15 @@ impl PriceProductToUpdate {
                         ..price_product.clone().price_product
                     };
 
-                    diesel::insert_into(prices_products::table)
+                    let old_amount = prices_products::table
+                        .select(prices_products::amount)
+                        .filter(prices_products::price_id.eq(new_price_product.price_id))
+                        .filter(prices_products::product_id.eq(param_product_id))
+                        .first::<Option<i32>>(connection)
+                        .optional()?
+                        .and_then(|old_amount| old_amount);
+
+                    let price_product = diesel::insert_into(prices_products::table)
                         .values(&new_price_product)
                         .on_conflict((prices_products::price_id, prices_products::product_id))
                         .do_update()
@@ -126,21 +206,32 @@ impl PriceProductToUpdate {
                             prices_products::user_id,
                             prices_products::amount,
                         ))
-                        .get_result::<PriceProduct>(connection)
+                        .get_result::<PriceProduct>(connection)?;
+
+                    PriceChange::record(
+                        connection,
+                        context.user_id,
+                        price_product.price_id,
+                        price_product.product_id,
+                        old_amount,
+                        price_product.amount,
+                    )?;
+                    Ok::<_, diesel::result::Error>(price_product)
                 })
                 .fold_results(vec![], |mut accum, value| {
                     accum.push(value);
                     accum
                 })?;
 
+            let scheduled = ScheduledPrice::by_price_product(
+                connection,
+                &product_prices.iter().map(|price_product| price_product.id).collect::<Vec<_>>(),
+            )?;
             let mut full_price_product = vec![];
             for price_product in product_prices {
                 let price = Price::find(&context, price_product.price_id)
                     .map_err(|_| diesel::result::Error::NotFound)?;
-                full_price_product.push(FullPriceProduct {
-                    price_product,
-                    price,
-                })
+                full_price_product.push(FullPriceProduct::new(price_product, price, &scheduled))
             }
             Ok(full_price_product)
         })
@@ -148,28 +239,73 @@ impl PriceProductToUpdate {
 }
 
 impl Price {
-    pub fn list(context: &Context) -> FieldResult<ListPrice> {
+    pub fn is_active_on(&self, date: NaiveDate) -> bool {
+        self.active_from.map_or(true, |from| from <= date)
+            && self.active_to.map_or(true, |to| date <= to)
+    }
+
+    fn check_dates(form: &FormPrice) -> FieldResult<()> {
+        match (form.active_from, form.active_to) {
+            (Some(from), Some(to)) if to < from => {
+                Err("The price list can't end before it starts".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Active price lists, or the archived ones when `archived` is set.
+    pub fn list(context: &Context, archived: bool) -> FieldResult<ListPrice> {
         let connection: &PgConnection = &context.conn;
 
+        let query = prices.filter(user_id.eq(context.user_id)).into_boxed();
+        let query = if archived {
+            query.filter(archived_at.is_not_null())
+        } else {
+            query.filter(archived_at.is_null())
+        };
+
         Ok(ListPrice {
-            data: prices
-                .filter(user_id.eq(context.user_id))
-                .load::<Price>(connection)?,
+            data: query.load::<Price>(connection)?,
         })
     }
 
     pub fn create(context: &Context, form: FormPrice) -> FieldResult<Price> {
         let connection: &PgConnection = &context.conn;
 
+        Price::check_dates(&form)?;
+
         let new_price = FormPrice {
             user_id: Some(context.user_id),
             ..form
         };
 
-        Ok(diesel::insert_into(prices::table)
-            .values(new_price)
-            .returning((id, name, user_id))
-            .get_result::<Price>(connection)?)
+        connection.transaction(|| {
+            if new_price.is_default == Some(true) {
+                Price::clear_default(context)?;
+            }
+            let price = diesel::insert_into(prices::table)
+                .values(new_price)
+                .returning((
+                    id,
+                    name,
+                    user_id,
+                    archived_at,
+                    active_from,
+                    active_to,
+                    is_default,
+                ))
+                .get_result::<Price>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createPrice",
+                "price",
+                price.id,
+                None::<&Price>,
+                Some(&price),
+            )?;
+            Ok(price)
+        })
     }
 
     pub fn update(context: &Context, form: FormPrice) -> FieldResult<Price> {
@@ -178,17 +314,43 @@ impl Price {
         let price_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
             "missing id".into(),
         ))?;
+        Price::check_dates(&form)?;
 
         let price_to_replace = FormPrice {
             user_id: Some(context.user_id),
             ..form.clone()
         };
 
-        let price = diesel::update(prices.filter(user_id.eq(context.user_id)).find(price_id))
-            .set(price_to_replace)
-            .get_result::<Price>(connection)?;
+        connection.transaction(|| {
+            let before = Price::find(context, price_id)?;
+            if price_to_replace.is_default == Some(true) {
+                Price::clear_default(context)?;
+            }
 
-        Ok(price)
+            let price = diesel::update(prices.filter(user_id.eq(context.user_id)).find(price_id))
+                .set(price_to_replace)
+                .get_result::<Price>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updatePrice",
+                "price",
+                price_id,
+                Some(&before),
+                Some(&price),
+            )?;
+            Ok(price)
+        })
+    }
+
+    /// A company has one default price list at most.
+    fn clear_default(context: &Context) -> Result<(), diesel::result::Error> {
+        let connection: &PgConnection = &context.conn;
+
+        diesel::update(prices.filter(user_id.eq(context.user_id)).filter(is_default.eq(true)))
+            .set(is_default.eq(false))
+            .execute(connection)?;
+        Ok(())
     }
 
     pub fn find(context: &Context, price_id: i32) -> FieldResult<Price> {
@@ -203,9 +365,62 @@ impl Price {
     pub fn destroy(context: &Context, price_id: i32) -> FieldResult<bool> {
         let connection: &PgConnection = &context.conn;
 
-        diesel::delete(prices.filter(user_id.eq(context.user_id)).find(price_id))
-            .execute(connection)?;
-        Ok(true)
+        connection.transaction(|| {
+            let before = Price::find(context, price_id)?;
+
+            diesel::delete(prices.filter(user_id.eq(context.user_id)).find(price_id))
+                .execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyPrice",
+                "price",
+                price_id,
+                Some(&before),
+                None::<&Price>,
+            )?;
+            Ok(true)
+        })
     }
 
+    /// Archived price lists keep the prices of their products.
+    pub fn archive(context: &Context, price_id: i32) -> FieldResult<Price> {
+        Price::set_archived_at(
+            context,
+            "archivePrice",
+            price_id,
+            Some(Local::now().naive_local()),
+        )
+    }
+
+    pub fn unarchive(context: &Context, price_id: i32) -> FieldResult<Price> {
+        Price::set_archived_at(context, "unarchivePrice", price_id, None)
+    }
+
+    fn set_archived_at(
+        context: &Context,
+        operation: &str,
+        price_id: i32,
+        param_archived_at: Option<NaiveDateTime>,
+    ) -> FieldResult<Price> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Price::find(context, price_id)?;
+
+            let price = diesel::update(prices.filter(user_id.eq(context.user_id)).find(price_id))
+                .set(archived_at.eq(param_archived_at))
+                .get_result::<Price>(connection)?;
+
+            AuditEvent::record(
+                context,
+                operation,
+                "price",
+                price_id,
+                Some(&before),
+                Some(&price),
+            )?;
+            Ok(price)
+        })
+    }
 }
@@ -0,0 +1,84 @@
+use chrono::{Local, NaiveDateTime};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+
+use crate::models::Context;
+use crate::schema::price_changes;
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "price_changes"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Change of the amount of a product in a price list")]
+pub struct PriceChange {
+    pub id: i32,
+    #[graphql(description = "User who changed the amount, or scheduled the change")]
+    pub user_id: i32,
+    pub price_id: i32,
+    pub product_id: i32,
+    #[graphql(description = "Missing when the product had no amount in the price list")]
+    pub old_amount: Option<i32>,
+    #[graphql(description = "Missing when the amount was removed")]
+    pub new_amount: Option<i32>,
+    pub changed_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "price_changes"]
+struct NewPriceChange {
+    user_id: i32,
+    price_id: i32,
+    product_id: i32,
+    old_amount: Option<i32>,
+    new_amount: Option<i32>,
+    changed_at: NaiveDateTime,
+}
+
+impl PriceChange {
+    /// Nothing is recorded when the amount stays the same.
+    pub fn record(
+        connection: &PgConnection,
+        user_id: i32,
+        price_id: i32,
+        product_id: i32,
+        old_amount: Option<i32>,
+        new_amount: Option<i32>,
+    ) -> Result<(), diesel::result::Error> {
+        if old_amount == new_amount {
+            return Ok(());
+        }
+
+        diesel::insert_into(price_changes::table)
+            .values(NewPriceChange {
+                user_id,
+                price_id,
+                product_id,
+                old_amount,
+                new_amount,
+                changed_at: Local::now().naive_local(),
+            })
+            .execute(connection)?;
+        Ok(())
+    }
+
+    /// Changes of a product from the latest, in every price list unless
+    /// one is given.
+    pub fn list(
+        context: &Context,
+        product_id: i32,
+        price_id: Option<i32>,
+    ) -> FieldResult<Vec<PriceChange>> {
+        let connection: &PgConnection = &context.conn;
+
+        let mut query = price_changes::table
+            .filter(price_changes::user_id.eq(context.user_id))
+            .filter(price_changes::product_id.eq(product_id))
+            .into_boxed();
+        if let Some(price_id) = price_id {
+            query = query.filter(price_changes::price_id.eq(price_id));
+        }
+
+        Ok(query
+            .order((price_changes::changed_at.desc(), price_changes::id.desc()))
+            .load::<PriceChange>(connection)?)
+    }
+}
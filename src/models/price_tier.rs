@@ -0,0 +1,293 @@
+use chrono::{Local, NaiveDate};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
+};
+use juniper::FieldResult;
+use std::cmp::Ordering;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::customer::{Customer, CustomerGroup};
+use crate::models::price::{FormPriceProduct, Price, PriceProduct};
+use crate::models::product::Product;
+use crate::models::Context;
+use crate::schema::{price_changes, price_tiers, prices, prices_products, scheduled_prices};
+
+#[derive(
+    Identifiable, Queryable, Associations, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(PriceProduct)]
+#[table_name = "price_tiers"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Amount of a product in a price list from a quantity on")]
+pub struct PriceTier {
+    pub id: i32,
+    pub user_id: i32,
+    pub price_product_id: i32,
+    pub min_quantity: f64,
+    pub amount: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+pub struct FormPriceTierLevel {
+    pub min_quantity: f64,
+    pub amount: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+pub struct FormPriceTiers {
+    pub price_id: i32,
+    pub product_id: i32,
+    #[graphql(description = "Replace the current tiers, none removes them")]
+    pub tiers: Vec<FormPriceTierLevel>,
+}
+
+#[derive(Insertable)]
+#[table_name = "price_tiers"]
+struct NewPriceTier {
+    user_id: i32,
+    price_product_id: i32,
+    min_quantity: f64,
+    amount: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, juniper::GraphQLObject)]
+#[graphql(description = "Price that applies to a sale line")]
+pub struct ResolvedPrice {
+    pub product_id: i32,
+    pub price_id: i32,
+    pub price_name: String,
+    pub customer_id: Option<i32>,
+    pub quantity: f64,
+    pub unit_amount: i32,
+    #[graphql(description = "Quantity of the tier that applied, missing for the base amount")]
+    pub tier_min_quantity: Option<f64>,
+    pub total: f64,
+}
+
+impl PriceTier {
+    /// Tiers of a product from the smallest quantity, in every price list
+    /// unless one is given.
+    pub fn list(
+        context: &Context,
+        product_id: i32,
+        price_id: Option<i32>,
+    ) -> FieldResult<Vec<PriceTier>> {
+        let connection: &PgConnection = &context.conn;
+
+        let mut query = prices_products::table
+            .filter(prices_products::user_id.eq(context.user_id))
+            .filter(prices_products::product_id.eq(product_id))
+            .select(prices_products::id)
+            .into_boxed();
+        if let Some(price_id) = price_id {
+            query = query.filter(prices_products::price_id.eq(price_id));
+        }
+        let price_product_ids = query.load::<i32>(connection)?;
+
+        Ok(price_tiers::table
+            .filter(price_tiers::price_product_id.eq_any(price_product_ids))
+            .order((
+                price_tiers::price_product_id.asc(),
+                price_tiers::min_quantity.asc(),
+            ))
+            .load::<PriceTier>(connection)?)
+    }
+
+    /// The product gets a price in the list, without amount, when it
+    /// doesn't have one yet, like for scheduled prices.
+    pub fn set_tiers(context: &Context, form: FormPriceTiers) -> FieldResult<Vec<PriceTier>> {
+        let connection: &PgConnection = &context.conn;
+
+        Price::find(context, form.price_id)?;
+        Product::show(context, form.product_id)?;
+        let mut levels = form.tiers.clone();
+        if levels
+            .iter()
+            .any(|level| !level.min_quantity.is_finite() || level.min_quantity <= 0.0)
+        {
+            return Err("Tier quantities have to be positive".into());
+        }
+        // Only finite quantities are left, they always compare.
+        levels.sort_by(|a, b| {
+            a.min_quantity
+                .partial_cmp(&b.min_quantity)
+                .unwrap_or(Ordering::Equal)
+        });
+        if levels
+            .windows(2)
+            .any(|pair| pair[0].min_quantity == pair[1].min_quantity)
+        {
+            return Err("Tier quantities have to be different".into());
+        }
+
+        connection.transaction(|| {
+            diesel::insert_into(prices_products::table)
+                .values(FormPriceProduct {
+                    id: None,
+                    price_id: form.price_id,
+                    product_id: Some(form.product_id),
+                    user_id: Some(context.user_id),
+                    amount: None,
+                })
+                .on_conflict((prices_products::price_id, prices_products::product_id))
+                .do_nothing()
+                .execute(connection)?;
+            let price_product_id = prices_products::table
+                .select(prices_products::id)
+                .filter(prices_products::price_id.eq(form.price_id))
+                .filter(prices_products::product_id.eq(form.product_id))
+                .first::<i32>(connection)?;
+
+            let before = diesel::delete(
+                price_tiers::table.filter(price_tiers::price_product_id.eq(price_product_id)),
+            )
+            .get_results::<PriceTier>(connection)?;
+            let tiers = diesel::insert_into(price_tiers::table)
+                .values(
+                    levels
+                        .iter()
+                        .map(|level| NewPriceTier {
+                            user_id: context.user_id,
+                            price_product_id,
+                            min_quantity: level.min_quantity,
+                            amount: level.amount,
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .get_results::<PriceTier>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "setPriceTiers",
+                "price_product",
+                price_product_id,
+                Some(&before),
+                Some(&tiers),
+            )?;
+            Ok(tiers)
+        })
+    }
+
+    /// Price of `quantity` units of a product for a customer on a day,
+    /// today when missing. The price list is the customer's own, then the
+    /// one of its group, then the default one, skipping those archived,
+    /// inactive that day or without a price for the product. The tier with
+    /// the largest quantity up to `quantity` overrides the amount.
+    pub fn resolve(
+        context: &Context,
+        product_id: i32,
+        customer_id: Option<i32>,
+        quantity: f64,
+        date: Option<NaiveDate>,
+    ) -> FieldResult<ResolvedPrice> {
+        let connection: &PgConnection = &context.conn;
+
+        if quantity <= 0.0 {
+            return Err("The quantity has to be positive".into());
+        }
+        let today = Local::today().naive_local();
+        let date = date.unwrap_or(today);
+        Product::show(context, product_id)?;
+
+        let mut candidates = vec![];
+        if let Some(customer_id) = customer_id {
+            let customer = Customer::find(context, customer_id)?;
+            candidates.extend(customer.price_id);
+            if let Some(customer_group_id) = customer.customer_group_id {
+                candidates.extend(CustomerGroup::find(context, customer_group_id)?.price_id);
+            }
+        }
+        candidates.extend(
+            prices::table
+                .select(prices::id)
+                .filter(prices::user_id.eq(context.user_id))
+                .filter(prices::is_default.eq(true))
+                .first::<i32>(connection)
+                .optional()?,
+        );
+
+        for price_id in candidates {
+            let price = Price::find(context, price_id)?;
+            if price.archived_at.is_some() || !price.is_active_on(date) {
+                continue;
+            }
+            let price_product = match prices_products::table
+                .filter(prices_products::price_id.eq(price_id))
+                .filter(prices_products::product_id.eq(product_id))
+                .first::<PriceProduct>(connection)
+                .optional()?
+            {
+                Some(price_product) => price_product,
+                None => continue,
+            };
+
+            let base_amount = if date < today {
+                PriceTier::amount_in_past(connection, &price_product, date)?
+            } else {
+                scheduled_prices::table
+                    .select(scheduled_prices::amount)
+                    .filter(scheduled_prices::price_product_id.eq(price_product.id))
+                    .filter(scheduled_prices::applied_at.is_null())
+                    .filter(scheduled_prices::starts_on.le(date))
+                    .order(scheduled_prices::starts_on.desc())
+                    .first::<i32>(connection)
+                    .optional()?
+                    .or(price_product.amount)
+            };
+            let tier = price_tiers::table
+                .filter(price_tiers::price_product_id.eq(price_product.id))
+                .filter(price_tiers::min_quantity.le(quantity))
+                .order(price_tiers::min_quantity.desc())
+                .first::<PriceTier>(connection)
+                .optional()?;
+
+            let unit_amount = match (&tier, base_amount) {
+                (Some(tier), _) => tier.amount,
+                (None, Some(amount)) => amount,
+                (None, None) => continue,
+            };
+            return Ok(ResolvedPrice {
+                product_id,
+                price_id,
+                price_name: price.name,
+                customer_id,
+                quantity,
+                unit_amount,
+                tier_min_quantity: tier.map(|tier| tier.min_quantity),
+                total: unit_amount as f64 * quantity,
+            });
+        }
+
+        Err(format!("Product {} has no price on {}", product_id, date).into())
+    }
+
+    /// Amount the product had at the end of a past day, according to the
+    /// recorded changes.
+    fn amount_in_past(
+        connection: &PgConnection,
+        price_product: &PriceProduct,
+        date: NaiveDate,
+    ) -> Result<Option<i32>, diesel::result::Error> {
+        let end_of_day = date.succ().and_hms(0, 0, 0);
+        let changes = price_changes::table
+            .filter(price_changes::price_id.eq(price_product.price_id))
+            .filter(price_changes::product_id.eq(price_product.product_id));
+
+        if let Some(new_amount) = changes
+            .select(price_changes::new_amount)
+            .filter(price_changes::changed_at.lt(end_of_day))
+            .order((price_changes::changed_at.desc(), price_changes::id.desc()))
+            .first::<Option<i32>>(connection)
+            .optional()?
+        {
+            return Ok(new_amount);
+        }
+        Ok(changes
+            .select(price_changes::old_amount)
+            .filter(price_changes::changed_at.ge(end_of_day))
+            .order((price_changes::changed_at.asc(), price_changes::id.asc()))
+            .first::<Option<i32>>(connection)
+            .optional()?
+            .unwrap_or(price_product.amount))
+    }
+}
@@ -1,27 +1,103 @@
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use diesel::sql_types::{BigInt, Bool, Date, Float, Float8, Integer, Nullable, Text};
 use diesel::BelongingToDsl;
 use diesel::{
-    pg::Pg, BoolExpressionMethods, ExpressionMethods, GroupedBy, PgConnection, QueryDsl,
-    RunQueryDsl,
+    pg::Pg, BoolExpressionMethods, Connection, ExpressionMethods, GroupedBy, IntoSql,
+    OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
 };
-use diesel_full_text_search::{plainto_tsquery, TsRumExtensions, TsVectorExtensions};
+use diesel_full_text_search::{TsQuery, TsRumExtensions, TsVectorExtensions};
 use juniper::FieldResult;
+use std::collections::HashMap;
 
+use crate::models::attachment::Attachment;
+use crate::models::audit_event::AuditEvent;
+use crate::models::category::Category;
+use crate::models::location::ProductStock;
 use crate::models::price::PriceProductToUpdate;
 use crate::models::price::{FormPriceProductsToUpdate, FullPriceProduct, Price, PriceProduct};
+use crate::models::product_search::{
+    FacetProductIds, ProductFacets, ProductFilter, ProductHighlight,
+};
+use crate::models::scheduled_price::ScheduledPrice;
+use crate::models::stock_movement::{
+    AdjustmentReason, StockChange, StockMovement, StockMovementKind,
+};
+use crate::models::supplier::Supplier;
+use crate::models::unit::Unit;
 use crate::models::Context;
 use crate::schema;
 use crate::schema::products;
 use crate::schema::products::dsl::*;
+use crate::storage;
+
+// `plainto_tsquery` with the text search configuration of the company the
+// user belongs to, the same one the products trigger indexes with.
+sql_function!(fn product_search_query(owner_id: Integer, query: Text) -> TsQuery);
+sql_function!(fn similarity(x: Text, y: Text) -> Float);
+// Stock of the product, or how many kits its components allow to assemble.
+sql_function!(fn product_available_stock(product_id: Integer) -> Float8);
+// pg_trgm operator, true when the trigram similarity is over the threshold.
+diesel_infix_operator!(TrigramMatches, " % ");
 
 #[derive(Debug, Clone, juniper::GraphQLObject)]
 pub struct ListProduct {
     pub data: Vec<FullProduct>,
+    #[graphql(description = "Matched words of the products listed, only when searching")]
+    pub highlights: Vec<ProductHighlight>,
+    pub facets: Option<ProductFacets>,
+}
+
+/// Products a search is limited to, besides the company and rank.
+#[derive(Clone)]
+struct ProductScope {
+    category_ids: Option<Vec<i32>>,
+    priced_ids: Option<Vec<i32>>,
+    in_stock: bool,
+    archived: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, juniper::GraphQLObject)]
 pub struct FullProduct {
     pub product: Product,
+    #[graphql(description = "Stock of the product, or how many kits can be assembled")]
+    pub available_stock: f64,
     pub price_products: Vec<FullPriceProduct>,
+    pub stocks: Vec<ProductStock>,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+#[graphql(description = "Sales of a product, optionally including its variants")]
+pub struct ProductSales {
+    pub product_id: i32,
+    pub name: String,
+    pub amount: f64,
+    pub total: f64,
+}
+
+#[derive(Debug, Clone, QueryableByName, juniper::GraphQLObject)]
+#[graphql(description = "Product proposed while the search is being typed")]
+pub struct ProductSuggestion {
+    #[sql_type = "Integer"]
+    pub product_id: i32,
+    #[sql_type = "Text"]
+    pub name: String,
+    #[sql_type = "Nullable<Text>"]
+    pub sku: Option<String>,
+    #[sql_type = "Float8"]
+    pub similarity: f64,
+}
+
+#[derive(QueryableByName)]
+struct ProductSalesRow {
+    #[sql_type = "Integer"]
+    product_id: i32,
+    #[sql_type = "diesel::sql_types::Text"]
+    name: String,
+    #[sql_type = "Float8"]
+    amount: f64,
+    #[sql_type = "Float8"]
+    total: f64,
 }
 
 #[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -35,6 +111,18 @@ pub struct Product {
     pub cost: Option<i32>,
     pub description: Option<String>,
     pub user_id: i32,
+    pub category_id: Option<i32>,
+    pub sku: Option<String>,
+    pub parent_id: Option<i32>,
+    pub unit_id: Option<i32>,
+    pub track_lots: bool,
+    pub serialized: bool,
+    pub min_stock: Option<f64>,
+    pub reorder_point: Option<f64>,
+    pub reorder_quantity: Option<f64>,
+    pub supplier_id: Option<i32>,
+    #[graphql(description = "When set the product is hidden from listings and can't be sold")]
+    pub archived_at: Option<NaiveDateTime>,
 }
 
 pub type ProductColumns = (
@@ -44,6 +132,17 @@ pub type ProductColumns = (
     products::cost,
     products::description,
     products::user_id,
+    products::category_id,
+    products::sku,
+    products::parent_id,
+    products::unit_id,
+    products::track_lots,
+    products::serialized,
+    products::min_stock,
+    products::reorder_point,
+    products::reorder_quantity,
+    products::supplier_id,
+    products::archived_at,
 );
 
 pub const PRODUCT_COLUMNS: ProductColumns = (
@@ -53,6 +152,17 @@ pub const PRODUCT_COLUMNS: ProductColumns = (
     products::cost,
     products::description,
     products::user_id,
+    products::category_id,
+    products::sku,
+    products::parent_id,
+    products::unit_id,
+    products::track_lots,
+    products::serialized,
+    products::min_stock,
+    products::reorder_point,
+    products::reorder_quantity,
+    products::supplier_id,
+    products::archived_at,
 );
 
 #[derive(
@@ -73,60 +183,281 @@ pub struct FormProduct {
     pub cost: Option<i32>,
     pub description: Option<String>,
     pub user_id: Option<i32>,
+    pub category_id: Option<i32>,
+    pub sku: Option<String>,
+    pub unit_id: Option<i32>,
+    pub track_lots: Option<bool>,
+    pub serialized: Option<bool>,
+    pub min_stock: Option<f64>,
+    pub reorder_point: Option<f64>,
+    pub reorder_quantity: Option<f64>,
+    pub supplier_id: Option<i32>,
 }
 
 impl Product {
+    /// Full text search first; when it finds nothing the search is retried
+    /// by trigram similarity on the name, so typos still find products.
+    /// Besides the page of products, the matched words of each one and the
+    /// facets of everything found are returned.
     pub fn list(
         context: &Context,
         search: String,
         limit: i32,
         rank: f64,
+        param_category_id: Option<i32>,
+        filter: Option<ProductFilter>,
     ) -> FieldResult<ListProduct> {
         let connection: &PgConnection = &context.conn;
-        let mut query = schema::products::table.into_boxed::<Pg>();
 
-        if !search.is_empty() {
+        let filter = filter.unwrap_or_default();
+        let scope = ProductScope {
+            category_ids: match param_category_id {
+                Some(param_category_id) => {
+                    Some(Category::descendant_ids(context, param_category_id)?)
+                }
+                None => None,
+            },
+            priced_ids: filter.priced_product_ids(context)?,
+            in_stock: filter.in_stock.unwrap_or(false),
+            archived: filter.archived.unwrap_or(false),
+        };
+
+        let mut fuzzy = false;
+        let mut query_products = Product::search(context, &search, rank, &scope, fuzzy)
+            .select(PRODUCT_COLUMNS)
+            .limit(i64::from(limit))
+            .load::<Product>(connection)?;
+        if query_products.is_empty() && !search.is_empty() {
+            fuzzy = true;
+            query_products = Product::search(context, &search, rank, &scope, fuzzy)
+                .select(PRODUCT_COLUMNS)
+                .limit(i64::from(limit))
+                .load::<Product>(connection)?;
+        }
+
+        let found_ids = |scope: &ProductScope| {
+            Product::search(context, &search, rank, scope, fuzzy)
+                .select(id)
+                .load::<i32>(connection)
+        };
+        let facet_ids = FacetProductIds {
+            found: found_ids(&scope)?,
+            categories: found_ids(&ProductScope {
+                category_ids: None,
+                ..scope.clone()
+            })?,
+            price_lists: found_ids(&ProductScope {
+                priced_ids: ProductFilter {
+                    price_id: None,
+                    ..filter.clone()
+                }
+                .priced_product_ids(context)?,
+                ..scope.clone()
+            })?,
+            price_ranges: found_ids(&ProductScope {
+                priced_ids: ProductFilter {
+                    min_price: None,
+                    max_price: None,
+                    ..filter.clone()
+                }
+                .priced_product_ids(context)?,
+                ..scope.clone()
+            })?,
+            in_stock: found_ids(&ProductScope {
+                in_stock: false,
+                ..scope.clone()
+            })?,
+        };
+        let listed_ids: Vec<i32> = query_products.iter().map(|product| product.id).collect();
+
+        Ok(ListProduct {
+            data: Product::with_details_grouped(connection, query_products)?,
+            highlights: ProductHighlight::list(context, &search, &listed_ids)?,
+            facets: Some(ProductFacets::for_products(
+                context,
+                &facet_ids,
+                filter.price_id,
+            )?),
+        })
+    }
+
+    fn search(
+        context: &Context,
+        search: &str,
+        rank: f64,
+        scope: &ProductScope,
+        fuzzy: bool,
+    ) -> products::BoxedQuery<'static, Pg> {
+        let mut query = schema::products::table
+            .filter(user_id.eq(context.user_id).and(product_rank.le(rank)))
+            .into_boxed::<Pg>();
+
+        if let Some(category_ids) = &scope.category_ids {
+            query = query.filter(category_id.eq_any(category_ids.clone()));
+        }
+        if let Some(priced_ids) = &scope.priced_ids {
+            query = query.filter(id.eq_any(priced_ids.clone()));
+        }
+        if scope.in_stock {
+            query = query.filter(product_available_stock(id).gt(0.0));
+        }
+        if scope.archived {
+            query = query.filter(archived_at.is_not_null());
+        } else {
+            query = query.filter(archived_at.is_null());
+        }
+
+        if search.is_empty() {
+            query = query.order(product_rank.desc());
+        } else if fuzzy {
             query = query
-                .filter(text_searchable_product_col.matches(plainto_tsquery(search.clone())))
+                .filter(TrigramMatches::new(
+                    name,
+                    search.to_string().into_sql::<Text>(),
+                ))
                 .order((
+                    similarity(name, search.to_string()).desc(),
                     product_rank.desc(),
-                    text_searchable_product_col.distance(plainto_tsquery(search)),
                 ));
         } else {
-            query = query.order(product_rank.desc());
+            query = query
+                .filter(
+                    text_searchable_product_col
+                        .matches(product_search_query(context.user_id, search.to_string())),
+                )
+                .order((
+                    product_rank.desc(),
+                    text_searchable_product_col
+                        .distance(product_search_query(context.user_id, search.to_string())),
+                ));
         }
-        let query_products = query
+        query
+    }
+
+    /// As you type search: names or SKUs starting with the prefix, or with
+    /// a word starting with it, come first; then names similar enough to
+    /// it to forgive typos. Ties go to the best ranked products.
+    pub fn suggest(
+        context: &Context,
+        prefix: String,
+        limit: i32,
+    ) -> FieldResult<Vec<ProductSuggestion>> {
+        let connection: &PgConnection = &context.conn;
+
+        let prefix = prefix.trim();
+        if prefix.is_empty() {
+            return Ok(vec![]);
+        }
+        let escaped = prefix
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+
+        Ok(diesel::sql_query(
+            "SELECT id AS product_id, name, sku, similarity(name, $2)::float8 AS similarity \
+             FROM products \
+             WHERE user_id = $1 AND archived_at IS NULL \
+               AND (name ILIKE $3 OR name ILIKE $4 OR sku ILIKE $3 OR name % $2) \
+             ORDER BY (name ILIKE $3 OR sku ILIKE $3) DESC, name ILIKE $4 DESC, \
+               similarity DESC, product_rank DESC NULLS LAST, name \
+             LIMIT $5",
+        )
+        .bind::<Integer, _>(context.user_id)
+        .bind::<Text, _>(prefix)
+        .bind::<Text, _>(format!("{}%", escaped))
+        .bind::<Text, _>(format!("% {}%", escaped))
+        .bind::<BigInt, _>(i64::from(limit))
+        .load::<ProductSuggestion>(connection)?)
+    }
+
+    /// Variants generated for a product, see `ProductOption::generate_variants`.
+    pub fn variants(context: &Context, product_id: i32) -> FieldResult<ListProduct> {
+        let connection: &PgConnection = &context.conn;
+
+        let query_products = schema::products::table
             .select(PRODUCT_COLUMNS)
-            .filter(user_id.eq(context.user_id).and(product_rank.le(rank)))
-            .limit(i64::from(limit))
+            .filter(user_id.eq(context.user_id))
+            .filter(parent_id.eq(product_id))
+            .order(name.asc())
             .load::<Product>(connection)?;
 
-        let products_with_prices = PriceProduct::belonging_to(&query_products)
-            .inner_join(schema::prices::table)
-            .load::<(PriceProduct, Price)>(connection)?
-            .grouped_by(&query_products);
-
-        let vec_full_product = query_products
-            .into_iter()
-            .zip(products_with_prices)
-            .map(|tuple_product| {
-                let full_price_product = tuple_product
-                    .1
-                    .iter()
-                    .map(|tuple_price_product| FullPriceProduct {
-                        price_product: tuple_price_product.0.clone(),
-                        price: tuple_price_product.1.clone(),
-                    })
-                    .collect();
-                FullProduct {
-                    product: tuple_product.0.clone(),
-                    price_products: full_price_product,
-                }
-            })
-            .collect();
         Ok(ListProduct {
-            data: vec_full_product,
+            data: Product::with_details_grouped(connection, query_products)?,
+            highlights: vec![],
+            facets: None,
+        })
+    }
+
+    /// A product with variants is only a grouping, sale lines have to
+    /// reference one of its variants.
+    pub fn check_sellable(context: &Context, product_id: i32) -> FieldResult<()> {
+        let connection: &PgConnection = &context.conn;
+
+        let archived = schema::products::table
+            .select(archived_at)
+            .filter(user_id.eq(context.user_id))
+            .find(product_id)
+            .first::<Option<NaiveDateTime>>(connection)?;
+        if archived.is_some() {
+            return Err(format!("Product {} is archived and can't be sold", product_id).into());
+        }
+
+        let variants_count: i64 = schema::products::table
+            .filter(user_id.eq(context.user_id))
+            .filter(parent_id.eq(product_id))
+            .count()
+            .get_result(connection)?;
+
+        if variants_count > 0 {
+            return Err(format!(
+                "Product {} has variants, one of them has to be sold instead",
+                product_id
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Sold quantities and totals per product, when `roll_up_variants` is
+    /// set the sales of variants are added to their parent product. Draft
+    /// and cancelled sales are left out.
+    pub fn sales_report(
+        context: &Context,
+        roll_up_variants: bool,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> FieldResult<Vec<ProductSales>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(diesel::sql_query(
+            "SELECT reported.id AS product_id, reported.name AS name, \
+               SUM(sale_products.amount)::float8 AS amount, \
+               SUM(sale_products.total)::float8 AS total \
+             FROM sale_products \
+             INNER JOIN sales ON sales.id = sale_products.sale_id \
+             INNER JOIN products ON products.id = sale_products.product_id \
+             INNER JOIN products reported ON reported.id = \
+               CASE WHEN $2 THEN COALESCE(products.parent_id, products.id) ELSE products.id END \
+             WHERE sales.user_id = $1 \
+               AND sales.state IN ('approved', 'partially_payed', 'payed') \
+               AND ($3::date IS NULL OR sales.sale_date >= $3) \
+               AND ($4::date IS NULL OR sales.sale_date <= $4) \
+             GROUP BY reported.id, reported.name \
+             ORDER BY total DESC",
+        )
+        .bind::<Integer, _>(context.user_id)
+        .bind::<Bool, _>(roll_up_variants)
+        .bind::<Nullable<Date>, _>(from)
+        .bind::<Nullable<Date>, _>(to)
+        .load::<ProductSalesRow>(connection)?
+        .into_iter()
+        .map(|row| ProductSales {
+            product_id: row.product_id,
+            name: row.name,
+            amount: row.amount,
+            total: row.total,
         })
+        .collect())
     }
 
     pub fn create(
@@ -136,24 +467,57 @@ impl Product {
     ) -> FieldResult<FullProduct> {
         let connection: &PgConnection = &context.conn;
 
+        if let Some(param_category_id) = form.category_id {
+            Category::find(context, param_category_id)?;
+        }
+        if let Some(param_unit_id) = form.unit_id {
+            Unit::find(context, param_unit_id)?;
+        }
+        if let Some(param_supplier_id) = form.supplier_id {
+            Supplier::find(context, param_supplier_id)?;
+        }
+
         let new_product = FormProduct {
             user_id: Some(context.user_id),
             ..form
         };
 
-        let product = diesel::insert_into(products::table)
-            .values(new_product)
-            .returning(PRODUCT_COLUMNS)
-            .get_result::<Product>(connection)?;
+        connection.transaction(|| {
+            let product = diesel::insert_into(products::table)
+                .values(new_product)
+                .returning(PRODUCT_COLUMNS)
+                .get_result::<Product>(connection)?;
 
-        let price_products = PriceProductToUpdate::batch_update(&context, prices, product.id)?;
+            let price_products = PriceProductToUpdate::batch_update(&context, prices, product.id)?;
 
-        Ok(FullProduct {
-            product,
-            price_products,
+            let full_product = FullProduct {
+                available_stock: product.stock,
+                product,
+                price_products,
+                stocks: vec![],
+                attachments: vec![],
+            };
+            AuditEvent::record(
+                context,
+                "createProduct",
+                "product",
+                full_product.product.id,
+                None::<&FullProduct>,
+                Some(&full_product),
+            )?;
+            Ok(full_product)
         })
     }
 
+    /// Kits have no stock of their own, as many can be assembled as the
+    /// scarcest component allows. Other products just report their stock.
+    pub fn available_stock(
+        connection: &PgConnection,
+        product_id: i32,
+    ) -> Result<f64, diesel::result::Error> {
+        diesel::select(product_available_stock(product_id)).get_result(connection)
+    }
+
     pub fn show(context: &Context, product_id: i32) -> FieldResult<FullProduct> {
         let connection: &PgConnection = &context.conn;
 
@@ -163,34 +527,231 @@ impl Product {
             .find(product_id)
             .first(connection)?;
 
-        let products_with_prices = PriceProduct::belonging_to(&product)
+        Product::with_details(connection, product)
+    }
+
+    /// Lookup used by point of sale scanners, the scanned code is matched
+    /// against the product barcodes first and then against the SKUs.
+    /// Archived products are not found.
+    pub fn find_by_barcode(context: &Context, scanned_code: String) -> FieldResult<FullProduct> {
+        let connection: &PgConnection = &context.conn;
+        let scanned_code = scanned_code.trim();
+
+        let product: Product = schema::products::table
+            .inner_join(schema::product_barcodes::table)
+            .select(PRODUCT_COLUMNS)
+            .filter(user_id.eq(context.user_id))
+            .filter(archived_at.is_null())
+            .filter(schema::product_barcodes::code.eq(scanned_code))
+            .first(connection)
+            .optional()?
+            .map_or_else(
+                || {
+                    schema::products::table
+                        .select(PRODUCT_COLUMNS)
+                        .filter(user_id.eq(context.user_id))
+                        .filter(archived_at.is_null())
+                        .filter(sku.eq(scanned_code))
+                        .first(connection)
+                },
+                Ok,
+            )?;
+
+        Product::with_details(connection, product)
+    }
+
+    fn with_details(connection: &PgConnection, product: Product) -> FieldResult<FullProduct> {
+        let prices_of_product = PriceProduct::belonging_to(&product)
             .inner_join(schema::prices::table)
-            .load::<(PriceProduct, Price)>(connection)?
-            .iter()
-            .map(|tuple_price_product| FullPriceProduct {
-                price_product: tuple_price_product.0.clone(),
-                price: tuple_price_product.1.clone(),
-            })
+            .load::<(PriceProduct, Price)>(connection)?;
+        let scheduled = ScheduledPrice::by_price_product(
+            connection,
+            &prices_of_product
+                .iter()
+                .map(|tuple_price_product| tuple_price_product.0.id)
+                .collect::<Vec<_>>(),
+        )?;
+        let products_with_prices = prices_of_product
+            .into_iter()
+            .map(|(price_product, price)| FullPriceProduct::new(price_product, price, &scheduled))
             .collect();
 
+        let stocks = ProductStock::belonging_to(&product).load::<ProductStock>(connection)?;
+
+        let attachments = Attachment::belonging_to(&product)
+            .order(schema::attachments::id.asc())
+            .load::<Attachment>(connection)?;
+
+        let available_stock = Product::available_stock(connection, product.id)?;
+
         Ok(FullProduct {
             product,
+            available_stock,
             price_products: products_with_prices,
+            stocks,
+            attachments,
         })
     }
 
+    fn with_details_grouped(
+        connection: &PgConnection,
+        query_products: Vec<Product>,
+    ) -> FieldResult<Vec<FullProduct>> {
+        let prices_of_products = PriceProduct::belonging_to(&query_products)
+            .inner_join(schema::prices::table)
+            .load::<(PriceProduct, Price)>(connection)?;
+        let scheduled = ScheduledPrice::by_price_product(
+            connection,
+            &prices_of_products
+                .iter()
+                .map(|tuple_price_product| tuple_price_product.0.id)
+                .collect::<Vec<_>>(),
+        )?;
+        let products_with_prices = prices_of_products.grouped_by(&query_products);
+
+        let products_stocks = ProductStock::belonging_to(&query_products)
+            .load::<ProductStock>(connection)?
+            .grouped_by(&query_products);
+
+        let products_attachments = Attachment::belonging_to(&query_products)
+            .order(schema::attachments::id.asc())
+            .load::<Attachment>(connection)?
+            .grouped_by(&query_products);
+
+        let available_stocks: HashMap<i32, f64> = products
+            .select((id, product_available_stock(id)))
+            .filter(
+                id.eq_any(
+                    query_products
+                        .iter()
+                        .map(|product| product.id)
+                        .collect::<Vec<i32>>(),
+                ),
+            )
+            .load::<(i32, f64)>(connection)?
+            .into_iter()
+            .collect();
+
+        Ok(query_products
+            .into_iter()
+            .zip(products_with_prices)
+            .zip(products_stocks)
+            .zip(products_attachments)
+            .map(|((tuple_product, stocks), attachments)| {
+                let full_price_product = tuple_product
+                    .1
+                    .iter()
+                    .map(|tuple_price_product| {
+                        FullPriceProduct::new(
+                            tuple_price_product.0.clone(),
+                            tuple_price_product.1.clone(),
+                            &scheduled,
+                        )
+                    })
+                    .collect();
+                FullProduct {
+                    product: tuple_product.0.clone(),
+                    available_stock: available_stocks
+                        .get(&tuple_product.0.id)
+                        .cloned()
+                        .unwrap_or(tuple_product.0.stock),
+                    price_products: full_price_product,
+                    stocks,
+                    attachments,
+                }
+            })
+            .collect())
+    }
+
+    /// Only products never sold can be deleted, the others have to be
+    /// archived so past sales keep their lines. Attachments go with the
+    /// product, their files are removed once it's deleted.
     pub fn destroy(context: &Context, product_id: i32) -> FieldResult<bool> {
         let connection: &PgConnection = &context.conn;
 
-        diesel::delete(
-            products
-                .filter(user_id.eq(context.user_id))
-                .find(product_id),
-        )
-        .execute(connection)?;
+        let sold: i64 = schema::sale_products::table
+            .inner_join(schema::products::table)
+            .filter(user_id.eq(context.user_id))
+            .filter(id.eq(product_id).or(parent_id.eq(product_id)))
+            .count()
+            .get_result(connection)?;
+        if sold > 0 {
+            return Err(format!("Product {} has sales, archive it instead", product_id).into());
+        }
+
+        let storage = storage::from_env()?;
+        let full_product = connection.transaction(|| {
+            let full_product = Product::show(context, product_id)?;
+
+            diesel::delete(
+                products
+                    .filter(user_id.eq(context.user_id))
+                    .find(product_id),
+            )
+            .execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyProduct",
+                "product",
+                product_id,
+                Some(&full_product),
+                None::<&FullProduct>,
+            )?;
+            Ok::<_, juniper::FieldError>(full_product)
+        })?;
+
+        Attachment::remove_files(storage.as_ref(), &full_product.attachments);
         Ok(true)
     }
 
+    /// Hides the product and its variants from listings and sales, past
+    /// sales still show them.
+    pub fn archive(context: &Context, product_id: i32) -> FieldResult<FullProduct> {
+        Product::set_archived_at(
+            context,
+            "archiveProduct",
+            product_id,
+            Some(Local::now().naive_local()),
+        )
+    }
+
+    pub fn unarchive(context: &Context, product_id: i32) -> FieldResult<FullProduct> {
+        Product::set_archived_at(context, "unarchiveProduct", product_id, None)
+    }
+
+    fn set_archived_at(
+        context: &Context,
+        operation: &str,
+        product_id: i32,
+        param_archived_at: Option<NaiveDateTime>,
+    ) -> FieldResult<FullProduct> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Product::show(context, product_id)?;
+
+            diesel::update(
+                products
+                    .filter(user_id.eq(context.user_id))
+                    .filter(id.eq(product_id).or(parent_id.eq(product_id))),
+            )
+            .set(archived_at.eq(param_archived_at))
+            .execute(connection)?;
+
+            let full_product = Product::show(context, product_id)?;
+            AuditEvent::record(
+                context,
+                operation,
+                "product",
+                product_id,
+                Some(&before),
+                Some(&full_product),
+            )?;
+            Ok(full_product)
+        })
+    }
+
     pub fn update(
         context: &Context,
         form: FormProduct,
@@ -202,25 +763,73 @@ impl Product {
             "missing id".into(),
         ))?;
 
+        if let Some(param_category_id) = form.category_id {
+            Category::find(context, param_category_id)?;
+        }
+        if let Some(param_unit_id) = form.unit_id {
+            Unit::find(context, param_unit_id)?;
+        }
+        if let Some(param_supplier_id) = form.supplier_id {
+            Supplier::find(context, param_supplier_id)?;
+        }
+
         let new_product_to_replace = FormProduct {
             user_id: Some(context.user_id),
+            stock: None,
             ..form.clone()
         };
 
-        let product = diesel::update(
-            products
-                .filter(user_id.eq(context.user_id))
-                .find(product_id),
-        )
-        .set(&new_product_to_replace)
-        .returning(PRODUCT_COLUMNS)
-        .get_result::<Product>(connection)?;
+        connection.transaction(|| {
+            let before = Product::show(context, product_id)?;
 
-        let price_products = PriceProductToUpdate::batch_update(&context, prices, product_id)?;
+            // Editing the stock by hand is an adjustment, it goes through the
+            // stock ledger instead of overwriting the column.
+            if let Some(param_stock) = form.stock {
+                if param_stock != before.product.stock {
+                    StockMovement::post(
+                        context,
+                        StockChange {
+                            product_id,
+                            location_id: None,
+                            lot_id: None,
+                            quantity: param_stock - before.product.stock,
+                            kind: StockMovementKind::Adjustment,
+                            sale_id: None,
+                            transfer_id: None,
+                            reason: Some(AdjustmentReason::Correction),
+                            stock_count_id: None,
+                        },
+                    )?;
+                }
+            }
 
-        Ok(FullProduct {
-            product,
-            price_products,
+            let product = diesel::update(
+                products
+                    .filter(user_id.eq(context.user_id))
+                    .find(product_id),
+            )
+            .set(&new_product_to_replace)
+            .returning(PRODUCT_COLUMNS)
+            .get_result::<Product>(connection)?;
+
+            let price_products = PriceProductToUpdate::batch_update(&context, prices, product_id)?;
+
+            let full_product = FullProduct {
+                available_stock: Product::available_stock(connection, product.id)?,
+                product,
+                price_products,
+                stocks: before.stocks.clone(),
+                attachments: before.attachments.clone(),
+            };
+            AuditEvent::record(
+                context,
+                "updateProduct",
+                "product",
+                product_id,
+                Some(&before),
+                Some(&full_product),
+            )?;
+            Ok(full_product)
         })
     }
 }
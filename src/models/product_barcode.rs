@@ -0,0 +1,189 @@
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::product::Product;
+use crate::models::Context;
+use crate::schema::product_barcodes;
+use crate::schema::product_barcodes::dsl;
+
+#[derive(DbEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, juniper::GraphQLEnum)]
+pub enum BarcodeSymbology {
+    Ean13,
+    UpcA,
+    Code128,
+}
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListProductBarcode {
+    pub data: Vec<ProductBarcode>,
+}
+
+#[derive(
+    Identifiable, Associations, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(Product)]
+#[table_name = "product_barcodes"]
+#[derive(juniper::GraphQLObject)]
+pub struct ProductBarcode {
+    pub id: i32,
+    pub product_id: i32,
+    pub user_id: i32,
+    pub code: String,
+    pub symbology: BarcodeSymbology,
+}
+
+#[derive(Insertable, Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[table_name = "product_barcodes"]
+#[derive(juniper::GraphQLInputObject)]
+pub struct FormProductBarcode {
+    pub product_id: i32,
+    pub user_id: Option<i32>,
+    pub code: String,
+    pub symbology: BarcodeSymbology,
+}
+
+impl BarcodeSymbology {
+    pub fn validate(self, code: &str) -> Result<(), String> {
+        match self {
+            BarcodeSymbology::Ean13 => validate_gtin(code, 13, "EAN-13"),
+            BarcodeSymbology::UpcA => validate_gtin(code, 12, "UPC-A"),
+            BarcodeSymbology::Code128 => {
+                // Code 128 carries its check symbol in the bars themselves,
+                // only the encodable characters can be checked here.
+                if !code.is_empty() && code.bytes().all(|byte| byte < 128) {
+                    Ok(())
+                } else {
+                    Err(format!("{} is not a valid Code 128 value", code))
+                }
+            }
+        }
+    }
+}
+
+/// EAN-13 and UPC-A share the same check digit, weights of 3 and 1
+/// alternate starting from the digit next to the check digit.
+fn validate_gtin(code: &str, length: usize, name: &str) -> Result<(), String> {
+    let digits: Vec<u32> = code.chars().filter_map(|c| c.to_digit(10)).collect();
+    if code.len() != length || digits.len() != length {
+        return Err(format!("{} barcodes must have {} digits", name, length));
+    }
+
+    let (check_digit, payload) = digits.split_last().unwrap();
+    let sum: u32 = payload
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| if index % 2 == 0 { digit * 3 } else { *digit })
+        .sum();
+
+    if (10 - sum % 10) % 10 == *check_digit {
+        Ok(())
+    } else {
+        Err(format!("{} has a wrong {} check digit", code, name))
+    }
+}
+
+impl ProductBarcode {
+    pub fn list(context: &Context, param_product_id: i32) -> FieldResult<ListProductBarcode> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(ListProductBarcode {
+            data: dsl::product_barcodes
+                .filter(dsl::user_id.eq(context.user_id))
+                .filter(dsl::product_id.eq(param_product_id))
+                .load::<ProductBarcode>(connection)?,
+        })
+    }
+
+    pub fn create(context: &Context, form: FormProductBarcode) -> FieldResult<ProductBarcode> {
+        let connection: &PgConnection = &context.conn;
+
+        let code = form.code.trim().to_string();
+        form.symbology.validate(&code)?;
+        Product::show(context, form.product_id)?;
+
+        let new_barcode = FormProductBarcode {
+            user_id: Some(context.user_id),
+            code,
+            ..form
+        };
+
+        connection.transaction(|| {
+            let barcode = diesel::insert_into(product_barcodes::table)
+                .values(new_barcode)
+                .get_result::<ProductBarcode>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createProductBarcode",
+                "product_barcode",
+                barcode.id,
+                None::<&ProductBarcode>,
+                Some(&barcode),
+            )?;
+            Ok(barcode)
+        })
+    }
+
+    pub fn destroy(context: &Context, barcode_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = dsl::product_barcodes
+                .filter(dsl::user_id.eq(context.user_id))
+                .find(barcode_id)
+                .first::<ProductBarcode>(connection)?;
+
+            diesel::delete(dsl::product_barcodes.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyProductBarcode",
+                "product_barcode",
+                barcode_id,
+                Some(&before),
+                None::<&ProductBarcode>,
+            )?;
+            Ok(true)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_check_digits() {
+        assert_eq!(BarcodeSymbology::Ean13.validate("4006381333931"), Ok(()));
+        assert_eq!(BarcodeSymbology::Ean13.validate("9780201379624"), Ok(()));
+        assert_eq!(BarcodeSymbology::Ean13.validate("4006381333900"), Ok(()));
+        assert_eq!(BarcodeSymbology::UpcA.validate("036000291452"), Ok(()));
+        assert_eq!(BarcodeSymbology::UpcA.validate("123456789012"), Ok(()));
+    }
+
+    #[test]
+    fn rejects_wrong_check_digits() {
+        assert!(BarcodeSymbology::Ean13.validate("4006381333932").is_err());
+        assert!(BarcodeSymbology::UpcA.validate("036000291453").is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_lengths_and_characters() {
+        // A valid UPC-A is not a valid EAN-13 without its leading zero.
+        assert!(BarcodeSymbology::Ean13.validate("036000291452").is_err());
+        assert_eq!(BarcodeSymbology::Ean13.validate("0036000291452"), Ok(()));
+        assert!(BarcodeSymbology::UpcA.validate("0036000291452").is_err());
+        assert!(BarcodeSymbology::Ean13.validate("400638133393a").is_err());
+        assert!(BarcodeSymbology::Ean13.validate("4006381 33931").is_err());
+        assert!(BarcodeSymbology::Ean13.validate("").is_err());
+    }
+
+    #[test]
+    fn code128_accepts_ascii_only() {
+        assert_eq!(BarcodeSymbology::Code128.validate("ABC-123 x"), Ok(()));
+        assert!(BarcodeSymbology::Code128.validate("").is_err());
+        assert!(BarcodeSymbology::Code128.validate("café").is_err());
+    }
+}
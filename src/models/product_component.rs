@@ -0,0 +1,255 @@
+use diesel::sql_types::{Float8, Integer};
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::product::{Product, PRODUCT_COLUMNS};
+use crate::models::Context;
+use crate::schema;
+use crate::schema::product_components;
+use crate::schema::product_components::dsl;
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+pub struct ListProductComponent {
+    pub data: Vec<FullProductComponent>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "product_components"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Product included in a kit and how many units of it")]
+pub struct ProductComponent {
+    pub id: i32,
+    pub kit_id: i32,
+    pub component_id: i32,
+    pub user_id: i32,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+pub struct FullProductComponent {
+    pub product_component: ProductComponent,
+    pub product: Product,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "product_components"]
+pub struct FormProductComponent {
+    pub id: Option<i32>,
+    pub kit_id: Option<i32>,
+    pub component_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub quantity: Option<f64>,
+}
+
+#[derive(QueryableByName, Debug, Clone, PartialEq)]
+pub struct ConsumedProduct {
+    #[sql_type = "Integer"]
+    pub product_id: i32,
+    #[sql_type = "Float8"]
+    pub quantity: f64,
+}
+
+#[derive(QueryableByName)]
+struct ComponentId {
+    #[sql_type = "Integer"]
+    id: i32,
+}
+
+impl ProductComponent {
+    pub fn list(context: &Context, kit_id: i32) -> FieldResult<ListProductComponent> {
+        let connection: &PgConnection = &context.conn;
+
+        let data = dsl::product_components
+            .inner_join(schema::products::table)
+            .select((
+                (
+                    dsl::id,
+                    dsl::kit_id,
+                    dsl::component_id,
+                    dsl::user_id,
+                    dsl::quantity,
+                ),
+                PRODUCT_COLUMNS,
+            ))
+            .filter(dsl::user_id.eq(context.user_id))
+            .filter(dsl::kit_id.eq(kit_id))
+            .load::<(ProductComponent, Product)>(connection)?
+            .into_iter()
+            .map(|(product_component, product)| FullProductComponent {
+                product_component,
+                product,
+            })
+            .collect();
+
+        Ok(ListProductComponent { data })
+    }
+
+    pub fn create(context: &Context, form: FormProductComponent) -> FieldResult<ProductComponent> {
+        let connection: &PgConnection = &context.conn;
+
+        let kit_id = form.kit_id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing kit_id".into(),
+        ))?;
+        let component_id = form
+            .component_id
+            .ok_or(diesel::result::Error::QueryBuilderError(
+                "missing component_id".into(),
+            ))?;
+        ProductComponent::check_component(context, kit_id, component_id)?;
+
+        let new_component = FormProductComponent {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let product_component = diesel::insert_into(product_components::table)
+                .values(new_component)
+                .get_result::<ProductComponent>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createProductComponent",
+                "product_component",
+                product_component.id,
+                None::<&ProductComponent>,
+                Some(&product_component),
+            )?;
+            Ok(product_component)
+        })
+    }
+
+    /// Only the quantity can be changed, a different component is a
+    /// different row.
+    pub fn update(context: &Context, form: FormProductComponent) -> FieldResult<ProductComponent> {
+        let connection: &PgConnection = &context.conn;
+
+        let product_component_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+
+        connection.transaction(|| {
+            let before = ProductComponent::find(context, product_component_id)?;
+
+            let product_component = diesel::update(dsl::product_components.find(before.id))
+                .set(dsl::quantity.eq(form.quantity.unwrap_or(before.quantity)))
+                .get_result::<ProductComponent>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updateProductComponent",
+                "product_component",
+                product_component_id,
+                Some(&before),
+                Some(&product_component),
+            )?;
+            Ok(product_component)
+        })
+    }
+
+    pub fn find(context: &Context, product_component_id: i32) -> FieldResult<ProductComponent> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(dsl::product_components
+            .filter(dsl::user_id.eq(context.user_id))
+            .find(product_component_id)
+            .first(connection)?)
+    }
+
+    pub fn destroy(context: &Context, product_component_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = ProductComponent::find(context, product_component_id)?;
+
+            diesel::delete(dsl::product_components.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyProductComponent",
+                "product_component",
+                product_component_id,
+                Some(&before),
+                None::<&ProductComponent>,
+            )?;
+            Ok(true)
+        })
+    }
+
+    /// Products whose stock is consumed when `amount` units of `product_id`
+    /// leave the store. Kits are expanded into their components, nested
+    /// kits included, any other product consumes itself.
+    pub fn consumed_products(
+        context: &Context,
+        product_id: i32,
+        amount: f64,
+    ) -> FieldResult<Vec<ConsumedProduct>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(diesel::sql_query(
+            "WITH RECURSIVE tree AS ( \
+               SELECT $1 AS product_id, $2::float8 AS quantity \
+               UNION ALL \
+               SELECT product_components.component_id, tree.quantity * product_components.quantity \
+               FROM product_components \
+               INNER JOIN tree ON product_components.kit_id = tree.product_id \
+               WHERE product_components.user_id = $3 \
+             ) \
+             SELECT product_id, SUM(quantity)::float8 AS quantity FROM tree \
+             WHERE NOT EXISTS ( \
+               SELECT 1 FROM product_components WHERE product_components.kit_id = tree.product_id \
+             ) \
+             GROUP BY product_id",
+        )
+        .bind::<Integer, _>(product_id)
+        .bind::<Float8, _>(amount)
+        .bind::<Integer, _>(context.user_id)
+        .load::<ConsumedProduct>(connection)?)
+    }
+
+    /// Kits have no stock of their own, as many can be assembled as the
+    /// scarcest component allows. Other products just report their stock.
+    pub fn available_stock(context: &Context, product_id: i32) -> FieldResult<f64> {
+        Ok(Product::show(context, product_id)?.available_stock)
+    }
+
+    fn check_component(context: &Context, kit_id: i32, component_id: i32) -> FieldResult<()> {
+        let connection: &PgConnection = &context.conn;
+
+        Product::show(context, kit_id)?;
+        Product::show(context, component_id)?;
+
+        let nested_ids: Vec<i32> = diesel::sql_query(
+            "WITH RECURSIVE tree AS ( \
+               SELECT $1 AS id \
+               UNION ALL \
+               SELECT product_components.component_id FROM product_components \
+               INNER JOIN tree ON product_components.kit_id = tree.id \
+               WHERE product_components.user_id = $2 \
+             ) \
+             SELECT id FROM tree",
+        )
+        .bind::<Integer, _>(component_id)
+        .bind::<Integer, _>(context.user_id)
+        .load::<ComponentId>(connection)?
+        .into_iter()
+        .map(|component| component.id)
+        .collect();
+
+        if nested_ids.contains(&kit_id) {
+            return Err("A kit can't contain itself".into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use diesel::sql_types::{Double, Integer, Nullable};
+use diesel::{PgConnection, RunQueryDsl};
+use std::env;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use crate::db_connection::PgPool;
+
+/// How `product_rank` is derived from sales, read from the environment
+/// with the defaults below when a variable is missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankSettings {
+    /// Only sales of the last `days` count (`RANK_DAYS`, 90).
+    pub days: i32,
+    /// Age in days at which a sale weighs half (`RANK_HALF_LIFE_DAYS`, 30).
+    pub half_life_days: f64,
+    /// Share of revenue in the rank, the rest is sold quantity
+    /// (`RANK_REVENUE_WEIGHT`, 0.5).
+    pub revenue_weight: f64,
+    /// Hours between scheduled recalculations, 0 turns the schedule off
+    /// (`RANK_INTERVAL_HOURS`, 24).
+    pub interval_hours: u64,
+}
+
+impl Default for RankSettings {
+    fn default() -> Self {
+        RankSettings {
+            days: 90,
+            half_life_days: 30.0,
+            revenue_weight: 0.5,
+            interval_hours: 24,
+        }
+    }
+}
+
+impl RankSettings {
+    pub fn from_env() -> Result<RankSettings, String> {
+        let default = RankSettings::default();
+        let settings = RankSettings {
+            days: env_or("RANK_DAYS", default.days)?,
+            half_life_days: env_or("RANK_HALF_LIFE_DAYS", default.half_life_days)?,
+            revenue_weight: env_or("RANK_REVENUE_WEIGHT", default.revenue_weight)?,
+            interval_hours: env_or("RANK_INTERVAL_HOURS", default.interval_hours)?,
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.days <= 0 {
+            return Err("RANK_DAYS should be positive".to_string());
+        }
+        if self.half_life_days <= 0.0 {
+            return Err("RANK_HALF_LIFE_DAYS should be positive".to_string());
+        }
+        if self.revenue_weight < 0.0 || self.revenue_weight > 1.0 {
+            return Err("RANK_REVENUE_WEIGHT should be between 0 and 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> Result<T, String> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| format!("{} has an invalid value: {}", name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Sets `product_rank` from recent sales: quantity and revenue of every
+/// sale line decay by half each `half_life_days`, are scaled against the
+/// best selling product of the company and blended by `revenue_weight`.
+/// Ranks end up between 0 and 1, products without recent sales get 0.
+/// Limited to one company when `user_id` is given. Returns the number of
+/// products updated.
+pub fn recalculate(
+    connection: &PgConnection,
+    settings: &RankSettings,
+    user_id: Option<i32>,
+) -> Result<usize, diesel::result::Error> {
+    diesel::sql_query(
+        "WITH decayed AS ( \
+           SELECT sale_products.product_id, sales.user_id, \
+             SUM(sale_products.amount * \
+               POWER(0.5, (CURRENT_DATE - sales.sale_date) / $2)) AS quantity, \
+             SUM(sale_products.total * \
+               POWER(0.5, (CURRENT_DATE - sales.sale_date) / $2)) AS revenue \
+           FROM sale_products \
+           INNER JOIN sales ON sales.id = sale_products.sale_id \
+           WHERE sales.state IN ('approved', 'partially_payed', 'payed') \
+             AND sales.sale_date > CURRENT_DATE - $1 \
+             AND ($4::integer IS NULL OR sales.user_id = $4) \
+           GROUP BY sale_products.product_id, sales.user_id \
+         ), scores AS ( \
+           SELECT product_id, \
+             (1 - $3) * COALESCE(quantity / NULLIF(MAX(quantity) OVER company, 0), 0) + \
+             $3 * COALESCE(revenue / NULLIF(MAX(revenue) OVER company, 0), 0) AS score \
+           FROM decayed \
+           WINDOW company AS (PARTITION BY user_id) \
+         ) \
+         UPDATE products \
+         SET product_rank = COALESCE( \
+           (SELECT scores.score FROM scores WHERE scores.product_id = products.id), 0) \
+         WHERE $4::integer IS NULL OR products.user_id = $4",
+    )
+    .bind::<Integer, _>(settings.days)
+    .bind::<Double, _>(settings.half_life_days)
+    .bind::<Double, _>(settings.revenue_weight)
+    .bind::<Nullable<Integer>, _>(user_id)
+    .execute(connection)
+}
+
+/// Recalculates the ranks of every company each `interval_hours` in a
+/// background thread, nothing is started when the interval is 0.
+pub fn schedule(pool: PgPool, settings: RankSettings) -> Option<thread::JoinHandle<()>> {
+    if settings.interval_hours == 0 {
+        return None;
+    }
+    let interval = Duration::from_secs(settings.interval_hours * 60 * 60);
+
+    Some(thread::spawn(move || loop {
+        match pool.get() {
+            Ok(connection) => match recalculate(&connection, &settings, None) {
+                Ok(updated) => log::info!("Recalculated the rank of {} products", updated),
+                Err(error) => log::error!("Couldn't recalculate product ranks: {}", error),
+            },
+            Err(error) => log::error!("Couldn't recalculate product ranks: {}", error),
+        }
+        thread::sleep(interval);
+    }))
+}
@@ -0,0 +1,310 @@
+use diesel::sql_types::{Array, Integer, Nullable, Text};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::models::product::product_available_stock;
+use crate::models::Context;
+use crate::schema::{prices_products, products};
+
+/// Number of price ranges the prices of the results are split into.
+const PRICE_RANGES: i32 = 5;
+
+#[derive(Debug, Clone, Default, PartialEq, juniper::GraphQLInputObject)]
+#[graphql(description = "Narrows the products listed, every filter given has to match")]
+pub struct ProductFilter {
+    #[graphql(description = "Price list the price filters apply to, any of them when missing")]
+    pub price_id: Option<i32>,
+    pub min_price: Option<i32>,
+    pub max_price: Option<i32>,
+    #[graphql(description = "Only products with stock left")]
+    pub in_stock: Option<bool>,
+    #[graphql(description = "Lists archived products instead of the active ones")]
+    pub archived: Option<bool>,
+}
+
+#[derive(Debug, Clone, QueryableByName, juniper::GraphQLObject)]
+#[graphql(description = "Name and description of a product with the searched words marked")]
+pub struct ProductHighlight {
+    #[sql_type = "Integer"]
+    pub product_id: i32,
+    #[sql_type = "Text"]
+    pub name: String,
+    #[sql_type = "Nullable<Text>"]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, QueryableByName, juniper::GraphQLObject)]
+pub struct CategoryFacet {
+    #[sql_type = "Nullable<Integer>"]
+    pub category_id: Option<i32>,
+    #[sql_type = "Nullable<Text>"]
+    pub name: Option<String>,
+    #[sql_type = "Integer"]
+    pub count: i32,
+}
+
+#[derive(Debug, Clone, QueryableByName, juniper::GraphQLObject)]
+pub struct PriceListFacet {
+    #[sql_type = "Integer"]
+    pub price_id: i32,
+    #[sql_type = "Text"]
+    pub name: String,
+    #[sql_type = "Integer"]
+    pub count: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, juniper::GraphQLObject)]
+#[graphql(description = "Products priced between both amounts, included")]
+pub struct PriceRangeFacet {
+    pub min_price: i32,
+    pub max_price: i32,
+    pub count: i32,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+#[graphql(
+    description = "Counts of the products found, each facet with every filter applied but its own"
+)]
+pub struct ProductFacets {
+    pub total: i32,
+    pub in_stock: i32,
+    pub categories: Vec<CategoryFacet>,
+    pub price_lists: Vec<PriceListFacet>,
+    pub price_ranges: Vec<PriceRangeFacet>,
+}
+
+/// Products found for each facet, with the search and every filter
+/// applied except the one the facet narrows.
+pub struct FacetProductIds {
+    pub found: Vec<i32>,
+    pub categories: Vec<i32>,
+    pub price_lists: Vec<i32>,
+    pub price_ranges: Vec<i32>,
+    pub in_stock: Vec<i32>,
+}
+
+impl ProductFilter {
+    /// Products priced as the filter asks, `None` when it doesn't filter
+    /// by price at all.
+    pub fn priced_product_ids(&self, context: &Context) -> FieldResult<Option<Vec<i32>>> {
+        let connection: &PgConnection = &context.conn;
+
+        if self.price_id.is_none() && self.min_price.is_none() && self.max_price.is_none() {
+            return Ok(None);
+        }
+
+        let mut query = prices_products::table
+            .select(prices_products::product_id)
+            .filter(prices_products::user_id.eq(context.user_id))
+            .filter(prices_products::amount.is_not_null())
+            .into_boxed();
+        if let Some(price_id) = self.price_id {
+            query = query.filter(prices_products::price_id.eq(price_id));
+        }
+        if let Some(min_price) = self.min_price {
+            query = query.filter(prices_products::amount.ge(min_price));
+        }
+        if let Some(max_price) = self.max_price {
+            query = query.filter(prices_products::amount.le(max_price));
+        }
+
+        Ok(Some(query.distinct().load::<i32>(connection)?))
+    }
+}
+
+impl ProductHighlight {
+    /// Snippets of the name and description of the products where the
+    /// searched words are wrapped in `<b>` tags. The text is HTML escaped
+    /// before marking it, so the tags are the only markup returned.
+    pub fn list(
+        context: &Context,
+        search: &str,
+        product_ids: &[i32],
+    ) -> FieldResult<Vec<ProductHighlight>> {
+        let connection: &PgConnection = &context.conn;
+
+        if search.is_empty() || product_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        Ok(diesel::sql_query(format!(
+            "SELECT id AS product_id, \
+               ts_headline(product_search_config(user_id), {}, \
+                 product_search_query(user_id, $2), 'HighlightAll=true') AS name, \
+               ts_headline(product_search_config(user_id), {}, \
+                 product_search_query(user_id, $2)) AS description \
+             FROM products \
+             WHERE user_id = $1 AND id = ANY($3)",
+            escape_html("name"),
+            escape_html("description"),
+        ))
+        .bind::<Integer, _>(context.user_id)
+        .bind::<Text, _>(search)
+        .bind::<Array<Integer>, _>(product_ids)
+        .load::<ProductHighlight>(connection)?)
+    }
+}
+
+/// SQL escaping the HTML special characters of a text column.
+fn escape_html(column: &str) -> String {
+    format!(
+        "replace(replace(replace(replace(replace({}, '&', '&amp;'), \
+           '<', '&lt;'), '>', '&gt;'), '\"', '&quot;'), '''', '&#39;')",
+        column
+    )
+}
+
+#[derive(QueryableByName)]
+struct PricedProduct {
+    #[sql_type = "Integer"]
+    product_id: i32,
+    #[sql_type = "Integer"]
+    amount: i32,
+}
+
+impl ProductFacets {
+    /// Facets of every product found, not only of the page returned, so
+    /// picking a category still shows how many products the others have.
+    /// The price ranges use the amounts of `price_id`, or of every price
+    /// list.
+    pub fn for_products(
+        context: &Context,
+        product_ids: &FacetProductIds,
+        price_id: Option<i32>,
+    ) -> FieldResult<ProductFacets> {
+        let connection: &PgConnection = &context.conn;
+
+        let in_stock: i64 = products::table
+            .filter(products::user_id.eq(context.user_id))
+            .filter(products::id.eq_any(&product_ids.in_stock))
+            .filter(product_available_stock(products::id).gt(0.0))
+            .count()
+            .get_result(connection)?;
+
+        let categories = diesel::sql_query(
+            "SELECT products.category_id, categories.name::text AS name, \
+               COUNT(*)::integer AS count \
+             FROM products \
+             LEFT JOIN categories ON categories.id = products.category_id \
+             WHERE products.user_id = $1 AND products.id = ANY($2) \
+             GROUP BY products.category_id, categories.name \
+             ORDER BY count DESC, categories.name",
+        )
+        .bind::<Integer, _>(context.user_id)
+        .bind::<Array<Integer>, _>(&product_ids.categories)
+        .load::<CategoryFacet>(connection)?;
+
+        let price_lists = diesel::sql_query(
+            "SELECT prices.id AS price_id, prices.name::text AS name, \
+               COUNT(DISTINCT prices_products.product_id)::integer AS count \
+             FROM prices_products \
+             INNER JOIN prices ON prices.id = prices_products.price_id \
+             WHERE prices_products.user_id = $1 AND prices_products.product_id = ANY($2) \
+               AND prices_products.amount IS NOT NULL AND prices.archived_at IS NULL \
+             GROUP BY prices.id, prices.name \
+             ORDER BY prices.name",
+        )
+        .bind::<Integer, _>(context.user_id)
+        .bind::<Array<Integer>, _>(&product_ids.price_lists)
+        .load::<PriceListFacet>(connection)?;
+
+        let priced = diesel::sql_query(
+            "SELECT product_id, amount \
+             FROM prices_products \
+             WHERE user_id = $1 AND product_id = ANY($2) AND amount IS NOT NULL \
+               AND ($3::integer IS NULL OR price_id = $3)",
+        )
+        .bind::<Integer, _>(context.user_id)
+        .bind::<Array<Integer>, _>(&product_ids.price_ranges)
+        .bind::<Nullable<Integer>, _>(price_id)
+        .load::<PricedProduct>(connection)?;
+
+        Ok(ProductFacets {
+            total: product_ids.found.len() as i32,
+            in_stock: in_stock as i32,
+            categories,
+            price_lists,
+            price_ranges: price_ranges(&priced),
+        })
+    }
+}
+
+/// Splits the span between the lowest and highest amount in up to
+/// `PRICE_RANGES` ranges of the same width, a product with several
+/// amounts in a range is counted once.
+fn price_ranges(priced: &[PricedProduct]) -> Vec<PriceRangeFacet> {
+    let (lowest, highest) = match (
+        priced.iter().map(|row| row.amount).min(),
+        priced.iter().map(|row| row.amount).max(),
+    ) {
+        (Some(lowest), Some(highest)) => (lowest, highest),
+        _ => return vec![],
+    };
+    let width = (highest - lowest + PRICE_RANGES) / PRICE_RANGES;
+
+    let mut ranges: BTreeMap<i32, BTreeSet<i32>> = BTreeMap::new();
+    for row in priced {
+        ranges
+            .entry((row.amount - lowest) / width)
+            .or_insert_with(BTreeSet::new)
+            .insert(row.product_id);
+    }
+
+    ranges
+        .into_iter()
+        .map(|(range, product_ids)| PriceRangeFacet {
+            min_price: lowest + range * width,
+            max_price: lowest + (range + 1) * width - 1,
+            count: product_ids.len() as i32,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn priced(rows: &[(i32, i32)]) -> Vec<PricedProduct> {
+        rows.iter()
+            .map(|&(product_id, amount)| PricedProduct { product_id, amount })
+            .collect()
+    }
+
+    fn range(min_price: i32, max_price: i32, count: i32) -> PriceRangeFacet {
+        PriceRangeFacet {
+            min_price,
+            max_price,
+            count,
+        }
+    }
+
+    #[test]
+    fn no_prices_make_no_ranges() {
+        assert_eq!(price_ranges(&[]), vec![]);
+    }
+
+    #[test]
+    fn a_single_price_makes_a_single_range() {
+        assert_eq!(
+            price_ranges(&priced(&[(1, 10), (2, 10)])),
+            vec![range(10, 10, 2)]
+        );
+    }
+
+    #[test]
+    fn the_width_is_rounded_up_to_cover_the_highest_price() {
+        assert_eq!(
+            price_ranges(&priced(&[(1, 10), (2, 14), (3, 20)])),
+            vec![range(10, 12, 1), range(13, 15, 1), range(19, 21, 1)]
+        );
+    }
+
+    #[test]
+    fn a_product_is_counted_once_per_range() {
+        assert_eq!(
+            price_ranges(&priced(&[(1, 10), (1, 11), (2, 12), (1, 50)])),
+            vec![range(10, 18, 2), range(46, 54, 1)]
+        );
+    }
+}
@@ -0,0 +1,502 @@
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+use std::collections::HashMap;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::price::{FormPriceProduct, FullPriceProduct, PriceProduct};
+use crate::models::price_change::PriceChange;
+use crate::models::product::{FullProduct, Product, PRODUCT_COLUMNS};
+use crate::models::Context;
+use crate::schema;
+use crate::schema::prices_products;
+use crate::schema::product_options;
+use crate::schema::product_options::dsl;
+use crate::schema::product_variant_values;
+use crate::schema::products;
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListProductOption {
+    pub data: Vec<ProductOption>,
+}
+
+#[derive(
+    Identifiable, Associations, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(Product)]
+#[table_name = "product_options"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Dimension a product varies on, like size or color")]
+pub struct ProductOption {
+    pub id: i32,
+    pub product_id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub option_values: Vec<String>,
+    pub position: i32,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "product_options"]
+pub struct FormProductOption {
+    pub id: Option<i32>,
+    pub product_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub name: Option<String>,
+    pub option_values: Option<Vec<String>>,
+    pub position: Option<i32>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "product_variant_values"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Value a variant takes for one of the options of its parent")]
+pub struct ProductVariantValue {
+    pub id: i32,
+    pub variant_id: i32,
+    pub product_option_id: i32,
+    pub value: String,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "product_variant_values"]
+struct NewProductVariantValue<'a> {
+    variant_id: i32,
+    product_option_id: i32,
+    value: &'a str,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "products"]
+struct NewVariant {
+    name: String,
+    stock: f64,
+    cost: Option<i32>,
+    description: Option<String>,
+    user_id: i32,
+    category_id: Option<i32>,
+    sku: Option<String>,
+    parent_id: i32,
+    unit_id: Option<i32>,
+    track_lots: bool,
+    serialized: bool,
+    supplier_id: Option<i32>,
+}
+
+impl ProductOption {
+    pub fn list(context: &Context, param_product_id: i32) -> FieldResult<ListProductOption> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(ListProductOption {
+            data: dsl::product_options
+                .filter(dsl::user_id.eq(context.user_id))
+                .filter(dsl::product_id.eq(param_product_id))
+                .order((dsl::position.asc(), dsl::id.asc()))
+                .load::<ProductOption>(connection)?,
+        })
+    }
+
+    pub fn create(context: &Context, form: FormProductOption) -> FieldResult<ProductOption> {
+        let connection: &PgConnection = &context.conn;
+
+        let param_product_id = form
+            .product_id
+            .ok_or(diesel::result::Error::QueryBuilderError(
+                "missing product_id".into(),
+            ))?;
+        let parent = Product::show(context, param_product_id)?;
+        if parent.product.parent_id.is_some() {
+            return Err("Variants can't have options of their own".into());
+        }
+
+        let new_option = FormProductOption {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let option = diesel::insert_into(product_options::table)
+                .values(new_option)
+                .get_result::<ProductOption>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createProductOption",
+                "product_option",
+                option.id,
+                None::<&ProductOption>,
+                Some(&option),
+            )?;
+            Ok(option)
+        })
+    }
+
+    pub fn update(context: &Context, form: FormProductOption) -> FieldResult<ProductOption> {
+        let connection: &PgConnection = &context.conn;
+
+        let option_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+
+        let option_to_replace = FormProductOption {
+            product_id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let before = ProductOption::find(context, option_id)?;
+
+            let option = diesel::update(
+                dsl::product_options
+                    .filter(dsl::user_id.eq(context.user_id))
+                    .find(option_id),
+            )
+            .set(option_to_replace)
+            .get_result::<ProductOption>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updateProductOption",
+                "product_option",
+                option_id,
+                Some(&before),
+                Some(&option),
+            )?;
+            Ok(option)
+        })
+    }
+
+    pub fn find(context: &Context, option_id: i32) -> FieldResult<ProductOption> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(dsl::product_options
+            .filter(dsl::user_id.eq(context.user_id))
+            .find(option_id)
+            .first(connection)?)
+    }
+
+    pub fn destroy(context: &Context, option_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = ProductOption::find(context, option_id)?;
+
+            diesel::delete(dsl::product_options.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyProductOption",
+                "product_option",
+                option_id,
+                Some(&before),
+                None::<&ProductOption>,
+            )?;
+            Ok(true)
+        })
+    }
+
+    /// Creates a variant for every combination of option values that
+    /// doesn't have one yet, so it can be called again after adding values.
+    /// Variants start without stock and with a copy of the parent prices.
+    pub fn generate_variants(context: &Context, product_id: i32) -> FieldResult<Vec<FullProduct>> {
+        let connection: &PgConnection = &context.conn;
+
+        let parent = Product::show(context, product_id)?;
+        let options = ProductOption::list(context, product_id)?.data;
+        if options.is_empty() {
+            return Err("The product has no options to generate variants from".into());
+        }
+
+        connection.transaction(|| {
+            let existing = ProductOption::existing_variants(connection, product_id)?;
+
+            let mut created = vec![];
+            for combination in new_combinations(&options, &existing)? {
+                let variant = diesel::insert_into(products::table)
+                    .values(NewVariant {
+                        name: format!("{} {}", parent.product.name, combination.join(" ")),
+                        stock: 0.0,
+                        cost: parent.product.cost,
+                        description: parent.product.description.clone(),
+                        user_id: context.user_id,
+                        category_id: parent.product.category_id,
+                        sku: parent.product.sku.as_ref().map(|parent_sku| {
+                            format!("{}-{}", parent_sku, combination.join("-")).to_uppercase()
+                        }),
+                        parent_id: product_id,
+                        unit_id: parent.product.unit_id,
+                        track_lots: parent.product.track_lots,
+                        serialized: parent.product.serialized,
+                        supplier_id: parent.product.supplier_id,
+                    })
+                    .returning(PRODUCT_COLUMNS)
+                    .get_result::<Product>(connection)?;
+
+                let variant_values: Vec<NewProductVariantValue> = options
+                    .iter()
+                    .zip(combination.iter())
+                    .map(|(option, value)| NewProductVariantValue {
+                        variant_id: variant.id,
+                        product_option_id: option.id,
+                        value,
+                    })
+                    .collect();
+                diesel::insert_into(product_variant_values::table)
+                    .values(&variant_values)
+                    .execute(connection)?;
+
+                let price_products = parent
+                    .price_products
+                    .iter()
+                    .map(|full_price_product| {
+                        let price_product = diesel::insert_into(prices_products::table)
+                            .values(FormPriceProduct {
+                                id: None,
+                                price_id: full_price_product.price.id,
+                                product_id: Some(variant.id),
+                                user_id: Some(context.user_id),
+                                amount: full_price_product.price_product.amount,
+                            })
+                            .get_result::<PriceProduct>(connection)?;
+                        PriceChange::record(
+                            connection,
+                            context.user_id,
+                            price_product.price_id,
+                            price_product.product_id,
+                            None,
+                            price_product.amount,
+                        )?;
+                        Ok(FullPriceProduct::new(
+                            price_product,
+                            full_price_product.price.clone(),
+                            &HashMap::new(),
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, diesel::result::Error>>()?;
+
+                let full_variant = FullProduct {
+                    available_stock: variant.stock,
+                    product: variant,
+                    price_products,
+                    stocks: vec![],
+                    attachments: vec![],
+                };
+                AuditEvent::record(
+                    context,
+                    "generateProductVariants",
+                    "product",
+                    full_variant.product.id,
+                    None::<&FullProduct>,
+                    Some(&full_variant),
+                )?;
+                created.push(full_variant);
+            }
+            Ok(created)
+        })
+    }
+
+    fn existing_variants(
+        connection: &PgConnection,
+        product_id: i32,
+    ) -> FieldResult<Vec<ExistingVariant>> {
+        let variants = products::table
+            .select((
+                products::id,
+                products::name,
+                products::archived_at.is_not_null(),
+            ))
+            .filter(products::parent_id.eq(product_id))
+            .order(products::id.asc())
+            .load::<(i32, String, bool)>(connection)?;
+        let variant_values = product_variant_values::table
+            .select((
+                product_variant_values::variant_id,
+                product_variant_values::product_option_id,
+                product_variant_values::value,
+            ))
+            .filter(
+                product_variant_values::variant_id.eq_any(
+                    variants
+                        .iter()
+                        .map(|variant| variant.0)
+                        .collect::<Vec<i32>>(),
+                ),
+            )
+            .load::<(i32, i32, String)>(connection)?;
+
+        Ok(variants
+            .into_iter()
+            .map(|(id, name, archived)| ExistingVariant {
+                name,
+                archived,
+                values: variant_values
+                    .iter()
+                    .filter(|row| row.0 == id)
+                    .map(|row| (row.1, row.2.clone()))
+                    .collect(),
+            })
+            .collect())
+    }
+}
+
+impl ProductVariantValue {
+    pub fn list(context: &Context, variant_id: i32) -> FieldResult<Vec<ProductVariantValue>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(schema::product_variant_values::table
+            .inner_join(schema::products::table)
+            .select((
+                schema::product_variant_values::id,
+                schema::product_variant_values::variant_id,
+                schema::product_variant_values::product_option_id,
+                schema::product_variant_values::value,
+            ))
+            .filter(schema::products::user_id.eq(context.user_id))
+            .filter(schema::product_variant_values::variant_id.eq(variant_id))
+            .load::<ProductVariantValue>(connection)?)
+    }
+}
+
+/// Variant already generated, with its values by option id.
+struct ExistingVariant {
+    name: String,
+    archived: bool,
+    values: HashMap<i32, String>,
+}
+
+/// Combinations no variant has yet. Variants created before an option was
+/// added have no value for it and would never match, so they are reported
+/// instead of silently getting duplicates. Archived ones are left alone.
+fn new_combinations(
+    options: &[ProductOption],
+    existing: &[ExistingVariant],
+) -> Result<Vec<Vec<String>>, String> {
+    let mut incomplete = vec![];
+    let mut existing_combinations = vec![];
+    for variant in existing {
+        let combination: Option<Vec<String>> = options
+            .iter()
+            .map(|option| variant.values.get(&option.id).cloned())
+            .collect();
+        match combination {
+            Some(combination) => existing_combinations.push(combination),
+            None if !variant.archived => incomplete.push(variant.name.as_str()),
+            None => {}
+        }
+    }
+    if !incomplete.is_empty() {
+        return Err(format!(
+            "Variants {} have no value for every option, archive them before generating variants",
+            incomplete.join(", ")
+        ));
+    }
+
+    Ok(combinations(options)
+        .into_iter()
+        .filter(|combination| !existing_combinations.contains(combination))
+        .collect())
+}
+
+/// Cartesian product of the option values, in the order of the options.
+fn combinations(options: &[ProductOption]) -> Vec<Vec<String>> {
+    options.iter().fold(vec![vec![]], |partial, option| {
+        partial
+            .iter()
+            .flat_map(|combination| {
+                option.option_values.iter().map(move |value| {
+                    let mut next = combination.clone();
+                    next.push(value.clone());
+                    next
+                })
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(id: i32, values: &[&str]) -> ProductOption {
+        ProductOption {
+            id,
+            product_id: 1,
+            user_id: 1,
+            name: format!("option {}", id),
+            option_values: values.iter().map(|value| value.to_string()).collect(),
+            position: id,
+        }
+    }
+
+    fn variant(name: &str, archived: bool, values: &[(i32, &str)]) -> ExistingVariant {
+        ExistingVariant {
+            name: name.to_string(),
+            archived,
+            values: values
+                .iter()
+                .map(|(option_id, value)| (*option_id, value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn combinations_follow_the_option_order() {
+        let options = [option(1, &["Red", "Blue"]), option(2, &["S", "M"])];
+        assert_eq!(
+            combinations(&options),
+            vec![
+                vec!["Red", "S"],
+                vec!["Red", "M"],
+                vec!["Blue", "S"],
+                vec!["Blue", "M"],
+            ]
+        );
+    }
+
+    #[test]
+    fn combinations_of_an_option_without_values_are_empty() {
+        assert!(combinations(&[option(1, &["Red"]), option(2, &[])]).is_empty());
+        assert_eq!(combinations(&[]), vec![Vec::<String>::new()]);
+    }
+
+    #[test]
+    fn regenerating_only_creates_missing_combinations() {
+        let options = [option(1, &["Red", "Blue", "Green"])];
+        let existing = [
+            variant("Shirt Red", false, &[(1, "Red")]),
+            variant("Shirt Blue", true, &[(1, "Blue")]),
+        ];
+        assert_eq!(
+            new_combinations(&options, &existing),
+            Ok(vec![vec!["Green".to_string()]])
+        );
+    }
+
+    #[test]
+    fn regenerating_after_adding_an_option_reports_incomplete_variants() {
+        let options = [option(1, &["Red", "Blue"]), option(2, &["S", "M"])];
+        let existing = [
+            variant("Shirt Red", false, &[(1, "Red")]),
+            variant("Shirt Blue", false, &[(1, "Blue")]),
+        ];
+        let error = new_combinations(&options, &existing).unwrap_err();
+        assert!(error.contains("Shirt Red, Shirt Blue"));
+
+        let archived = [
+            variant("Shirt Red", true, &[(1, "Red")]),
+            variant("Shirt Blue", true, &[(1, "Blue")]),
+            variant("Shirt Red S", false, &[(1, "Red"), (2, "S")]),
+        ];
+        assert_eq!(new_combinations(&options, &archived).unwrap().len(), 3);
+    }
+}
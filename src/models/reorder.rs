@@ -0,0 +1,198 @@
+use chrono::{Duration, Local};
+use diesel::sql_types::{Date, Float8, Integer, Nullable, Text};
+use diesel::{PgConnection, RunQueryDsl};
+use juniper::FieldResult;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::models::supplier::Supplier;
+use crate::models::Context;
+
+#[derive(Debug, Clone, QueryableByName, juniper::GraphQLObject)]
+#[graphql(description = "Product at or below its reorder point or minimum stock")]
+pub struct LowStockProduct {
+    #[sql_type = "Integer"]
+    pub product_id: i32,
+    #[sql_type = "Text"]
+    pub name: String,
+    #[sql_type = "Nullable<Integer>"]
+    pub location_id: Option<i32>,
+    #[sql_type = "Float8"]
+    pub stock: f64,
+    #[sql_type = "Nullable<Float8>"]
+    pub min_stock: Option<f64>,
+    #[sql_type = "Nullable<Float8>"]
+    pub reorder_point: Option<f64>,
+    #[sql_type = "Nullable<Float8>"]
+    pub reorder_quantity: Option<f64>,
+    #[sql_type = "Nullable<Integer>"]
+    pub supplier_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+pub struct ReorderSuggestion {
+    pub product: LowStockProduct,
+    #[graphql(description = "Average quantity sold per day in the period looked at")]
+    pub daily_sales: f64,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+#[graphql(description = "Suggestions to order from a supplier, missing for products without one")]
+pub struct SupplierReorder {
+    pub supplier: Option<Supplier>,
+    pub suggestions: Vec<ReorderSuggestion>,
+}
+
+#[derive(QueryableByName)]
+struct SoldQuantity {
+    #[sql_type = "Integer"]
+    product_id: i32,
+    #[sql_type = "Float8"]
+    quantity: f64,
+}
+
+impl LowStockProduct {
+    /// Products whose stock reached the reorder point, or fell below the
+    /// minimum when there's no reorder point. With a location the
+    /// thresholds and stock of that location are used.
+    pub fn list(context: &Context, location_id: Option<i32>) -> FieldResult<Vec<LowStockProduct>> {
+        Ok(LowStockProduct::with_thresholds(context, location_id)?
+            .into_iter()
+            .filter(|product| match (product.reorder_point, product.min_stock) {
+                (Some(reorder_point), _) => product.stock <= reorder_point,
+                (None, Some(min_stock)) => product.stock < min_stock,
+                (None, None) => false,
+            })
+            .collect())
+    }
+
+    fn with_thresholds(
+        context: &Context,
+        location_id: Option<i32>,
+    ) -> FieldResult<Vec<LowStockProduct>> {
+        let connection: &PgConnection = &context.conn;
+
+        let query = match location_id {
+            Some(_) => diesel::sql_query(
+                "SELECT products.id AS product_id, products.name, \
+                        product_stocks.location_id, product_stocks.stock, \
+                        product_stocks.min_stock, product_stocks.reorder_point, \
+                        product_stocks.reorder_quantity, products.supplier_id \
+                 FROM product_stocks \
+                 INNER JOIN products ON products.id = product_stocks.product_id \
+                 WHERE product_stocks.user_id = $1 \
+                   AND product_stocks.location_id = $2 \
+                   AND COALESCE(product_stocks.reorder_point, product_stocks.min_stock) \
+                     IS NOT NULL \
+                 ORDER BY products.name",
+            ),
+            None => diesel::sql_query(
+                "SELECT products.id AS product_id, products.name, \
+                        $2::integer AS location_id, products.stock, \
+                        products.min_stock, products.reorder_point, \
+                        products.reorder_quantity, products.supplier_id \
+                 FROM products \
+                 WHERE products.user_id = $1 \
+                   AND COALESCE(products.reorder_point, products.min_stock) IS NOT NULL \
+                 ORDER BY products.name",
+            ),
+        };
+
+        Ok(query
+            .bind::<Integer, _>(context.user_id)
+            .bind::<Nullable<Integer>, _>(location_id)
+            .load::<LowStockProduct>(connection)?)
+    }
+}
+
+impl ReorderSuggestion {
+    /// Proposes what to buy so the stock lasts until the order arrives.
+    /// Sales of the last `days` give the daily velocity, which is projected
+    /// over the lead time of the preferred supplier. A product is suggested
+    /// when its projected stock is at or below the reorder point (or the
+    /// minimum), for the shortfall or its reorder quantity, whichever is
+    /// larger.
+    pub fn list(
+        context: &Context,
+        location_id: Option<i32>,
+        days: i32,
+    ) -> FieldResult<Vec<SupplierReorder>> {
+        let connection: &PgConnection = &context.conn;
+
+        if days <= 0 {
+            return Err("The sales period should be at least one day".into());
+        }
+        let since = Local::today().naive_local() - Duration::days(i64::from(days));
+
+        let sold: HashMap<i32, f64> = diesel::sql_query(
+            "SELECT sale_products.product_id, \
+                    SUM(sale_products.amount * COALESCE(product_units.factor, 1))::float8 \
+                      AS quantity \
+             FROM sale_products \
+             INNER JOIN sales ON sales.id = sale_products.sale_id \
+             LEFT JOIN product_units ON product_units.product_id = sale_products.product_id \
+               AND product_units.unit_id = sale_products.unit_id \
+             WHERE sales.user_id = $1 \
+               AND sales.state IN ('approved', 'partially_payed', 'payed') \
+               AND sales.sale_date >= $2 \
+               AND ($3::integer IS NULL OR sales.location_id = $3) \
+             GROUP BY sale_products.product_id",
+        )
+        .bind::<Integer, _>(context.user_id)
+        .bind::<Date, _>(since)
+        .bind::<Nullable<Integer>, _>(location_id)
+        .load::<SoldQuantity>(connection)?
+        .into_iter()
+        .map(|row| (row.product_id, row.quantity))
+        .collect();
+
+        let suppliers: HashMap<i32, Supplier> = Supplier::list(context)?
+            .data
+            .into_iter()
+            .map(|supplier| (supplier.id, supplier))
+            .collect();
+
+        let mut by_supplier: BTreeMap<Option<i32>, Vec<ReorderSuggestion>> = BTreeMap::new();
+        for product in LowStockProduct::with_thresholds(context, location_id)? {
+            let threshold = match product.reorder_point.or(product.min_stock) {
+                Some(threshold) => threshold,
+                None => continue,
+            };
+            let daily_sales =
+                sold.get(&product.product_id).cloned().unwrap_or(0.0) / f64::from(days);
+            let lead_time_days = product
+                .supplier_id
+                .and_then(|supplier_id| suppliers.get(&supplier_id))
+                .map_or(0, |supplier| supplier.lead_time_days);
+
+            let needed_until_arrival = daily_sales * f64::from(lead_time_days);
+            if product.stock - needed_until_arrival > threshold {
+                continue;
+            }
+            let shortfall = threshold + needed_until_arrival - product.stock;
+            let quantity = shortfall
+                .max(product.reorder_quantity.unwrap_or(0.0))
+                .ceil();
+            if quantity <= 0.0 {
+                continue;
+            }
+
+            by_supplier
+                .entry(product.supplier_id)
+                .or_insert_with(Vec::new)
+                .push(ReorderSuggestion {
+                    product,
+                    daily_sales,
+                    quantity,
+                });
+        }
+
+        Ok(by_supplier
+            .into_iter()
+            .map(|(supplier_id, suggestions)| SupplierReorder {
+                supplier: supplier_id.and_then(|supplier_id| suppliers.get(&supplier_id).cloned()),
+                suggestions,
+            })
+            .collect())
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::{
     sql_types, BelongingToDsl, BoolExpressionMethods, Connection, ExpressionMethods, GroupedBy,
     PgConnection, QueryDsl, RunQueryDsl,
@@ -6,20 +6,31 @@ use diesel::{
 use juniper::FieldResult;
 
 use crate::errors::MyStoreError;
+use crate::models::attachment::Attachment;
+use crate::models::audit_event::AuditEvent;
+use crate::models::customer::Customer;
+use crate::models::location::Location;
+use crate::models::lot::Lot;
+use crate::models::price_tier::PriceTier;
 use crate::models::product::{Product, PRODUCT_COLUMNS};
 use crate::models::sale_product::{
     FormSaleProduct, FormSaleProducts, FullFormSaleProduct, FullSaleProduct, SaleProduct,
+    SALE_PRODUCT_COLUMNS,
 };
 use crate::models::sale_state::Event;
 use crate::models::sale_state::SaleState;
 use crate::models::sale_state::SaleStateMapping;
+use crate::models::sale_state_transition::SaleStateTransition;
+use crate::models::serial_number::SerialNumber;
+use crate::models::stock_movement::StockMovement;
+use crate::models::unit::ProductUnit;
 use crate::models::Context;
 use crate::schema;
-use crate::schema::sale_products::dsl as sale_products_dsl;
 use crate::schema::sales;
 use crate::schema::sales::dsl;
+use crate::storage;
 
-#[derive(Identifiable, Queryable, Debug, Clone, PartialEq)]
+#[derive(Identifiable, Queryable, Serialize, Debug, Clone, PartialEq)]
 #[table_name = "sales"]
 #[derive(juniper::GraphQLObject)]
 #[graphql(description = "Sale Bill")]
@@ -30,6 +41,10 @@ pub struct Sale {
     pub total: f64,
     pub bill_number: Option<String>,
     pub state: SaleState,
+    pub location_id: Option<i32>,
+    #[graphql(description = "When the sale was created, as a draft")]
+    pub created_at: NaiveDateTime,
+    pub customer_id: Option<i32>,
 }
 
 #[derive(Insertable, Deserialize, Serialize, AsChangeset, Debug, Clone, PartialEq)]
@@ -43,12 +58,16 @@ pub struct FormSale {
     pub total: Option<f64>,
     pub bill_number: Option<String>,
     pub state: Option<SaleState>,
+    pub location_id: Option<i32>,
+    #[graphql(description = "Customer whose prices fill in the lines without one")]
+    pub customer_id: Option<i32>,
 }
 
-#[derive(Debug, Clone, juniper::GraphQLObject)]
+#[derive(Debug, Clone, Serialize, juniper::GraphQLObject)]
 pub struct FullSale {
     pub sale: Sale,
     pub sale_products: Vec<FullSaleProduct>,
+    pub history: Vec<SaleStateTransition>,
 }
 
 #[derive(Debug, Clone, juniper::GraphQLObject)]
@@ -71,6 +90,9 @@ type BoxedQuery<'a> = diesel::query_builder::BoxedSelectStatement<
         sql_types::Float8,
         sql_types::Nullable<sql_types::Text>,
         SaleStateMapping,
+        sql_types::Nullable<sql_types::Integer>,
+        sql_types::Timestamp,
+        sql_types::Nullable<sql_types::Integer>,
     ),
     schema::sales::table,
     diesel::pg::Pg,
@@ -84,14 +106,38 @@ impl Sale {
             .filter(dsl::user_id.eq(context.user_id))
             .find(sale_id);
 
-        let sale = sale_query_builder.first::<Sale>(conn)?;
-        let sale_state = sale.state.next(event)?;
+        conn.transaction(|| {
+            let sale = sale_query_builder.first::<Sale>(conn)?;
+            let operation = event.operation_name();
+            let sale_state = sale.clone().state.next(event.clone())?;
 
-        diesel::update(sale_query_builder)
-            .set(dsl::state.eq(sale_state))
-            .get_result::<Sale>(conn)?;
+            let updated_sale = diesel::update(sale_query_builder)
+                .set(dsl::state.eq(sale_state.clone()))
+                .get_result::<Sale>(conn)?;
 
-        Ok(true)
+            match event {
+                Event::Approve => {
+                    SerialNumber::assign_sale(context, &updated_sale)?;
+                    StockMovement::post_sale(context, &updated_sale)?;
+                }
+                Event::Cancel => {
+                    StockMovement::revert_sale(context, sale_id)?;
+                }
+                Event::PartiallyPay | Event::Pay => {}
+            }
+
+            SaleStateTransition::record(context, sale_id, sale.state.clone(), sale_state, event)?;
+
+            AuditEvent::record(
+                context,
+                operation,
+                "sale",
+                sale_id,
+                Some(&sale),
+                Some(&updated_sale),
+            )?;
+            Ok(true)
+        })
     }
 
     pub fn list(context: &Context, search: Option<FormSale>, limit: i32) -> FieldResult<ListSale> {
@@ -105,22 +151,15 @@ impl Sale {
 
         let query_sale_products = SaleProduct::belonging_to(&query_sales)
             .inner_join(schema::products::table)
-            .select((
-                (
-                    schema::sale_products::id,
-                    schema::sale_products::product_id,
-                    schema::sale_products::sale_id,
-                    schema::sale_products::amount,
-                    schema::sale_products::discount,
-                    schema::sale_products::tax,
-                    schema::sale_products::price,
-                    schema::sale_products::total,
-                ),
-                PRODUCT_COLUMNS,
-            ))
+            .select((SALE_PRODUCT_COLUMNS, PRODUCT_COLUMNS))
             .load::<(SaleProduct, Product)>(conn)?
             .grouped_by(&query_sales);
 
+        let query_history = SaleStateTransition::belonging_to(&query_sales)
+            .order(schema::sale_state_transitions::created_at.asc())
+            .load::<SaleStateTransition>(conn)?
+            .grouped_by(&query_sales);
+
         let tuple_full_sale: Vec<(Sale, Vec<(SaleProduct, Product)>)> = query_sales
             .into_iter()
             .zip(query_sale_products)
@@ -128,7 +167,8 @@ impl Sale {
 
         let vec_full_sale = tuple_full_sale
             .iter()
-            .map(|tuple_sale| {
+            .zip(query_history)
+            .map(|(tuple_sale, history)| {
                 let full_sale_product = tuple_sale
                     .1
                     .iter()
@@ -140,6 +180,7 @@ impl Sale {
                 FullSale {
                     sale: tuple_sale.0.clone(),
                     sale_products: full_sale_product,
+                    history,
                 }
             })
             .collect();
@@ -158,19 +199,7 @@ impl Sale {
 
         let sale_products = SaleProduct::belonging_to(&sale)
             .inner_join(schema::products::table)
-            .select((
-                (
-                    schema::sale_products::id,
-                    schema::sale_products::product_id,
-                    schema::sale_products::sale_id,
-                    schema::sale_products::amount,
-                    schema::sale_products::discount,
-                    schema::sale_products::tax,
-                    schema::sale_products::price,
-                    schema::sale_products::total,
-                ),
-                PRODUCT_COLUMNS,
-            ))
+            .select((SALE_PRODUCT_COLUMNS, PRODUCT_COLUMNS))
             .load::<(SaleProduct, Product)>(conn)?
             .iter()
             .map(|tuple| FullSaleProduct {
@@ -178,9 +207,15 @@ impl Sale {
                 product: tuple.1.clone(),
             })
             .collect();
+
+        let history = SaleStateTransition::belonging_to(&sale)
+            .order(schema::sale_state_transitions::created_at.asc())
+            .load::<SaleStateTransition>(conn)?;
+
         Ok(FullSale {
             sale,
             sale_products,
+            history,
         })
     }
 
@@ -191,6 +226,19 @@ impl Sale {
     ) -> FieldResult<FullSale> {
         let conn: &PgConnection = &context.conn;
 
+        if let Some(param_location_id) = form.location_id {
+            Location::find(context, param_location_id)?;
+        }
+        if let Some(param_customer_id) = form.customer_id {
+            Customer::find(context, param_customer_id)?;
+        }
+        let form_sale_products = Sale::prepare_sale_products(
+            context,
+            form.customer_id,
+            form.sale_date,
+            form_sale_products,
+        )?;
+
         let new_sale = FormSale {
             user_id: Some(context.user_id),
             state: Some(SaleState::Draft),
@@ -207,6 +255,9 @@ impl Sale {
                     sales::dsl::total,
                     sales::dsl::bill_number,
                     sales::dsl::state,
+                    sales::dsl::location_id,
+                    sales::dsl::created_at,
+                    sales::dsl::customer_id,
                 ))
                 .get_result::<Sale>(conn)?;
 
@@ -220,16 +271,7 @@ impl Sale {
                     };
                     let sale_product = diesel::insert_into(schema::sale_products::table)
                         .values(new_sale_product)
-                        .returning((
-                            sale_products_dsl::id,
-                            sale_products_dsl::product_id,
-                            sale_products_dsl::sale_id,
-                            sale_products_dsl::amount,
-                            sale_products_dsl::discount,
-                            sale_products_dsl::tax,
-                            sale_products_dsl::price,
-                            sale_products_dsl::total,
-                        ))
+                        .returning(SALE_PRODUCT_COLUMNS)
                         .get_result::<SaleProduct>(conn);
 
                     if let Some(param_product_id) = param_new_sale_product.sale_product.product_id {
@@ -238,8 +280,16 @@ impl Sale {
                             .find(param_product_id)
                             .first(conn);
 
+                        let mut sale_product = sale_product?;
+                        if let Some(serials) = param_new_sale_product.serial_numbers {
+                            sale_product =
+                                diesel::update(schema::sale_products::table.find(sale_product.id))
+                                    .set(schema::sale_products::serials.eq(serials))
+                                    .get_result::<SaleProduct>(conn)?;
+                        }
+
                         Ok(FullSaleProduct {
-                            sale_product: sale_product?,
+                            sale_product,
                             product: product?,
                         })
                     } else {
@@ -248,10 +298,20 @@ impl Sale {
                 })
                 .collect();
 
-            Ok(FullSale {
+            let full_sale = FullSale {
                 sale,
                 sale_products: sale_products?,
-            })
+                history: vec![],
+            };
+            AuditEvent::record(
+                context,
+                "createSale",
+                "sale",
+                full_sale.sale.id,
+                None::<&FullSale>,
+                Some(&full_sale),
+            )?;
+            Ok(full_sale)
         })
     }
 
@@ -265,7 +325,26 @@ impl Sale {
             "missing id".into(),
         ))?;
 
+        if let Some(param_location_id) = form.location_id {
+            Location::find(context, param_location_id)?;
+        }
+        if let Some(param_customer_id) = form.customer_id {
+            Customer::find(context, param_customer_id)?;
+        }
+        let current = dsl::sales
+            .filter(dsl::user_id.eq(context.user_id))
+            .find(sale_id)
+            .first::<Sale>(conn)?;
+        let form_sale_products = Sale::prepare_sale_products(
+            context,
+            form.customer_id.or(current.customer_id),
+            Some(form.sale_date.unwrap_or(current.sale_date)),
+            form_sale_products,
+        )?;
+
         conn.transaction(|| {
+            let before = Sale::show(context, sale_id)?;
+
             let sale = diesel::update(
                 dsl::sales
                     .filter(
@@ -292,8 +371,16 @@ impl Sale {
                             .find(param_product_id)
                             .first(conn);
 
+                        let mut sale_product = sale_product?;
+                        if let Some(serials) = param_sale_product.serial_numbers {
+                            sale_product =
+                                diesel::update(schema::sale_products::table.find(sale_product.id))
+                                    .set(schema::sale_products::serials.eq(serials))
+                                    .get_result::<SaleProduct>(conn)?;
+                        }
+
                         Ok(FullSaleProduct {
-                            sale_product: sale_product?,
+                            sale_product,
                             product: product?,
                         })
                     } else {
@@ -302,27 +389,186 @@ impl Sale {
                 })
                 .collect();
 
-            Ok(FullSale {
+            let full_sale = FullSale {
                 sale,
                 sale_products: updated_sale_products?,
-            })
+                history: before.history.clone(),
+            };
+            AuditEvent::record(
+                context,
+                "updateSale",
+                "sale",
+                sale_id,
+                Some(&before),
+                Some(&full_sale),
+            )?;
+            Ok(full_sale)
         })
     }
 
+    /// Only drafts can be deleted, the files of their attachments are
+    /// removed with them.
     pub fn destroy(context: &Context, sale_id: i32) -> FieldResult<bool> {
         let conn: &PgConnection = &context.conn;
 
-        let deleted_rows = diesel::delete(
-            dsl::sales
-                .filter(
-                    dsl::user_id
-                        .eq(context.user_id)
-                        .and(dsl::state.eq(SaleState::Draft)),
-                )
-                .find(sale_id),
-        )
-        .execute(conn)?;
-        Ok(deleted_rows == 1)
+        let storage = storage::from_env()?;
+        let attachments = Attachment::of_owner(context, None, Some(sale_id))?;
+        let destroyed = conn.transaction(|| {
+            let before = Sale::show(context, sale_id)?;
+
+            let deleted_rows = diesel::delete(
+                dsl::sales
+                    .filter(
+                        dsl::user_id
+                            .eq(context.user_id)
+                            .and(dsl::state.eq(SaleState::Draft)),
+                    )
+                    .find(sale_id),
+            )
+            .execute(conn)?;
+
+            if deleted_rows == 1 {
+                AuditEvent::record(
+                    context,
+                    "destroySale",
+                    "sale",
+                    sale_id,
+                    Some(&before),
+                    None::<&FullSale>,
+                )?;
+            }
+            Ok::<_, juniper::FieldError>(deleted_rows == 1)
+        })?;
+
+        if destroyed {
+            Attachment::remove_files(storage.as_ref(), &attachments);
+        }
+        Ok(destroyed)
+    }
+
+    /// Validates the lines before they are stored and fills in the lot of
+    /// lot tracked products when it's missing, following FEFO. Serialized
+    /// products need a known, unsold serial number per unit, kept on the
+    /// line until the sale is approved. Lines without a price get the one
+    /// of the customer on the sale date, see `PriceTier::resolve`.
+    fn prepare_sale_products(
+        context: &Context,
+        customer_id: Option<i32>,
+        sale_date: Option<NaiveDate>,
+        form_sale_products: FormSaleProducts,
+    ) -> FieldResult<FormSaleProducts> {
+        let mut data = vec![];
+        for mut form_sale_product in form_sale_products.data {
+            if let Some(param_product_id) = form_sale_product.sale_product.product_id {
+                Product::check_sellable(context, param_product_id)?;
+                if let Some(param_unit_id) = form_sale_product.sale_product.unit_id {
+                    ProductUnit::check_sale_unit(context, param_product_id, param_unit_id)?;
+                }
+                if form_sale_product.sale_product.price.is_none() {
+                    form_sale_product.sale_product.price = Some(Sale::sale_product_price(
+                        context,
+                        param_product_id,
+                        customer_id,
+                        sale_date,
+                        &form_sale_product.sale_product,
+                    )?);
+                }
+                form_sale_product.sale_product.lot_id = Sale::sale_product_lot(
+                    context,
+                    param_product_id,
+                    &form_sale_product.sale_product,
+                )?;
+                form_sale_product.serial_numbers = Sale::sale_product_serials(
+                    context,
+                    param_product_id,
+                    &form_sale_product.sale_product,
+                    form_sale_product.serial_numbers.take(),
+                )?;
+            }
+            data.push(form_sale_product);
+        }
+        Ok(FormSaleProducts { data })
+    }
+
+    /// Price of one unit of the line, tiers apply to the quantity in the
+    /// base unit.
+    fn sale_product_price(
+        context: &Context,
+        param_product_id: i32,
+        customer_id: Option<i32>,
+        sale_date: Option<NaiveDate>,
+        sale_product: &FormSaleProduct,
+    ) -> FieldResult<i32> {
+        let factor = ProductUnit::factor(context, param_product_id, sale_product.unit_id)?;
+        let quantity = sale_product.amount.unwrap_or(0.0) * factor;
+        let resolved =
+            PriceTier::resolve(context, param_product_id, customer_id, quantity, sale_date)?;
+
+        Ok((f64::from(resolved.unit_amount) * factor).round() as i32)
+    }
+
+    fn sale_product_serials(
+        context: &Context,
+        param_product_id: i32,
+        sale_product: &FormSaleProduct,
+        serials: Option<Vec<String>>,
+    ) -> FieldResult<Option<Vec<String>>> {
+        let product = Product::show(context, param_product_id)?.product;
+
+        match (product.serialized, serials) {
+            (false, None) => Ok(None),
+            (false, Some(_)) => {
+                Err(format!("Product {} isn't serialized", param_product_id).into())
+            }
+            (true, serials) => {
+                let factor = ProductUnit::factor(context, param_product_id, sale_product.unit_id)?;
+                let amount = sale_product.amount.unwrap_or(0.0) * factor;
+                Ok(Some(SerialNumber::check_sale_serials(
+                    context,
+                    param_product_id,
+                    amount,
+                    serials.unwrap_or_default(),
+                )?))
+            }
+        }
+    }
+
+    fn sale_product_lot(
+        context: &Context,
+        param_product_id: i32,
+        sale_product: &FormSaleProduct,
+    ) -> FieldResult<Option<i32>> {
+        let product = Product::show(context, param_product_id)?.product;
+
+        match (product.track_lots, sale_product.lot_id) {
+            (false, None) => Ok(None),
+            (false, Some(_)) => {
+                Err(format!("Product {} doesn't track lots", param_product_id).into())
+            }
+            (true, Some(param_lot_id)) => {
+                let lot = Lot::find(context, param_lot_id)?;
+                if lot.product_id != param_product_id {
+                    return Err(format!(
+                        "Lot {} doesn't belong to product {}",
+                        lot.lot_number, param_product_id
+                    )
+                    .into());
+                }
+                Ok(Some(lot.id))
+            }
+            (true, None) => {
+                let factor = ProductUnit::factor(context, param_product_id, sale_product.unit_id)?;
+                let amount = sale_product.amount.unwrap_or(0.0) * factor;
+                match Lot::suggest(context, param_product_id, amount)? {
+                    Some(lot) => Ok(Some(lot.id)),
+                    None => Err(format!(
+                        "No lot of product {} has enough stock, pick one",
+                        param_product_id
+                    )
+                    .into()),
+                }
+            }
+        }
     }
 
     fn searching_records<'a>(search: Option<FormSale>) -> BoxedQuery<'a> {
@@ -335,6 +581,9 @@ impl Sale {
             if let Some(sale_bill_number) = sale.bill_number {
                 query = query.filter(dsl::bill_number.eq(sale_bill_number));
             }
+            if let Some(sale_customer_id) = sale.customer_id {
+                query = query.filter(dsl::customer_id.eq(sale_customer_id));
+            }
         }
 
         query
@@ -2,7 +2,7 @@ use crate::models::product::{FormProduct, Product};
 use crate::models::sale::Sale;
 use crate::schema::sale_products;
 
-#[derive(Identifiable, Associations, Queryable, Debug, Clone, PartialEq)]
+#[derive(Identifiable, Associations, Queryable, Serialize, Debug, Clone, PartialEq)]
 #[table_name = "sale_products"]
 #[belongs_to(Sale)]
 #[belongs_to(Product)]
@@ -17,9 +17,43 @@ pub struct SaleProduct {
     pub tax: i32,
     pub price: i32,
     pub total: f64,
+    pub unit_id: Option<i32>,
+    pub lot_id: Option<i32>,
+    #[graphql(
+        description = "Serials asked for the line, assigned to it when the sale is approved"
+    )]
+    pub serials: Option<Vec<String>>,
 }
 
-#[derive(juniper::GraphQLObject, Debug, Clone)]
+pub type SaleProductColumns = (
+    sale_products::id,
+    sale_products::product_id,
+    sale_products::sale_id,
+    sale_products::amount,
+    sale_products::discount,
+    sale_products::tax,
+    sale_products::price,
+    sale_products::total,
+    sale_products::unit_id,
+    sale_products::lot_id,
+    sale_products::serials,
+);
+
+pub const SALE_PRODUCT_COLUMNS: SaleProductColumns = (
+    sale_products::id,
+    sale_products::product_id,
+    sale_products::sale_id,
+    sale_products::amount,
+    sale_products::discount,
+    sale_products::tax,
+    sale_products::price,
+    sale_products::total,
+    sale_products::unit_id,
+    sale_products::lot_id,
+    sale_products::serials,
+);
+
+#[derive(juniper::GraphQLObject, Serialize, Debug, Clone)]
 pub struct FullSaleProduct {
     pub sale_product: SaleProduct,
     pub product: Product,
@@ -38,12 +72,16 @@ pub struct FormSaleProduct {
     pub tax: Option<i32>,
     pub price: Option<i32>,
     pub total: Option<f64>,
+    pub unit_id: Option<i32>,
+    pub lot_id: Option<i32>,
 }
 
 #[derive(juniper::GraphQLInputObject, Debug, Clone)]
 pub struct FullFormSaleProduct {
     pub sale_product: FormSaleProduct,
     pub product: FormProduct,
+    #[graphql(description = "One serial per unit, required for serialized products")]
+    pub serial_numbers: Option<Vec<String>>,
 }
 
 #[derive(juniper::GraphQLInputObject)]
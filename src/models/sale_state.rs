@@ -7,7 +7,8 @@ pub enum SaleState {
     Cancelled,
 }
 
-#[derive(Debug)]
+#[derive(DbEnum, Debug, Clone, PartialEq, Serialize, Deserialize, juniper::GraphQLEnum)]
+#[PgType = "sale_event"]
 pub enum Event {
     Approve,
     Cancel,
@@ -15,6 +16,17 @@ pub enum Event {
     Pay,
 }
 
+impl Event {
+    pub fn operation_name(&self) -> &'static str {
+        match self {
+            Event::Approve => "approveSale",
+            Event::Cancel => "cancelSale",
+            Event::PartiallyPay => "partiallyPaySale",
+            Event::Pay => "paySale",
+        }
+    }
+}
+
 impl SaleState {
     pub fn next(self, event: Event) -> Result<SaleState, String> {
         match (self, event) {
@@ -0,0 +1,117 @@
+use chrono::{Local, NaiveDateTime};
+use diesel::sql_types::{Float8, Int8, Nullable};
+use diesel::{PgConnection, RunQueryDsl};
+use juniper::FieldResult;
+
+use crate::models::sale::Sale;
+use crate::models::sale_state::{Event, SaleState, SaleStateMapping};
+use crate::models::Context;
+use crate::schema::sale_state_transitions;
+
+#[derive(Identifiable, Associations, Queryable, Serialize, Debug, Clone, PartialEq)]
+#[belongs_to(Sale)]
+#[table_name = "sale_state_transitions"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Change of state of a sale")]
+pub struct SaleStateTransition {
+    pub id: i32,
+    pub sale_id: i32,
+    pub from_state: SaleState,
+    pub to_state: SaleState,
+    pub event: Event,
+    pub user_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "sale_state_transitions"]
+struct NewSaleStateTransition {
+    sale_id: i32,
+    from_state: SaleState,
+    to_state: SaleState,
+    event: Event,
+    user_id: i32,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+#[graphql(description = "Average time sales took to go from one state to another")]
+pub struct StateDurationReport {
+    pub from_state: SaleState,
+    pub to_state: SaleState,
+    pub average_seconds: Option<f64>,
+    pub sales_count: i32,
+}
+
+#[derive(QueryableByName)]
+struct StateDurationRow {
+    #[sql_type = "Nullable<Float8>"]
+    average_seconds: Option<f64>,
+    #[sql_type = "Int8"]
+    sales_count: i64,
+}
+
+impl SaleStateTransition {
+    pub fn record(
+        context: &Context,
+        sale_id: i32,
+        from_state: SaleState,
+        to_state: SaleState,
+        event: Event,
+    ) -> FieldResult<SaleStateTransition> {
+        let conn: &PgConnection = &context.conn;
+
+        Ok(diesel::insert_into(sale_state_transitions::table)
+            .values(NewSaleStateTransition {
+                sale_id,
+                from_state,
+                to_state,
+                event,
+                user_id: context.user_id,
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<SaleStateTransition>(conn)?)
+    }
+
+    /// Average time between the first time a sale reached `from_state` and
+    /// the first time it reached `to_state`, e.g. from approval to payment.
+    /// Sales are created as drafts without a transition, so they reach
+    /// `Draft` when created.
+    pub fn average_duration(
+        context: &Context,
+        from_state: SaleState,
+        to_state: SaleState,
+    ) -> FieldResult<StateDurationReport> {
+        let conn: &PgConnection = &context.conn;
+
+        let row = diesel::sql_query(
+            "SELECT AVG(EXTRACT(EPOCH FROM (reached.created_at - started.created_at)))::float8 \
+                 AS average_seconds, \
+             COUNT(*) AS sales_count \
+             FROM ( \
+               SELECT sale_id, MIN(created_at) AS created_at FROM ( \
+                 SELECT sale_id, created_at FROM sale_state_transitions WHERE to_state = $2 \
+                 UNION ALL \
+                 SELECT id, created_at FROM sales WHERE $2 = 'draft' \
+               ) entered GROUP BY sale_id \
+             ) started \
+             INNER JOIN ( \
+               SELECT sale_id, MIN(created_at) AS created_at FROM sale_state_transitions \
+               WHERE to_state = $3 GROUP BY sale_id \
+             ) reached ON reached.sale_id = started.sale_id \
+             INNER JOIN sales ON sales.id = started.sale_id \
+             WHERE sales.user_id = $1 AND reached.created_at >= started.created_at",
+        )
+        .bind::<diesel::sql_types::Integer, _>(context.user_id)
+        .bind::<SaleStateMapping, _>(from_state.clone())
+        .bind::<SaleStateMapping, _>(to_state.clone())
+        .get_result::<StateDurationRow>(conn)?;
+
+        Ok(StateDurationReport {
+            from_state,
+            to_state,
+            average_seconds: row.average_seconds,
+            sales_count: row.sales_count as i32,
+        })
+    }
+}
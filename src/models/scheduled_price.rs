@@ -0,0 +1,221 @@
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use crate::db_connection::PgPool;
+use crate::models::audit_event::AuditEvent;
+use crate::models::price::{FormPriceProduct, Price, PriceProduct};
+use crate::models::product::Product;
+use crate::models::Context;
+use crate::schema::{prices_products, scheduled_prices};
+
+/// Due scheduled prices are applied every hour.
+const APPLY_INTERVAL_SECS: u64 = 60 * 60;
+
+#[derive(
+    Identifiable, Queryable, Associations, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(PriceProduct)]
+#[table_name = "scheduled_prices"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Amount a product will have in a price list from a given day")]
+pub struct ScheduledPrice {
+    pub id: i32,
+    pub user_id: i32,
+    pub price_product_id: i32,
+    pub amount: i32,
+    pub starts_on: NaiveDate,
+    pub created_at: NaiveDateTime,
+    #[graphql(description = "When the amount was copied to the price of the product")]
+    pub applied_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+pub struct FormScheduledPrice {
+    pub price_id: i32,
+    pub product_id: i32,
+    pub amount: i32,
+    pub starts_on: NaiveDate,
+}
+
+#[derive(Insertable)]
+#[table_name = "scheduled_prices"]
+struct NewScheduledPrice {
+    user_id: i32,
+    price_product_id: i32,
+    amount: i32,
+    starts_on: NaiveDate,
+    created_at: NaiveDateTime,
+}
+
+impl ScheduledPrice {
+    /// Scheduled prices of the given prices of products, by price product
+    /// and from the earliest day.
+    pub fn by_price_product(
+        connection: &PgConnection,
+        price_product_ids: &[i32],
+    ) -> Result<HashMap<i32, Vec<ScheduledPrice>>, diesel::result::Error> {
+        let mut scheduled: HashMap<i32, Vec<ScheduledPrice>> = HashMap::new();
+        for scheduled_price in scheduled_prices::table
+            .filter(scheduled_prices::price_product_id.eq_any(price_product_ids))
+            .order(scheduled_prices::starts_on.asc())
+            .load::<ScheduledPrice>(connection)?
+        {
+            scheduled
+                .entry(scheduled_price.price_product_id)
+                .or_insert_with(Vec::new)
+                .push(scheduled_price);
+        }
+        Ok(scheduled)
+    }
+
+    /// Prepares a price change, the product gets a price in the list,
+    /// without amount, when it doesn't have one yet. Scheduling again the
+    /// same day replaces the amount.
+    pub fn create(context: &Context, form: FormScheduledPrice) -> FieldResult<ScheduledPrice> {
+        let connection: &PgConnection = &context.conn;
+
+        if form.starts_on <= Local::today().naive_local() {
+            return Err("Prices can only be scheduled for a future day".into());
+        }
+        let price = Price::find(context, form.price_id)?;
+        if !price.is_active_on(form.starts_on) {
+            return Err(format!(
+                "Price list {} is not active on {}",
+                price.name, form.starts_on
+            )
+            .into());
+        }
+        Product::show(context, form.product_id)?;
+
+        connection.transaction(|| {
+            diesel::insert_into(prices_products::table)
+                .values(FormPriceProduct {
+                    id: None,
+                    price_id: form.price_id,
+                    product_id: Some(form.product_id),
+                    user_id: Some(context.user_id),
+                    amount: None,
+                })
+                .on_conflict((prices_products::price_id, prices_products::product_id))
+                .do_nothing()
+                .execute(connection)?;
+            let price_product_id = prices_products::table
+                .select(prices_products::id)
+                .filter(prices_products::price_id.eq(form.price_id))
+                .filter(prices_products::product_id.eq(form.product_id))
+                .first::<i32>(connection)?;
+
+            let scheduled_price = diesel::insert_into(scheduled_prices::table)
+                .values(NewScheduledPrice {
+                    user_id: context.user_id,
+                    price_product_id,
+                    amount: form.amount,
+                    starts_on: form.starts_on,
+                    created_at: Local::now().naive_local(),
+                })
+                .on_conflict((
+                    scheduled_prices::price_product_id,
+                    scheduled_prices::starts_on,
+                ))
+                .do_update()
+                .set((
+                    scheduled_prices::amount.eq(form.amount),
+                    scheduled_prices::user_id.eq(context.user_id),
+                    scheduled_prices::applied_at.eq(None::<NaiveDateTime>),
+                ))
+                .get_result::<ScheduledPrice>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "schedulePrice",
+                "scheduled_price",
+                scheduled_price.id,
+                None::<&ScheduledPrice>,
+                Some(&scheduled_price),
+            )?;
+            Ok(scheduled_price)
+        })
+    }
+
+    /// Only changes not applied yet can be cancelled.
+    pub fn cancel(context: &Context, scheduled_price_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = scheduled_prices::table
+                .filter(scheduled_prices::user_id.eq(context.user_id))
+                .filter(scheduled_prices::applied_at.is_null())
+                .find(scheduled_price_id)
+                .first::<ScheduledPrice>(connection)?;
+
+            diesel::delete(scheduled_prices::table.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "cancelScheduledPrice",
+                "scheduled_price",
+                scheduled_price_id,
+                Some(&before),
+                None::<&ScheduledPrice>,
+            )?;
+            Ok(true)
+        })
+    }
+
+    /// Copies the amounts that are due to the prices of the products, the
+    /// latest one when several are due, and records the changes on behalf
+    /// of whoever scheduled them. Returns how many prices changed.
+    pub fn apply_due(connection: &PgConnection) -> Result<usize, diesel::result::Error> {
+        connection.transaction(|| {
+            let updated = diesel::sql_query(
+                "WITH due AS ( \
+                   SELECT DISTINCT ON (price_product_id) price_product_id, amount, user_id \
+                   FROM scheduled_prices \
+                   WHERE applied_at IS NULL AND starts_on <= CURRENT_DATE \
+                   ORDER BY price_product_id, starts_on DESC \
+                 ), changed AS ( \
+                   SELECT prices_products.id, prices_products.price_id, \
+                     prices_products.product_id, prices_products.amount AS old_amount, \
+                     due.amount AS new_amount, due.user_id \
+                   FROM prices_products \
+                   INNER JOIN due ON due.price_product_id = prices_products.id \
+                 ), recorded AS ( \
+                   INSERT INTO price_changes \
+                     (user_id, price_id, product_id, old_amount, new_amount, changed_at) \
+                   SELECT user_id, price_id, product_id, old_amount, new_amount, NOW() \
+                   FROM changed \
+                   WHERE old_amount IS DISTINCT FROM new_amount \
+                 ) \
+                 UPDATE prices_products SET amount = changed.new_amount \
+                 FROM changed \
+                 WHERE prices_products.id = changed.id",
+            )
+            .execute(connection)?;
+
+            diesel::sql_query(
+                "UPDATE scheduled_prices SET applied_at = NOW() \
+                 WHERE applied_at IS NULL AND starts_on <= CURRENT_DATE",
+            )
+            .execute(connection)?;
+            Ok(updated)
+        })
+    }
+
+    /// Applies the due prices of every company in a background thread.
+    pub fn schedule(pool: PgPool) -> thread::JoinHandle<()> {
+        thread::spawn(move || loop {
+            match pool.get() {
+                Ok(connection) => match ScheduledPrice::apply_due(&connection) {
+                    Ok(updated) => log::info!("Applied {} scheduled prices", updated),
+                    Err(error) => log::error!("Couldn't apply scheduled prices: {}", error),
+                },
+                Err(error) => log::error!("Couldn't apply scheduled prices: {}", error),
+            }
+            thread::sleep(Duration::from_secs(APPLY_INTERVAL_SECS));
+        })
+    }
+}
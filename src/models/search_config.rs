@@ -0,0 +1,80 @@
+use diesel::sql_types::{Integer, Text};
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::Context;
+use crate::schema::users;
+
+#[derive(Debug, Clone, PartialEq, Serialize, QueryableByName, juniper::GraphQLObject)]
+#[graphql(description = "Text search configuration used to index and search products")]
+pub struct SearchConfig {
+    #[sql_type = "Text"]
+    pub name: String,
+}
+
+impl SearchConfig {
+    /// Configurations installed in the database, `english` and `spanish`
+    /// among them.
+    pub fn list(context: &Context) -> FieldResult<Vec<SearchConfig>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(diesel::sql_query(
+            "SELECT cfgname::text AS name FROM pg_catalog.pg_ts_config ORDER BY cfgname",
+        )
+        .load::<SearchConfig>(connection)?)
+    }
+
+    pub fn show(context: &Context) -> FieldResult<SearchConfig> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(SearchConfig {
+            name: users::table
+                .select(users::search_config)
+                .find(context.user_id)
+                .first::<String>(connection)?,
+        })
+    }
+
+    /// Changes the configuration of the company, which is the logged in
+    /// account, and reindexes its products.
+    pub fn update(context: &Context, name: String) -> FieldResult<SearchConfig> {
+        let connection: &PgConnection = &context.conn;
+
+        let name = name.trim().to_lowercase();
+        if !SearchConfig::list(context)?
+            .iter()
+            .any(|config| config.name == name)
+        {
+            return Err(format!("Unknown search configuration {}", name).into());
+        }
+
+        connection.transaction(|| {
+            let before = SearchConfig::show(context)?;
+
+            diesel::update(users::table.find(context.user_id))
+                .set(users::search_config.eq(&name))
+                .execute(connection)?;
+
+            diesel::sql_query(
+                "UPDATE products \
+                 SET text_searchable_product_col = to_tsvector( \
+                   product_search_config(user_id), name || ' ' || COALESCE(description, '')) \
+                 WHERE user_id = $1",
+            )
+            .bind::<Integer, _>(context.user_id)
+            .execute(connection)?;
+
+            let config = SearchConfig { name: name.clone() };
+            AuditEvent::record(
+                context,
+                "updateSearchConfig",
+                "user",
+                context.user_id,
+                Some(&before),
+                Some(&config),
+            )?;
+            Ok(config)
+        })
+    }
+}
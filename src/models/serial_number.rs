@@ -0,0 +1,386 @@
+use chrono::{Local, NaiveDateTime};
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::location::Location;
+use crate::models::product::{Product, PRODUCT_COLUMNS};
+use crate::models::sale::Sale;
+use crate::models::sale_product::{SaleProduct, SALE_PRODUCT_COLUMNS};
+use crate::models::stock_movement::{
+    AdjustmentReason, StockChange, StockMovement, StockMovementKind,
+};
+use crate::models::unit::ProductUnit;
+use crate::models::Context;
+use crate::schema;
+use crate::schema::serial_numbers;
+use crate::schema::serial_numbers::dsl;
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListSerialNumber {
+    pub data: Vec<SerialNumber>,
+}
+
+#[derive(
+    Identifiable, Associations, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(Product)]
+#[table_name = "serial_numbers"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Unit of a serialized product, sold when it has a sale line")]
+pub struct SerialNumber {
+    pub id: i32,
+    pub product_id: i32,
+    pub user_id: i32,
+    pub serial: String,
+    pub sale_product_id: Option<i32>,
+    pub received_at: NaiveDateTime,
+    #[graphql(description = "Where the unit is kept, or was sold from")]
+    pub location_id: Option<i32>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "serial_numbers"]
+struct NewSerialNumber<'a> {
+    product_id: i32,
+    user_id: i32,
+    serial: &'a str,
+    received_at: NaiveDateTime,
+    location_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+#[graphql(description = "Where a serial number is, the sale is missing while it's in stock")]
+pub struct SerialNumberLocation {
+    pub serial_number: SerialNumber,
+    pub product: Product,
+    pub sale_product: Option<SaleProduct>,
+    pub sale: Option<Sale>,
+}
+
+impl SerialNumber {
+    pub fn list(
+        context: &Context,
+        param_product_id: i32,
+        in_stock: bool,
+    ) -> FieldResult<ListSerialNumber> {
+        let connection: &PgConnection = &context.conn;
+
+        let mut query = dsl::serial_numbers
+            .filter(dsl::user_id.eq(context.user_id))
+            .filter(dsl::product_id.eq(param_product_id))
+            .into_boxed();
+        if in_stock {
+            query = query.filter(dsl::sale_product_id.is_null());
+        }
+
+        Ok(ListSerialNumber {
+            data: query
+                .order(dsl::serial.asc())
+                .load::<SerialNumber>(connection)?,
+        })
+    }
+
+    /// Registers received units of a serialized product, adding them to
+    /// its stock and to the stock of the location receiving them.
+    pub fn receive(
+        context: &Context,
+        param_product_id: i32,
+        param_location_id: Option<i32>,
+        serials: Vec<String>,
+    ) -> FieldResult<Vec<SerialNumber>> {
+        let connection: &PgConnection = &context.conn;
+
+        let product = Product::show(context, param_product_id)?.product;
+        if !product.serialized {
+            return Err(format!("Product {} isn't serialized", param_product_id).into());
+        }
+        if let Some(param_location_id) = param_location_id {
+            Location::find(context, param_location_id)?;
+        }
+        let serials = clean_serials(serials)?;
+
+        connection.transaction(|| {
+            let received =
+                SerialNumber::register(context, param_product_id, param_location_id, serials)?;
+
+            StockMovement::post(
+                context,
+                StockChange {
+                    product_id: product.id,
+                    location_id: param_location_id,
+                    lot_id: None,
+                    quantity: received.len() as f64,
+                    kind: StockMovementKind::Receipt,
+                    sale_id: None,
+                    transfer_id: None,
+                    reason: None,
+                    stock_count_id: None,
+                },
+            )?;
+            let updated_product = Product::show(context, product.id)?.product;
+
+            AuditEvent::record(
+                context,
+                "receiveSerialNumbers",
+                "product",
+                product.id,
+                Some(&product),
+                Some(&updated_product),
+            )?;
+            Ok(received)
+        })
+    }
+
+    /// Stores units of a serialized product at a location, refusing serials
+    /// the product already has. Posting the stock movement for them is up
+    /// to the caller.
+    pub fn register(
+        context: &Context,
+        param_product_id: i32,
+        param_location_id: Option<i32>,
+        serials: Vec<String>,
+    ) -> FieldResult<Vec<SerialNumber>> {
+        let connection: &PgConnection = &context.conn;
+
+        let serials = clean_serials(serials)?;
+        let received: Vec<String> = dsl::serial_numbers
+            .select(dsl::serial)
+            .filter(dsl::user_id.eq(context.user_id))
+            .filter(dsl::product_id.eq(param_product_id))
+            .filter(dsl::serial.eq_any(serials.clone()))
+            .load(connection)?;
+        if let Some(repeated) = serials.iter().find(|serial| received.contains(serial)) {
+            return Err(format!("Serial {} was already received", repeated).into());
+        }
+
+        let received_at = Local::now().naive_local();
+        let new_serial_numbers: Vec<NewSerialNumber> = serials
+            .iter()
+            .map(|serial| NewSerialNumber {
+                product_id: param_product_id,
+                user_id: context.user_id,
+                serial,
+                received_at,
+                location_id: param_location_id,
+            })
+            .collect();
+
+        Ok(diesel::insert_into(serial_numbers::table)
+            .values(&new_serial_numbers)
+            .get_results::<SerialNumber>(connection)?)
+    }
+
+    /// Only serials still in stock can be removed, sold ones are history.
+    /// The unit is adjusted out of the location it's kept at.
+    pub fn destroy(
+        context: &Context,
+        serial_number_id: i32,
+        reason: AdjustmentReason,
+    ) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = dsl::serial_numbers
+                .filter(dsl::user_id.eq(context.user_id))
+                .find(serial_number_id)
+                .first::<SerialNumber>(connection)?;
+            if before.sale_product_id.is_some() {
+                return Err(format!("Serial {} was already sold", before.serial).into());
+            }
+
+            diesel::delete(dsl::serial_numbers.find(before.id)).execute(connection)?;
+            StockMovement::post(
+                context,
+                StockChange {
+                    product_id: before.product_id,
+                    location_id: before.location_id,
+                    lot_id: None,
+                    quantity: -1.0,
+                    kind: StockMovementKind::Adjustment,
+                    sale_id: None,
+                    transfer_id: None,
+                    reason: Some(reason),
+                    stock_count_id: None,
+                },
+            )?;
+
+            AuditEvent::record(
+                context,
+                "destroySerialNumber",
+                "serial_number",
+                serial_number_id,
+                Some(&before),
+                None::<&SerialNumber>,
+            )?;
+            Ok(true)
+        })
+    }
+
+    /// "Where is serial X": every unit with that serial and the sale it
+    /// went out with, if any.
+    pub fn locate(
+        context: &Context,
+        param_serial: String,
+    ) -> FieldResult<Vec<SerialNumberLocation>> {
+        let connection: &PgConnection = &context.conn;
+
+        let found = dsl::serial_numbers
+            .inner_join(schema::products::table)
+            .select((
+                (
+                    dsl::id,
+                    dsl::product_id,
+                    dsl::user_id,
+                    dsl::serial,
+                    dsl::sale_product_id,
+                    dsl::received_at,
+                    dsl::location_id,
+                ),
+                PRODUCT_COLUMNS,
+            ))
+            .filter(dsl::user_id.eq(context.user_id))
+            .filter(dsl::serial.eq(param_serial.trim()))
+            .load::<(SerialNumber, Product)>(connection)?;
+
+        let mut locations = vec![];
+        for (serial_number, product) in found {
+            let (sale_product, sale) = match serial_number.sale_product_id {
+                Some(param_sale_product_id) => {
+                    let (sale_product, sale) = schema::sale_products::table
+                        .inner_join(schema::sales::table)
+                        .select((
+                            SALE_PRODUCT_COLUMNS,
+                            (
+                                schema::sales::id,
+                                schema::sales::user_id,
+                                schema::sales::sale_date,
+                                schema::sales::total,
+                                schema::sales::bill_number,
+                                schema::sales::state,
+                                schema::sales::location_id,
+                                schema::sales::created_at,
+                                schema::sales::customer_id,
+                            ),
+                        ))
+                        .filter(schema::sales::user_id.eq(context.user_id))
+                        .filter(schema::sale_products::id.eq(param_sale_product_id))
+                        .first::<(SaleProduct, Sale)>(connection)?;
+                    (Some(sale_product), Some(sale))
+                }
+                None => (None, None),
+            };
+            locations.push(SerialNumberLocation {
+                serial_number,
+                product,
+                sale_product,
+                sale,
+            });
+        }
+        Ok(locations)
+    }
+
+    /// Checks the serials given for a sale line: one per unit, all of them
+    /// known and in stock.
+    pub fn check_sale_serials(
+        context: &Context,
+        param_product_id: i32,
+        amount: f64,
+        serials: Vec<String>,
+    ) -> FieldResult<Vec<String>> {
+        let connection: &PgConnection = &context.conn;
+
+        let serials = clean_serials(serials)?;
+        if amount.fract() != 0.0 || serials.len() as f64 != amount {
+            return Err(format!(
+                "Product {} needs one serial number per unit sold",
+                param_product_id
+            )
+            .into());
+        }
+
+        let available: Vec<String> = dsl::serial_numbers
+            .select(dsl::serial)
+            .filter(dsl::user_id.eq(context.user_id))
+            .filter(dsl::product_id.eq(param_product_id))
+            .filter(dsl::serial.eq_any(serials.clone()))
+            .filter(dsl::sale_product_id.is_null())
+            .load(connection)?;
+
+        if let Some(unknown) = serials.iter().find(|serial| !available.contains(serial)) {
+            return Err(format!("Serial {} is unknown or was already sold", unknown).into());
+        }
+        Ok(serials)
+    }
+
+    /// Assigns the serials asked for by the lines of a sale being
+    /// approved, checking again they weren't sold by another sale since
+    /// the draft was stored. Run inside the approval transaction.
+    pub fn assign_sale(context: &Context, sale: &Sale) -> FieldResult<()> {
+        let connection: &PgConnection = &context.conn;
+
+        let sale_products = schema::sale_products::table
+            .select(SALE_PRODUCT_COLUMNS)
+            .filter(schema::sale_products::sale_id.eq(sale.id))
+            .filter(schema::sale_products::serials.is_not_null())
+            .load::<SaleProduct>(connection)?;
+
+        for sale_product in sale_products {
+            let factor =
+                ProductUnit::factor(context, sale_product.product_id, sale_product.unit_id)?;
+            let serials = SerialNumber::check_sale_serials(
+                context,
+                sale_product.product_id,
+                sale_product.amount * factor,
+                sale_product.serials.unwrap_or_default(),
+            )?;
+
+            diesel::update(
+                dsl::serial_numbers
+                    .filter(dsl::user_id.eq(context.user_id))
+                    .filter(dsl::product_id.eq(sale_product.product_id))
+                    .filter(dsl::serial.eq_any(serials)),
+            )
+            .set((
+                dsl::sale_product_id.eq(sale_product.id),
+                dsl::location_id.eq(sale.location_id),
+            ))
+            .execute(connection)?;
+        }
+        Ok(())
+    }
+
+    /// Puts the serials sold by a cancelled sale back in stock, at the
+    /// location they were sold from.
+    pub fn release_sale(context: &Context, sale_id: i32) -> FieldResult<usize> {
+        let connection: &PgConnection = &context.conn;
+
+        let sale_product_ids = schema::sale_products::table
+            .select(schema::sale_products::id)
+            .filter(schema::sale_products::sale_id.eq(sale_id))
+            .load::<i32>(connection)?;
+
+        Ok(diesel::update(
+            dsl::serial_numbers
+                .filter(dsl::user_id.eq(context.user_id))
+                .filter(dsl::sale_product_id.eq_any(sale_product_ids)),
+        )
+        .set(dsl::sale_product_id.eq(None::<i32>))
+        .execute(connection)?)
+    }
+}
+
+fn clean_serials(serials: Vec<String>) -> FieldResult<Vec<String>> {
+    let mut cleaned: Vec<String> = vec![];
+    for serial in serials {
+        let serial = serial.trim().to_string();
+        if serial.is_empty() {
+            return Err("Serial numbers can't be empty".into());
+        }
+        if cleaned.contains(&serial) {
+            return Err(format!("Serial {} is repeated", serial).into());
+        }
+        cleaned.push(serial);
+    }
+    Ok(cleaned)
+}
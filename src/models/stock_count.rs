@@ -0,0 +1,431 @@
+use chrono::{Local, NaiveDateTime};
+use diesel::{
+    BelongingToDsl, Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl,
+    RunQueryDsl,
+};
+use juniper::FieldResult;
+use std::collections::HashMap;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::location::{Location, ProductStock};
+use crate::models::product::{Product, PRODUCT_COLUMNS};
+use crate::models::stock_movement::{
+    AdjustmentReason, StockChange, StockMovement, StockMovementKind,
+};
+use crate::models::Context;
+use crate::schema;
+use crate::schema::stock_count_lines;
+use crate::schema::stock_counts;
+use crate::schema::stock_counts::dsl;
+
+#[derive(DbEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, juniper::GraphQLEnum)]
+pub enum StockCountState {
+    Open,
+    Confirmed,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListStockCount {
+    pub data: Vec<StockCount>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "stock_counts"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Physical count of the stock, of a location when it has one")]
+pub struct StockCount {
+    pub id: i32,
+    pub user_id: i32,
+    pub location_id: Option<i32>,
+    pub state: StockCountState,
+    pub created_at: NaiveDateTime,
+    pub confirmed_at: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Identifiable, Associations, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(StockCount)]
+#[belongs_to(Product)]
+#[table_name = "stock_count_lines"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Product in a stock count, expected as of the snapshot")]
+pub struct StockCountLine {
+    pub id: i32,
+    pub stock_count_id: i32,
+    pub product_id: i32,
+    pub expected: f64,
+    pub counted: Option<f64>,
+    pub reason: Option<AdjustmentReason>,
+    pub counted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+pub struct FormStockCountLine {
+    pub stock_count_id: i32,
+    pub product_id: i32,
+    pub counted: f64,
+    #[graphql(description = "Defaults to a count correction")]
+    pub reason: Option<AdjustmentReason>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "stock_count_lines"]
+struct NewStockCountLine {
+    stock_count_id: i32,
+    product_id: i32,
+    expected: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, juniper::GraphQLObject)]
+pub struct FullStockCount {
+    pub stock_count: StockCount,
+    pub stock_count_lines: Vec<StockCountLine>,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+#[graphql(description = "Difference between the counted and the expected quantity")]
+pub struct StockCountVariance {
+    pub stock_count_line: StockCountLine,
+    pub product: Product,
+    pub variance: f64,
+}
+
+impl StockCount {
+    pub fn list(
+        context: &Context,
+        state: Option<StockCountState>,
+        limit: i32,
+    ) -> FieldResult<ListStockCount> {
+        let connection: &PgConnection = &context.conn;
+
+        let mut query = dsl::stock_counts
+            .filter(dsl::user_id.eq(context.user_id))
+            .into_boxed();
+        if let Some(state) = state {
+            query = query.filter(dsl::state.eq(state));
+        }
+
+        Ok(ListStockCount {
+            data: query
+                .order(dsl::created_at.desc())
+                .limit(i64::from(limit))
+                .load::<StockCount>(connection)?,
+        })
+    }
+
+    pub fn show(context: &Context, stock_count_id: i32) -> FieldResult<FullStockCount> {
+        let connection: &PgConnection = &context.conn;
+
+        let stock_count = dsl::stock_counts
+            .filter(dsl::user_id.eq(context.user_id))
+            .find(stock_count_id)
+            .first::<StockCount>(connection)?;
+
+        let stock_count_lines = StockCountLine::belonging_to(&stock_count)
+            .order(stock_count_lines::id.asc())
+            .load::<StockCountLine>(connection)?;
+
+        Ok(FullStockCount {
+            stock_count,
+            stock_count_lines,
+        })
+    }
+
+    /// Starts a count taking a snapshot of the expected quantities, in the
+    /// location when there is one. Cycle counts pass the products to count,
+    /// full counts leave them out and get every product.
+    pub fn create(
+        context: &Context,
+        param_location_id: Option<i32>,
+        product_ids: Option<Vec<i32>>,
+    ) -> FieldResult<FullStockCount> {
+        let connection: &PgConnection = &context.conn;
+
+        if let Some(param_location_id) = param_location_id {
+            Location::find(context, param_location_id)?;
+        }
+
+        connection.transaction(|| {
+            let stock_count = diesel::insert_into(stock_counts::table)
+                .values((
+                    dsl::user_id.eq(context.user_id),
+                    dsl::location_id.eq(param_location_id),
+                    dsl::state.eq(StockCountState::Open),
+                    dsl::created_at.eq(Local::now().naive_local()),
+                ))
+                .get_result::<StockCount>(connection)?;
+
+            let mut query = schema::products::table
+                .select((schema::products::id, schema::products::stock))
+                .filter(schema::products::user_id.eq(context.user_id))
+                .into_boxed();
+            if let Some(product_ids) = product_ids {
+                query = query.filter(schema::products::id.eq_any(product_ids));
+            }
+            let snapshot = query
+                .order(schema::products::id.asc())
+                .load::<(i32, f64)>(connection)?;
+
+            let location_stocks: HashMap<i32, f64> = match stock_count.location_id {
+                Some(param_location_id) => schema::product_stocks::table
+                    .select((
+                        schema::product_stocks::product_id,
+                        schema::product_stocks::stock,
+                    ))
+                    .filter(schema::product_stocks::location_id.eq(param_location_id))
+                    .load::<(i32, f64)>(connection)?
+                    .into_iter()
+                    .collect(),
+                None => HashMap::new(),
+            };
+
+            let new_lines: Vec<NewStockCountLine> = snapshot
+                .into_iter()
+                .map(|(product_id, stock)| NewStockCountLine {
+                    stock_count_id: stock_count.id,
+                    product_id,
+                    expected: match stock_count.location_id {
+                        Some(_) => location_stocks.get(&product_id).cloned().unwrap_or(0.0),
+                        None => stock,
+                    },
+                })
+                .collect();
+            diesel::insert_into(stock_count_lines::table)
+                .values(&new_lines)
+                .execute(connection)?;
+
+            let full_stock_count = StockCount::show(context, stock_count.id)?;
+            AuditEvent::record(
+                context,
+                "createStockCount",
+                "stock_count",
+                stock_count.id,
+                None::<&FullStockCount>,
+                Some(&full_stock_count),
+            )?;
+            Ok(full_stock_count)
+        })
+    }
+
+    /// Enters the counted quantity of a product, counting it again replaces
+    /// the previous figure. Products missing from the snapshot are added
+    /// with their current stock as expected quantity.
+    pub fn count(context: &Context, form: FormStockCountLine) -> FieldResult<StockCountLine> {
+        let connection: &PgConnection = &context.conn;
+
+        if form.counted < 0.0 {
+            return Err("Counted quantities can't be negative".into());
+        }
+
+        connection.transaction(|| {
+            let stock_count = StockCount::show(context, form.stock_count_id)?.stock_count;
+            stock_count.check_open()?;
+
+            let existing_line = stock_count_lines::table
+                .filter(stock_count_lines::stock_count_id.eq(stock_count.id))
+                .filter(stock_count_lines::product_id.eq(form.product_id))
+                .first::<StockCountLine>(connection)
+                .optional()?;
+
+            let line_id = match &existing_line {
+                Some(line) => line.id,
+                None => {
+                    let product = Product::show(context, form.product_id)?.product;
+                    let expected = match stock_count.location_id {
+                        Some(param_location_id) => {
+                            ProductStock::stock(context, product.id, param_location_id)?
+                        }
+                        None => product.stock,
+                    };
+                    diesel::insert_into(stock_count_lines::table)
+                        .values(NewStockCountLine {
+                            stock_count_id: stock_count.id,
+                            product_id: product.id,
+                            expected,
+                        })
+                        .get_result::<StockCountLine>(connection)?
+                        .id
+                }
+            };
+
+            let line = diesel::update(stock_count_lines::table.find(line_id))
+                .set((
+                    stock_count_lines::counted.eq(form.counted),
+                    stock_count_lines::reason.eq(form.reason),
+                    stock_count_lines::counted_at.eq(Local::now().naive_local()),
+                ))
+                .get_result::<StockCountLine>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "countStock",
+                "stock_count_line",
+                line.id,
+                existing_line.as_ref(),
+                Some(&line),
+            )?;
+            Ok(line)
+        })
+    }
+
+    /// Counted lines that don't match the snapshot, with the product.
+    pub fn variance(
+        context: &Context,
+        stock_count_id: i32,
+    ) -> FieldResult<Vec<StockCountVariance>> {
+        let connection: &PgConnection = &context.conn;
+
+        let stock_count = StockCount::show(context, stock_count_id)?.stock_count;
+
+        Ok(StockCountLine::belonging_to(&stock_count)
+            .inner_join(schema::products::table)
+            .select((stock_count_lines::all_columns, PRODUCT_COLUMNS))
+            .filter(stock_count_lines::counted.is_not_null())
+            .order(schema::products::name.asc())
+            .load::<(StockCountLine, Product)>(connection)?
+            .into_iter()
+            .filter_map(|(stock_count_line, product)| {
+                let variance = stock_count_line.counted? - stock_count_line.expected;
+                if variance == 0.0 {
+                    return None;
+                }
+                Some(StockCountVariance {
+                    stock_count_line,
+                    product,
+                    variance,
+                })
+            })
+            .collect())
+    }
+
+    /// Posts an adjustment for every counted line that doesn't match the
+    /// stock the books had when it was counted, lines never counted are
+    /// left as they are. Sales, receipts and transfers posted while the
+    /// count is open are kept, the adjustment only corrects the count.
+    pub fn confirm(context: &Context, stock_count_id: i32) -> FieldResult<FullStockCount> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = StockCount::show(context, stock_count_id)?;
+            before.stock_count.check_open()?;
+
+            for line in &before.stock_count_lines {
+                let (counted, counted_at) = match (line.counted, line.counted_at) {
+                    (Some(counted), Some(counted_at)) => (counted, counted_at),
+                    _ => continue,
+                };
+                let booked = StockCount::stock_at(
+                    context,
+                    line.product_id,
+                    before.stock_count.location_id,
+                    counted_at,
+                )?;
+                if counted == booked {
+                    continue;
+                }
+                StockMovement::post(
+                    context,
+                    StockChange {
+                        product_id: line.product_id,
+                        location_id: before.stock_count.location_id,
+                        lot_id: None,
+                        quantity: counted - booked,
+                        kind: StockMovementKind::Adjustment,
+                        sale_id: None,
+                        transfer_id: None,
+                        reason: Some(line.reason.unwrap_or(AdjustmentReason::CountCorrection)),
+                        stock_count_id: Some(stock_count_id),
+                    },
+                )?;
+            }
+
+            StockCount::set_state(
+                context,
+                before,
+                StockCountState::Confirmed,
+                "confirmStockCount",
+            )
+        })
+    }
+
+    /// Stock the books had at a moment: the current one without the
+    /// movements posted since.
+    fn stock_at(
+        context: &Context,
+        product_id: i32,
+        location_id: Option<i32>,
+        at: NaiveDateTime,
+    ) -> FieldResult<f64> {
+        let connection: &PgConnection = &context.conn;
+
+        let mut query = schema::stock_movements::table
+            .select(schema::stock_movements::quantity)
+            .filter(schema::stock_movements::user_id.eq(context.user_id))
+            .filter(schema::stock_movements::product_id.eq(product_id))
+            .filter(schema::stock_movements::created_at.gt(at))
+            .into_boxed();
+        let current = match location_id {
+            Some(param_location_id) => {
+                query = query.filter(schema::stock_movements::location_id.eq(param_location_id));
+                ProductStock::stock(context, product_id, param_location_id)?
+            }
+            None => Product::show(context, product_id)?.product.stock,
+        };
+        let since: f64 = query.load::<f64>(connection)?.iter().sum();
+
+        Ok(current - since)
+    }
+
+    pub fn cancel(context: &Context, stock_count_id: i32) -> FieldResult<FullStockCount> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = StockCount::show(context, stock_count_id)?;
+            before.stock_count.check_open()?;
+
+            StockCount::set_state(
+                context,
+                before,
+                StockCountState::Cancelled,
+                "cancelStockCount",
+            )
+        })
+    }
+
+    fn set_state(
+        context: &Context,
+        before: FullStockCount,
+        state: StockCountState,
+        operation: &str,
+    ) -> FieldResult<FullStockCount> {
+        let connection: &PgConnection = &context.conn;
+
+        let confirmed_at = match state {
+            StockCountState::Confirmed => Some(Local::now().naive_local()),
+            _ => None,
+        };
+        diesel::update(dsl::stock_counts.find(before.stock_count.id))
+            .set((dsl::state.eq(state), dsl::confirmed_at.eq(confirmed_at)))
+            .execute(connection)?;
+
+        let full_stock_count = StockCount::show(context, before.stock_count.id)?;
+        AuditEvent::record(
+            context,
+            operation,
+            "stock_count",
+            before.stock_count.id,
+            Some(&before),
+            Some(&full_stock_count),
+        )?;
+        Ok(full_stock_count)
+    }
+
+    fn check_open(&self) -> FieldResult<()> {
+        if self.state != StockCountState::Open {
+            return Err(format!("Stock count {} is {:?}", self.id, self.state).into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+use chrono::{Local, NaiveDateTime};
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::location::Location;
+use crate::models::lot::Lot;
+use crate::models::product::Product;
+use crate::models::product_component::ProductComponent;
+use crate::models::sale::Sale;
+use crate::models::sale_product::{SaleProduct, SALE_PRODUCT_COLUMNS};
+use crate::models::serial_number::SerialNumber;
+use crate::models::unit::ProductUnit;
+use crate::models::Context;
+use crate::schema;
+use crate::schema::stock_movements;
+use crate::schema::stock_movements::dsl;
+
+#[derive(DbEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, juniper::GraphQLEnum)]
+pub enum StockMovementKind {
+    Receipt,
+    Sale,
+    SaleCancellation,
+    TransferOut,
+    TransferIn,
+    TransferDiscrepancy,
+    Adjustment,
+}
+
+#[derive(DbEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, juniper::GraphQLEnum)]
+#[graphql(description = "Why the stock of a product was adjusted by hand")]
+pub enum AdjustmentReason {
+    CountCorrection,
+    Damage,
+    Theft,
+    Expiry,
+    Found,
+    Correction,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "stock_movements"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Change in the stock of a product, negative when it leaves")]
+pub struct StockMovement {
+    pub id: i32,
+    pub user_id: i32,
+    pub product_id: i32,
+    pub location_id: Option<i32>,
+    pub lot_id: Option<i32>,
+    pub quantity: f64,
+    pub kind: StockMovementKind,
+    pub sale_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub transfer_id: Option<i32>,
+    pub reason: Option<AdjustmentReason>,
+    pub stock_count_id: Option<i32>,
+}
+
+/// Movement to post, quantities are in the base unit of the product.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StockChange {
+    pub product_id: i32,
+    pub location_id: Option<i32>,
+    pub lot_id: Option<i32>,
+    pub quantity: f64,
+    pub kind: StockMovementKind,
+    pub sale_id: Option<i32>,
+    pub transfer_id: Option<i32>,
+    pub reason: Option<AdjustmentReason>,
+    pub stock_count_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+#[graphql(description = "Stock added or, when negative, removed outside of any document")]
+pub struct FormStockAdjustment {
+    pub product_id: i32,
+    pub location_id: Option<i32>,
+    pub lot_id: Option<i32>,
+    pub quantity: f64,
+    pub reason: AdjustmentReason,
+}
+
+#[derive(Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+#[graphql(description = "Goods received, bought by the box and sold by the piece for instance")]
+pub struct FormStockReceipt {
+    pub product_id: i32,
+    pub location_id: Option<i32>,
+    #[graphql(description = "Required for products tracking lots")]
+    pub lot_id: Option<i32>,
+    #[graphql(
+        description = "Unit enabled for purchase the quantity is in, the base unit when missing"
+    )]
+    pub unit_id: Option<i32>,
+    pub quantity: f64,
+    #[graphql(description = "One per base unit received, required for serialized products")]
+    pub serials: Option<Vec<String>>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "stock_movements"]
+struct NewStockMovement {
+    user_id: i32,
+    product_id: i32,
+    location_id: Option<i32>,
+    lot_id: Option<i32>,
+    quantity: f64,
+    kind: StockMovementKind,
+    sale_id: Option<i32>,
+    created_at: NaiveDateTime,
+    transfer_id: Option<i32>,
+    reason: Option<AdjustmentReason>,
+    stock_count_id: Option<i32>,
+}
+
+impl StockMovement {
+    pub fn list(context: &Context, product_id: i32, limit: i32) -> FieldResult<Vec<StockMovement>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(dsl::stock_movements
+            .filter(dsl::user_id.eq(context.user_id))
+            .filter(dsl::product_id.eq(product_id))
+            .order(dsl::created_at.desc())
+            .limit(i64::from(limit))
+            .load::<StockMovement>(connection)?)
+    }
+
+    /// Stores the movement and applies it to the product total, the stock
+    /// of its location and the stock of its lot. Meant to be called inside
+    /// the transaction of the operation causing it.
+    pub fn post(context: &Context, change: StockChange) -> FieldResult<StockMovement> {
+        let connection: &PgConnection = &context.conn;
+
+        let movement = diesel::insert_into(stock_movements::table)
+            .values(NewStockMovement {
+                user_id: context.user_id,
+                product_id: change.product_id,
+                location_id: change.location_id,
+                lot_id: change.lot_id,
+                quantity: change.quantity,
+                kind: change.kind,
+                sale_id: change.sale_id,
+                created_at: Local::now().naive_local(),
+                transfer_id: change.transfer_id,
+                reason: change.reason,
+                stock_count_id: change.stock_count_id,
+            })
+            .get_result::<StockMovement>(connection)?;
+
+        diesel::update(
+            schema::products::table
+                .filter(schema::products::user_id.eq(context.user_id))
+                .find(movement.product_id),
+        )
+        .set(schema::products::stock.eq(schema::products::stock + movement.quantity))
+        .execute(connection)?;
+
+        if let Some(location_id) = movement.location_id {
+            diesel::insert_into(schema::product_stocks::table)
+                .values((
+                    schema::product_stocks::product_id.eq(movement.product_id),
+                    schema::product_stocks::location_id.eq(location_id),
+                    schema::product_stocks::user_id.eq(context.user_id),
+                    schema::product_stocks::stock.eq(movement.quantity),
+                ))
+                .on_conflict((
+                    schema::product_stocks::product_id,
+                    schema::product_stocks::location_id,
+                ))
+                .do_update()
+                .set(
+                    schema::product_stocks::stock
+                        .eq(schema::product_stocks::stock + movement.quantity),
+                )
+                .execute(connection)?;
+        }
+
+        if let Some(lot_id) = movement.lot_id {
+            diesel::update(
+                schema::lots::table
+                    .filter(schema::lots::user_id.eq(context.user_id))
+                    .find(lot_id),
+            )
+            .set(schema::lots::stock.eq(schema::lots::stock + movement.quantity))
+            .execute(connection)?;
+        }
+
+        Ok(movement)
+    }
+
+    pub fn adjust(context: &Context, form: FormStockAdjustment) -> FieldResult<StockMovement> {
+        let connection: &PgConnection = &context.conn;
+
+        if form.quantity == 0.0 {
+            return Err("Adjustments need a quantity".into());
+        }
+        if let Some(param_location_id) = form.location_id {
+            Location::find(context, param_location_id)?;
+        }
+        if let Some(param_lot_id) = form.lot_id {
+            if Lot::find(context, param_lot_id)?.product_id != form.product_id {
+                return Err(format!(
+                    "Lot {} doesn't belong to product {}",
+                    param_lot_id, form.product_id
+                )
+                .into());
+            }
+        }
+
+        connection.transaction(|| {
+            let before = Product::show(context, form.product_id)?.product;
+
+            let movement = StockMovement::post(
+                context,
+                StockChange {
+                    product_id: before.id,
+                    location_id: form.location_id,
+                    lot_id: form.lot_id,
+                    quantity: form.quantity,
+                    kind: StockMovementKind::Adjustment,
+                    sale_id: None,
+                    transfer_id: None,
+                    reason: Some(form.reason),
+                    stock_count_id: None,
+                },
+            )?;
+            let product = Product::show(context, before.id)?.product;
+
+            AuditEvent::record(
+                context,
+                "adjustStock",
+                "product",
+                product.id,
+                Some(&before),
+                Some(&product),
+            )?;
+            Ok(movement)
+        })
+    }
+
+    /// Adds received goods to the stock, converted to the base unit of the
+    /// product, and registers their serial numbers when it's serialized.
+    pub fn receive(context: &Context, form: FormStockReceipt) -> FieldResult<StockMovement> {
+        let connection: &PgConnection = &context.conn;
+
+        if form.quantity <= 0.0 {
+            return Err("Receipts need a positive quantity".into());
+        }
+        let before = Product::show(context, form.product_id)?.product;
+        if let Some(param_unit_id) = form.unit_id {
+            ProductUnit::check_purchase_unit(context, before.id, param_unit_id)?;
+        }
+        let quantity = ProductUnit::convert(context, before.id, form.quantity, form.unit_id, None)?;
+        if let Some(param_location_id) = form.location_id {
+            Location::find(context, param_location_id)?;
+        }
+        match form.lot_id {
+            Some(param_lot_id) => {
+                if Lot::find(context, param_lot_id)?.product_id != before.id {
+                    return Err(format!(
+                        "Lot {} doesn't belong to product {}",
+                        param_lot_id, before.id
+                    )
+                    .into());
+                }
+            }
+            None if before.track_lots => {
+                return Err(format!("Product {} is received into a lot", before.id).into());
+            }
+            None => {}
+        }
+        match &form.serials {
+            Some(serials) if before.serialized && serials.len() as f64 != quantity => {
+                return Err(format!(
+                    "{} serial numbers were given for {} units",
+                    serials.len(),
+                    quantity
+                )
+                .into());
+            }
+            Some(_) if !before.serialized => {
+                return Err(format!("Product {} isn't serialized", before.id).into());
+            }
+            None if before.serialized => {
+                return Err(
+                    format!("Product {} is received with serial numbers", before.id).into(),
+                );
+            }
+            _ => {}
+        }
+
+        connection.transaction(|| {
+            if let Some(serials) = form.serials {
+                SerialNumber::register(context, before.id, form.location_id, serials)?;
+            }
+            let movement = StockMovement::post(
+                context,
+                StockChange {
+                    product_id: before.id,
+                    location_id: form.location_id,
+                    lot_id: form.lot_id,
+                    quantity,
+                    kind: StockMovementKind::Receipt,
+                    sale_id: None,
+                    transfer_id: None,
+                    reason: None,
+                    stock_count_id: None,
+                },
+            )?;
+            let product = Product::show(context, before.id)?.product;
+
+            AuditEvent::record(
+                context,
+                "receiveStock",
+                "product",
+                product.id,
+                Some(&before),
+                Some(&product),
+            )?;
+            Ok(movement)
+        })
+    }
+
+    /// Takes the sold quantities out of the sale location. Kits consume
+    /// their components and alternative units are converted to the base
+    /// unit.
+    pub fn post_sale(context: &Context, sale: &Sale) -> FieldResult<Vec<StockMovement>> {
+        let connection: &PgConnection = &context.conn;
+
+        let sale_products = schema::sale_products::table
+            .select(SALE_PRODUCT_COLUMNS)
+            .filter(schema::sale_products::sale_id.eq(sale.id))
+            .load::<SaleProduct>(connection)?;
+
+        let mut movements = vec![];
+        for sale_product in sale_products {
+            let factor =
+                ProductUnit::factor(context, sale_product.product_id, sale_product.unit_id)?;
+            let amount = sale_product.amount * factor;
+
+            let consumed = if sale_product.lot_id.is_some() {
+                vec![(sale_product.product_id, amount)]
+            } else {
+                ProductComponent::consumed_products(context, sale_product.product_id, amount)?
+                    .into_iter()
+                    .map(|consumed_product| {
+                        (consumed_product.product_id, consumed_product.quantity)
+                    })
+                    .collect()
+            };
+
+            for (product_id, quantity) in consumed {
+                movements.push(StockMovement::post(
+                    context,
+                    StockChange {
+                        product_id,
+                        location_id: sale.location_id,
+                        lot_id: sale_product.lot_id,
+                        quantity: -quantity,
+                        kind: StockMovementKind::Sale,
+                        sale_id: Some(sale.id),
+                        transfer_id: None,
+                        reason: None,
+                        stock_count_id: None,
+                    },
+                )?);
+            }
+        }
+        Ok(movements)
+    }
+
+    /// Puts back everything `post_sale` took out for the sale, serials
+    /// included.
+    pub fn revert_sale(context: &Context, sale_id: i32) -> FieldResult<Vec<StockMovement>> {
+        let connection: &PgConnection = &context.conn;
+
+        let sale_movements = dsl::stock_movements
+            .filter(dsl::user_id.eq(context.user_id))
+            .filter(dsl::sale_id.eq(sale_id))
+            .filter(dsl::kind.eq(StockMovementKind::Sale))
+            .load::<StockMovement>(connection)?;
+        SerialNumber::release_sale(context, sale_id)?;
+
+        sale_movements
+            .into_iter()
+            .map(|movement| {
+                StockMovement::post(
+                    context,
+                    StockChange {
+                        product_id: movement.product_id,
+                        location_id: movement.location_id,
+                        lot_id: movement.lot_id,
+                        quantity: -movement.quantity,
+                        kind: StockMovementKind::SaleCancellation,
+                        sale_id: Some(sale_id),
+                        transfer_id: None,
+                        reason: None,
+                        stock_count_id: None,
+                    },
+                )
+            })
+            .collect()
+    }
+}
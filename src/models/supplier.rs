@@ -0,0 +1,141 @@
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::Context;
+use crate::schema::suppliers;
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListSupplier {
+    pub data: Vec<Supplier>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "suppliers"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Supplier products are bought from")]
+pub struct Supplier {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    #[graphql(description = "Days between ordering and receiving the goods")]
+    pub lead_time_days: i32,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "suppliers"]
+pub struct FormSupplier {
+    pub id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub name: Option<String>,
+    pub lead_time_days: Option<i32>,
+}
+
+impl Supplier {
+    pub fn list(context: &Context) -> FieldResult<ListSupplier> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(ListSupplier {
+            data: suppliers::table
+                .filter(suppliers::user_id.eq(context.user_id))
+                .order(suppliers::name.asc())
+                .load::<Supplier>(connection)?,
+        })
+    }
+
+    pub fn create(context: &Context, form: FormSupplier) -> FieldResult<Supplier> {
+        let connection: &PgConnection = &context.conn;
+
+        let new_supplier = FormSupplier {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let supplier = diesel::insert_into(suppliers::table)
+                .values(new_supplier)
+                .get_result::<Supplier>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createSupplier",
+                "supplier",
+                supplier.id,
+                None::<&Supplier>,
+                Some(&supplier),
+            )?;
+            Ok(supplier)
+        })
+    }
+
+    pub fn update(context: &Context, form: FormSupplier) -> FieldResult<Supplier> {
+        let connection: &PgConnection = &context.conn;
+
+        let supplier_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+
+        let supplier_to_replace = FormSupplier {
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let before = Supplier::find(context, supplier_id)?;
+
+            let supplier = diesel::update(suppliers::table.find(before.id))
+                .set(supplier_to_replace)
+                .get_result::<Supplier>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updateSupplier",
+                "supplier",
+                supplier_id,
+                Some(&before),
+                Some(&supplier),
+            )?;
+            Ok(supplier)
+        })
+    }
+
+    pub fn find(context: &Context, supplier_id: i32) -> FieldResult<Supplier> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(suppliers::table
+            .filter(suppliers::user_id.eq(context.user_id))
+            .find(supplier_id)
+            .first(connection)?)
+    }
+
+    /// Products bought from the supplier are left without preferred one.
+    pub fn destroy(context: &Context, supplier_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Supplier::find(context, supplier_id)?;
+
+            diesel::delete(suppliers::table.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroySupplier",
+                "supplier",
+                supplier_id,
+                Some(&before),
+                None::<&Supplier>,
+            )?;
+            Ok(true)
+        })
+    }
+}
@@ -0,0 +1,616 @@
+use chrono::{Local, NaiveDateTime};
+use diesel::sql_types::{BigInt, Double, Integer, Nullable};
+use diesel::{
+    BelongingToDsl, Connection, ExpressionMethods, GroupedBy, PgConnection, QueryDsl, RunQueryDsl,
+};
+use juniper::FieldResult;
+use std::collections::HashMap;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::location::{Location, ProductStock};
+use crate::models::lot::Lot;
+use crate::models::product::{Product, PRODUCT_COLUMNS};
+use crate::models::stock_movement::{StockChange, StockMovement, StockMovementKind};
+use crate::models::Context;
+use crate::schema;
+use crate::schema::transfer_products;
+use crate::schema::transfers;
+use crate::schema::transfers::dsl;
+
+#[derive(DbEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, juniper::GraphQLEnum)]
+pub enum TransferState {
+    Draft,
+    InTransit,
+    Received,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "transfers"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Stock sent from one location to another")]
+pub struct Transfer {
+    pub id: i32,
+    pub user_id: i32,
+    pub source_location_id: i32,
+    pub destination_location_id: i32,
+    pub state: TransferState,
+    pub created_at: NaiveDateTime,
+    pub sent_at: Option<NaiveDateTime>,
+    pub received_at: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "transfers"]
+pub struct FormTransfer {
+    pub id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub source_location_id: Option<i32>,
+    pub destination_location_id: Option<i32>,
+}
+
+#[derive(
+    Identifiable, Associations, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(Transfer)]
+#[belongs_to(Product)]
+#[table_name = "transfer_products"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Line of a transfer, quantities in the base unit of the product")]
+pub struct TransferProduct {
+    pub id: i32,
+    pub transfer_id: i32,
+    pub product_id: i32,
+    pub lot_id: Option<i32>,
+    pub quantity: f64,
+    #[graphql(description = "Missing until the transfer is received")]
+    pub received_quantity: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+pub struct FormTransferProduct {
+    pub product_id: i32,
+    pub lot_id: Option<i32>,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, juniper::GraphQLInputObject)]
+#[graphql(description = "Quantity that arrived for a line, when it differs from the one sent")]
+pub struct FormReceivedTransferProduct {
+    pub transfer_product_id: i32,
+    pub received_quantity: f64,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "transfer_products"]
+struct NewTransferProduct {
+    transfer_id: i32,
+    product_id: i32,
+    lot_id: Option<i32>,
+    quantity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, juniper::GraphQLObject)]
+pub struct FullTransferProduct {
+    pub transfer_product: TransferProduct,
+    pub product: Product,
+}
+
+#[derive(Debug, Clone, Serialize, juniper::GraphQLObject)]
+pub struct FullTransfer {
+    pub transfer: Transfer,
+    pub transfer_products: Vec<FullTransferProduct>,
+}
+
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+pub struct ListTransfer {
+    pub data: Vec<FullTransfer>,
+}
+
+#[derive(Debug, Clone, QueryableByName, juniper::GraphQLObject)]
+#[graphql(description = "Quantity of a product on its way to a location")]
+pub struct InTransitStock {
+    #[sql_type = "Integer"]
+    pub product_id: i32,
+    #[sql_type = "Integer"]
+    pub location_id: i32,
+    #[sql_type = "Double"]
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone, QueryableByName, juniper::GraphQLObject)]
+#[graphql(description = "Received transfer line where less or more arrived than was sent")]
+pub struct TransferDiscrepancy {
+    #[sql_type = "Integer"]
+    pub transfer_id: i32,
+    #[sql_type = "Integer"]
+    pub transfer_product_id: i32,
+    #[sql_type = "Integer"]
+    pub product_id: i32,
+    #[sql_type = "Nullable<Integer>"]
+    pub lot_id: Option<i32>,
+    #[sql_type = "Double"]
+    pub quantity: f64,
+    #[sql_type = "Double"]
+    pub received_quantity: f64,
+    #[sql_type = "Double"]
+    pub difference: f64,
+}
+
+impl Transfer {
+    pub fn list(
+        context: &Context,
+        state: Option<TransferState>,
+        limit: i32,
+    ) -> FieldResult<ListTransfer> {
+        let connection: &PgConnection = &context.conn;
+
+        let mut query = dsl::transfers
+            .filter(dsl::user_id.eq(context.user_id))
+            .into_boxed();
+        if let Some(state) = state {
+            query = query.filter(dsl::state.eq(state));
+        }
+        let query_transfers = query
+            .order(dsl::created_at.desc())
+            .limit(i64::from(limit))
+            .load::<Transfer>(connection)?;
+
+        let query_transfer_products = TransferProduct::belonging_to(&query_transfers)
+            .inner_join(schema::products::table)
+            .select((transfer_products::all_columns, PRODUCT_COLUMNS))
+            .order(transfer_products::id.asc())
+            .load::<(TransferProduct, Product)>(connection)?
+            .grouped_by(&query_transfers);
+
+        Ok(ListTransfer {
+            data: query_transfers
+                .into_iter()
+                .zip(query_transfer_products)
+                .map(|(transfer, lines)| FullTransfer {
+                    transfer,
+                    transfer_products: lines
+                        .into_iter()
+                        .map(|(transfer_product, product)| FullTransferProduct {
+                            transfer_product,
+                            product,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        })
+    }
+
+    pub fn show(context: &Context, transfer_id: i32) -> FieldResult<FullTransfer> {
+        let connection: &PgConnection = &context.conn;
+
+        let transfer = dsl::transfers
+            .filter(dsl::user_id.eq(context.user_id))
+            .find(transfer_id)
+            .first::<Transfer>(connection)?;
+
+        let transfer_products = TransferProduct::belonging_to(&transfer)
+            .inner_join(schema::products::table)
+            .select((transfer_products::all_columns, PRODUCT_COLUMNS))
+            .order(transfer_products::id.asc())
+            .load::<(TransferProduct, Product)>(connection)?
+            .into_iter()
+            .map(|(transfer_product, product)| FullTransferProduct {
+                transfer_product,
+                product,
+            })
+            .collect();
+
+        Ok(FullTransfer {
+            transfer,
+            transfer_products,
+        })
+    }
+
+    pub fn create(
+        context: &Context,
+        form: FormTransfer,
+        lines: Vec<FormTransferProduct>,
+    ) -> FieldResult<FullTransfer> {
+        let connection: &PgConnection = &context.conn;
+
+        Transfer::check_locations(context, &form)?;
+        Transfer::check_lines(context, &lines)?;
+
+        let new_transfer = FormTransfer {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let transfer = diesel::insert_into(transfers::table)
+                .values((
+                    new_transfer,
+                    dsl::state.eq(TransferState::Draft),
+                    dsl::created_at.eq(Local::now().naive_local()),
+                ))
+                .get_result::<Transfer>(connection)?;
+
+            Transfer::insert_lines(context, transfer.id, lines)?;
+
+            let full_transfer = Transfer::show(context, transfer.id)?;
+            AuditEvent::record(
+                context,
+                "createTransfer",
+                "transfer",
+                transfer.id,
+                None::<&FullTransfer>,
+                Some(&full_transfer),
+            )?;
+            Ok(full_transfer)
+        })
+    }
+
+    /// Only drafts can be changed, the lines given replace the old ones.
+    pub fn update(
+        context: &Context,
+        form: FormTransfer,
+        lines: Vec<FormTransferProduct>,
+    ) -> FieldResult<FullTransfer> {
+        let connection: &PgConnection = &context.conn;
+
+        let transfer_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+        Transfer::check_lines(context, &lines)?;
+
+        let transfer_to_replace = FormTransfer {
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let before = Transfer::show(context, transfer_id)?;
+            before.transfer.check_state(TransferState::Draft)?;
+
+            let transfer = diesel::update(dsl::transfers.find(before.transfer.id))
+                .set(transfer_to_replace)
+                .get_result::<Transfer>(connection)?;
+            Transfer::check_locations(
+                context,
+                &FormTransfer {
+                    id: None,
+                    user_id: None,
+                    source_location_id: Some(transfer.source_location_id),
+                    destination_location_id: Some(transfer.destination_location_id),
+                },
+            )?;
+
+            diesel::delete(
+                transfer_products::table.filter(transfer_products::transfer_id.eq(transfer.id)),
+            )
+            .execute(connection)?;
+            Transfer::insert_lines(context, transfer.id, lines)?;
+
+            let full_transfer = Transfer::show(context, transfer.id)?;
+            AuditEvent::record(
+                context,
+                "updateTransfer",
+                "transfer",
+                transfer_id,
+                Some(&before),
+                Some(&full_transfer),
+            )?;
+            Ok(full_transfer)
+        })
+    }
+
+    pub fn destroy(context: &Context, transfer_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Transfer::show(context, transfer_id)?;
+            before.transfer.check_state(TransferState::Draft)?;
+
+            diesel::delete(dsl::transfers.find(before.transfer.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyTransfer",
+                "transfer",
+                transfer_id,
+                Some(&before),
+                None::<&FullTransfer>,
+            )?;
+            Ok(true)
+        })
+    }
+
+    /// Takes the lines out of the source location. Until the transfer is
+    /// received the quantities are only visible as in transit. The source
+    /// location has to hold what is sent.
+    pub fn send(context: &Context, transfer_id: i32) -> FieldResult<FullTransfer> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Transfer::show(context, transfer_id)?;
+            before.transfer.check_state(TransferState::Draft)?;
+            if before.transfer_products.is_empty() {
+                return Err(format!("Transfer {} has no lines", transfer_id).into());
+            }
+            let mut sent: HashMap<i32, f64> = HashMap::new();
+            for line in &before.transfer_products {
+                *sent.entry(line.transfer_product.product_id).or_insert(0.0) +=
+                    line.transfer_product.quantity;
+            }
+            for (product_id, quantity) in sent {
+                let stock =
+                    ProductStock::stock(context, product_id, before.transfer.source_location_id)?;
+                if quantity > stock {
+                    return Err(format!(
+                        "Product {} has {} in stock at the source location, {} can't be sent",
+                        product_id, stock, quantity
+                    )
+                    .into());
+                }
+            }
+
+            for line in &before.transfer_products {
+                StockMovement::post(
+                    context,
+                    StockChange {
+                        product_id: line.transfer_product.product_id,
+                        location_id: Some(before.transfer.source_location_id),
+                        lot_id: line.transfer_product.lot_id,
+                        quantity: -line.transfer_product.quantity,
+                        kind: StockMovementKind::TransferOut,
+                        sale_id: None,
+                        transfer_id: Some(transfer_id),
+                        reason: None,
+                        stock_count_id: None,
+                    },
+                )?;
+            }
+
+            diesel::update(dsl::transfers.find(transfer_id))
+                .set((
+                    dsl::state.eq(TransferState::InTransit),
+                    dsl::sent_at.eq(Local::now().naive_local()),
+                ))
+                .execute(connection)?;
+
+            let full_transfer = Transfer::show(context, transfer_id)?;
+            AuditEvent::record(
+                context,
+                "sendTransfer",
+                "transfer",
+                transfer_id,
+                Some(&before),
+                Some(&full_transfer),
+            )?;
+            Ok(full_transfer)
+        })
+    }
+
+    /// Puts the lines into the destination location. Lines missing from
+    /// `received` arrived complete; for the others the difference with
+    /// what was sent is posted as a discrepancy, so a shortfall is
+    /// written off and a surplus added to the destination.
+    pub fn receive(
+        context: &Context,
+        transfer_id: i32,
+        received: Vec<FormReceivedTransferProduct>,
+    ) -> FieldResult<FullTransfer> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Transfer::show(context, transfer_id)?;
+            before.transfer.check_state(TransferState::InTransit)?;
+
+            for form_received in &received {
+                if form_received.received_quantity < 0.0 {
+                    return Err("Received quantities can't be negative".into());
+                }
+                if !before
+                    .transfer_products
+                    .iter()
+                    .any(|line| line.transfer_product.id == form_received.transfer_product_id)
+                {
+                    return Err(format!(
+                        "Line {} doesn't belong to transfer {}",
+                        form_received.transfer_product_id, transfer_id
+                    )
+                    .into());
+                }
+            }
+
+            for line in &before.transfer_products {
+                let sent = line.transfer_product.quantity;
+                let received_quantity = received
+                    .iter()
+                    .find(|form_received| {
+                        form_received.transfer_product_id == line.transfer_product.id
+                    })
+                    .map_or(sent, |form_received| form_received.received_quantity);
+
+                let mut change = StockChange {
+                    product_id: line.transfer_product.product_id,
+                    location_id: Some(before.transfer.destination_location_id),
+                    lot_id: line.transfer_product.lot_id,
+                    quantity: sent,
+                    kind: StockMovementKind::TransferIn,
+                    sale_id: None,
+                    transfer_id: Some(transfer_id),
+                    reason: None,
+                    stock_count_id: None,
+                };
+                StockMovement::post(context, change.clone())?;
+                if (received_quantity - sent).abs() > f64::EPSILON {
+                    change.quantity = received_quantity - sent;
+                    change.kind = StockMovementKind::TransferDiscrepancy;
+                    StockMovement::post(context, change)?;
+                }
+
+                diesel::update(transfer_products::table.find(line.transfer_product.id))
+                    .set(transfer_products::received_quantity.eq(received_quantity))
+                    .execute(connection)?;
+            }
+
+            diesel::update(dsl::transfers.find(transfer_id))
+                .set((
+                    dsl::state.eq(TransferState::Received),
+                    dsl::received_at.eq(Local::now().naive_local()),
+                ))
+                .execute(connection)?;
+
+            let full_transfer = Transfer::show(context, transfer_id)?;
+            AuditEvent::record(
+                context,
+                "receiveTransfer",
+                "transfer",
+                transfer_id,
+                Some(&before),
+                Some(&full_transfer),
+            )?;
+            Ok(full_transfer)
+        })
+    }
+
+    /// Stock sent but not received yet, by product and destination.
+    pub fn in_transit(
+        context: &Context,
+        location_id: Option<i32>,
+    ) -> FieldResult<Vec<InTransitStock>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(diesel::sql_query(
+            "SELECT transfer_products.product_id, \
+                    transfers.destination_location_id AS location_id, \
+                    SUM(transfer_products.quantity) AS quantity \
+             FROM transfer_products \
+             INNER JOIN transfers ON transfers.id = transfer_products.transfer_id \
+             WHERE transfers.user_id = $1 \
+               AND transfers.state = 'in_transit' \
+               AND ($2::integer IS NULL OR transfers.destination_location_id = $2) \
+             GROUP BY transfer_products.product_id, transfers.destination_location_id \
+             ORDER BY transfer_products.product_id, transfers.destination_location_id",
+        )
+        .bind::<Integer, _>(context.user_id)
+        .bind::<Nullable<Integer>, _>(location_id)
+        .load::<InTransitStock>(connection)?)
+    }
+
+    /// Received lines whose quantity didn't match the one sent, newest
+    /// transfers first.
+    pub fn discrepancies(context: &Context, limit: i32) -> FieldResult<Vec<TransferDiscrepancy>> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(diesel::sql_query(
+            "SELECT transfers.id AS transfer_id, \
+                    transfer_products.id AS transfer_product_id, \
+                    transfer_products.product_id, \
+                    transfer_products.lot_id, \
+                    transfer_products.quantity, \
+                    transfer_products.received_quantity, \
+                    transfer_products.received_quantity - transfer_products.quantity \
+                      AS difference \
+             FROM transfer_products \
+             INNER JOIN transfers ON transfers.id = transfer_products.transfer_id \
+             WHERE transfers.user_id = $1 \
+               AND transfers.state = 'received' \
+               AND transfer_products.received_quantity <> transfer_products.quantity \
+             ORDER BY transfers.received_at DESC, transfer_products.id \
+             LIMIT $2",
+        )
+        .bind::<Integer, _>(context.user_id)
+        .bind::<BigInt, _>(i64::from(limit))
+        .load::<TransferDiscrepancy>(connection)?)
+    }
+
+    fn check_state(&self, state: TransferState) -> FieldResult<()> {
+        if self.state != state {
+            return Err(format!(
+                "Transfer {} is {:?}, it should be {:?}",
+                self.id, self.state, state
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn check_locations(context: &Context, form: &FormTransfer) -> FieldResult<()> {
+        let source_location_id =
+            form.source_location_id
+                .ok_or(diesel::result::Error::QueryBuilderError(
+                    "missing source_location_id".into(),
+                ))?;
+        let destination_location_id =
+            form.destination_location_id
+                .ok_or(diesel::result::Error::QueryBuilderError(
+                    "missing destination_location_id".into(),
+                ))?;
+        if source_location_id == destination_location_id {
+            return Err("A transfer needs two different locations".into());
+        }
+        Location::find(context, source_location_id)?;
+        Location::find(context, destination_location_id)?;
+        Ok(())
+    }
+
+    fn check_lines(context: &Context, lines: &[FormTransferProduct]) -> FieldResult<()> {
+        for line in lines {
+            if line.quantity <= 0.0 {
+                return Err(
+                    format!("Quantity of product {} should be positive", line.product_id).into(),
+                );
+            }
+            let product = Product::show(context, line.product_id)?.product;
+            match (product.track_lots, line.lot_id) {
+                (false, None) => {}
+                (false, Some(_)) => {
+                    return Err(format!("Product {} doesn't track lots", product.id).into());
+                }
+                (true, None) => {
+                    return Err(format!("Product {} needs a lot", product.id).into());
+                }
+                (true, Some(param_lot_id)) => {
+                    let lot = Lot::find(context, param_lot_id)?;
+                    if lot.product_id != product.id {
+                        return Err(format!(
+                            "Lot {} doesn't belong to product {}",
+                            lot.lot_number, product.id
+                        )
+                        .into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_lines(
+        context: &Context,
+        transfer_id: i32,
+        lines: Vec<FormTransferProduct>,
+    ) -> FieldResult<()> {
+        let connection: &PgConnection = &context.conn;
+
+        let new_lines: Vec<NewTransferProduct> = lines
+            .into_iter()
+            .map(|line| NewTransferProduct {
+                transfer_id,
+                product_id: line.product_id,
+                lot_id: line.lot_id,
+                quantity: line.quantity,
+            })
+            .collect();
+        diesel::insert_into(transfer_products::table)
+            .values(&new_lines)
+            .execute(connection)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
+};
+use juniper::FieldResult;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::product::Product;
+use crate::models::Context;
+use crate::schema::product_units;
+use crate::schema::units;
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListUnit {
+    pub data: Vec<Unit>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[table_name = "units"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Unit of measure, like kg or box of 12")]
+pub struct Unit {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "units"]
+pub struct FormUnit {
+    pub id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, juniper::GraphQLObject)]
+pub struct ListProductUnit {
+    pub data: Vec<ProductUnit>,
+}
+
+#[derive(
+    Identifiable, Associations, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq,
+)]
+#[belongs_to(Product)]
+#[belongs_to(Unit)]
+#[table_name = "product_units"]
+#[derive(juniper::GraphQLObject)]
+#[graphql(description = "Alternative unit a product is sold or purchased in")]
+pub struct ProductUnit {
+    pub id: i32,
+    pub product_id: i32,
+    pub unit_id: i32,
+    pub user_id: i32,
+    #[graphql(description = "How many base units of the product one of this unit holds")]
+    pub factor: f64,
+    pub for_sale: bool,
+    pub for_purchase: bool,
+}
+
+#[derive(
+    Insertable,
+    Deserialize,
+    Serialize,
+    AsChangeset,
+    Debug,
+    Clone,
+    PartialEq,
+    juniper::GraphQLInputObject,
+)]
+#[table_name = "product_units"]
+pub struct FormProductUnit {
+    pub id: Option<i32>,
+    pub product_id: Option<i32>,
+    pub unit_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub factor: Option<f64>,
+    pub for_sale: Option<bool>,
+    pub for_purchase: Option<bool>,
+}
+
+impl Unit {
+    pub fn list(context: &Context) -> FieldResult<ListUnit> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(ListUnit {
+            data: units::table
+                .filter(units::user_id.eq(context.user_id))
+                .order(units::name.asc())
+                .load::<Unit>(connection)?,
+        })
+    }
+
+    pub fn create(context: &Context, form: FormUnit) -> FieldResult<Unit> {
+        let connection: &PgConnection = &context.conn;
+
+        let new_unit = FormUnit {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let unit = diesel::insert_into(units::table)
+                .values(new_unit)
+                .get_result::<Unit>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createUnit",
+                "unit",
+                unit.id,
+                None::<&Unit>,
+                Some(&unit),
+            )?;
+            Ok(unit)
+        })
+    }
+
+    pub fn update(context: &Context, form: FormUnit) -> FieldResult<Unit> {
+        let connection: &PgConnection = &context.conn;
+
+        let unit_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+
+        let unit_to_replace = FormUnit {
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let before = Unit::find(context, unit_id)?;
+
+            let unit = diesel::update(units::table.find(before.id))
+                .set(unit_to_replace)
+                .get_result::<Unit>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updateUnit",
+                "unit",
+                unit_id,
+                Some(&before),
+                Some(&unit),
+            )?;
+            Ok(unit)
+        })
+    }
+
+    pub fn find(context: &Context, unit_id: i32) -> FieldResult<Unit> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(units::table
+            .filter(units::user_id.eq(context.user_id))
+            .find(unit_id)
+            .first(connection)?)
+    }
+
+    pub fn destroy(context: &Context, unit_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = Unit::find(context, unit_id)?;
+
+            diesel::delete(units::table.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyUnit",
+                "unit",
+                unit_id,
+                Some(&before),
+                None::<&Unit>,
+            )?;
+            Ok(true)
+        })
+    }
+}
+
+impl ProductUnit {
+    pub fn list(context: &Context, param_product_id: i32) -> FieldResult<ListProductUnit> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(ListProductUnit {
+            data: product_units::table
+                .filter(product_units::user_id.eq(context.user_id))
+                .filter(product_units::product_id.eq(param_product_id))
+                .order(product_units::factor.asc())
+                .load::<ProductUnit>(connection)?,
+        })
+    }
+
+    pub fn create(context: &Context, form: FormProductUnit) -> FieldResult<ProductUnit> {
+        let connection: &PgConnection = &context.conn;
+
+        let param_product_id = form
+            .product_id
+            .ok_or(diesel::result::Error::QueryBuilderError(
+                "missing product_id".into(),
+            ))?;
+        let param_unit_id = form
+            .unit_id
+            .ok_or(diesel::result::Error::QueryBuilderError(
+                "missing unit_id".into(),
+            ))?;
+
+        let product = Product::show(context, param_product_id)?.product;
+        Unit::find(context, param_unit_id)?;
+        if product.unit_id.is_none() {
+            return Err("The product needs a base unit before adding other units".into());
+        }
+        if product.unit_id == Some(param_unit_id) {
+            return Err("The base unit of a product always has a factor of 1".into());
+        }
+
+        let new_product_unit = FormProductUnit {
+            id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let product_unit = diesel::insert_into(product_units::table)
+                .values(new_product_unit)
+                .get_result::<ProductUnit>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "createProductUnit",
+                "product_unit",
+                product_unit.id,
+                None::<&ProductUnit>,
+                Some(&product_unit),
+            )?;
+            Ok(product_unit)
+        })
+    }
+
+    pub fn update(context: &Context, form: FormProductUnit) -> FieldResult<ProductUnit> {
+        let connection: &PgConnection = &context.conn;
+
+        let product_unit_id = form.id.ok_or(diesel::result::Error::QueryBuilderError(
+            "missing id".into(),
+        ))?;
+
+        let product_unit_to_replace = FormProductUnit {
+            product_id: None,
+            unit_id: None,
+            user_id: Some(context.user_id),
+            ..form
+        };
+
+        connection.transaction(|| {
+            let before = ProductUnit::find(context, product_unit_id)?;
+
+            let product_unit = diesel::update(product_units::table.find(before.id))
+                .set(product_unit_to_replace)
+                .get_result::<ProductUnit>(connection)?;
+
+            AuditEvent::record(
+                context,
+                "updateProductUnit",
+                "product_unit",
+                product_unit_id,
+                Some(&before),
+                Some(&product_unit),
+            )?;
+            Ok(product_unit)
+        })
+    }
+
+    pub fn find(context: &Context, product_unit_id: i32) -> FieldResult<ProductUnit> {
+        let connection: &PgConnection = &context.conn;
+
+        Ok(product_units::table
+            .filter(product_units::user_id.eq(context.user_id))
+            .find(product_unit_id)
+            .first(connection)?)
+    }
+
+    pub fn destroy(context: &Context, product_unit_id: i32) -> FieldResult<bool> {
+        let connection: &PgConnection = &context.conn;
+
+        connection.transaction(|| {
+            let before = ProductUnit::find(context, product_unit_id)?;
+
+            diesel::delete(product_units::table.find(before.id)).execute(connection)?;
+
+            AuditEvent::record(
+                context,
+                "destroyProductUnit",
+                "product_unit",
+                product_unit_id,
+                Some(&before),
+                None::<&ProductUnit>,
+            )?;
+            Ok(true)
+        })
+    }
+
+    /// Base units held by one `unit_id` of the product. A missing unit or
+    /// the base unit itself is 1.
+    pub fn factor(context: &Context, product_id: i32, unit_id: Option<i32>) -> FieldResult<f64> {
+        let connection: &PgConnection = &context.conn;
+
+        let product = Product::show(context, product_id)?.product;
+        let unit_id = match unit_id {
+            Some(unit_id) if product.unit_id != Some(unit_id) => unit_id,
+            _ => return Ok(1.0),
+        };
+
+        product_units::table
+            .select(product_units::factor)
+            .filter(product_units::user_id.eq(context.user_id))
+            .filter(product_units::product_id.eq(product_id))
+            .filter(product_units::unit_id.eq(unit_id))
+            .first::<f64>(connection)
+            .optional()?
+            .ok_or_else(|| {
+                format!(
+                    "Unit {} isn't configured for product {}",
+                    unit_id, product_id
+                )
+                .into()
+            })
+    }
+
+    /// Converts an amount of the product between two of its units, a
+    /// missing unit is the base unit.
+    pub fn convert(
+        context: &Context,
+        product_id: i32,
+        amount: f64,
+        from_unit_id: Option<i32>,
+        to_unit_id: Option<i32>,
+    ) -> FieldResult<f64> {
+        let from_factor = ProductUnit::factor(context, product_id, from_unit_id)?;
+        let to_factor = ProductUnit::factor(context, product_id, to_unit_id)?;
+        Ok(amount * from_factor / to_factor)
+    }
+
+    /// Sale lines can use the base unit or any unit enabled for sale.
+    pub fn check_sale_unit(context: &Context, product_id: i32, unit_id: i32) -> FieldResult<()> {
+        let connection: &PgConnection = &context.conn;
+
+        let product = Product::show(context, product_id)?.product;
+        if product.unit_id == Some(unit_id) {
+            return Ok(());
+        }
+
+        let for_sale = product_units::table
+            .select(product_units::for_sale)
+            .filter(product_units::user_id.eq(context.user_id))
+            .filter(product_units::product_id.eq(product_id))
+            .filter(product_units::unit_id.eq(unit_id))
+            .first::<bool>(connection)
+            .optional()?;
+
+        match for_sale {
+            Some(true) => Ok(()),
+            _ => Err(format!("Product {} can't be sold in unit {}", product_id, unit_id).into()),
+        }
+    }
+
+    /// Receipts can use the base unit or any unit enabled for purchase.
+    pub fn check_purchase_unit(
+        context: &Context,
+        product_id: i32,
+        unit_id: i32,
+    ) -> FieldResult<()> {
+        let connection: &PgConnection = &context.conn;
+
+        let product = Product::show(context, product_id)?.product;
+        if product.unit_id == Some(unit_id) {
+            return Ok(());
+        }
+
+        let for_purchase = product_units::table
+            .select(product_units::for_purchase)
+            .filter(product_units::user_id.eq(context.user_id))
+            .filter(product_units::product_id.eq(product_id))
+            .filter(product_units::unit_id.eq(unit_id))
+            .first::<bool>(connection)
+            .optional()?;
+
+        match for_purchase {
+            Some(true) => Ok(()),
+            _ => Err(format!(
+                "Product {} can't be purchased in unit {}",
+                product_id, unit_id
+            )
+            .into()),
+        }
+    }
+}
@@ -1,11 +1,24 @@
 use bcrypt::{hash, verify, DEFAULT_COST};
-use chrono::Local;
 use chrono::NaiveDateTime;
-use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use chrono::{Duration, Local};
+use diesel::{BoolExpressionMethods, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
 
 use crate::errors::MyStoreError;
+use crate::models::backup_code::BackupCode;
+use crate::models::login_attempt::LoginAttempt;
 use crate::schema::users;
-use crate::schema::users::dsl::email;
+use crate::schema::users::dsl::{
+    company, email, failed_login_attempts, locked_until, totp_enabled, totp_last_step, totp_secret,
+};
+use crate::utils::totp;
+
+// Failed logins allowed for an account before it gets locked, every further
+// failure doubles the lockout period.
+pub const MAX_FAILED_ATTEMPTS: i32 = 5;
+// Failed logins allowed from a single address inside the lockout window.
+const MAX_FAILED_ATTEMPTS_PER_IP: i64 = 20;
+const LOCKOUT_MINUTES: i64 = 15;
+const TOTP_ISSUER: &str = "MyStore";
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Insertable)]
 #[table_name = "users"]
@@ -16,6 +29,21 @@ pub struct User {
     #[serde(skip)]
     pub password: String,
     pub created_at: NaiveDateTime,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<NaiveDateTime>,
+    pub admin: bool,
+    #[serde(skip)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    #[serde(skip)]
+    pub totp_last_step: Option<i64>,
+    pub search_config: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable)]
@@ -45,6 +73,212 @@ impl User {
     pub fn hash_password(plain: String) -> Result<String, MyStoreError> {
         Ok(hash(plain, DEFAULT_COST)?)
     }
+
+    pub fn unlock(
+        admin_id: i32,
+        user_email: &str,
+        connection: &PgConnection,
+    ) -> Result<User, MyStoreError> {
+        let admin: User = users::table.find(admin_id).first(connection)?;
+        if !admin.admin {
+            return Err(MyStoreError::Unauthorized(
+                "Only administrators can unlock accounts".to_string(),
+            ));
+        }
+
+        Ok(diesel::update(
+            users::table
+                .filter(email.eq(user_email))
+                .filter(company.eq(&admin.company)),
+        )
+        .set((
+            failed_login_attempts.eq(0),
+            locked_until.eq(None::<NaiveDateTime>),
+        ))
+        .get_result(connection)?)
+    }
+
+    /// Grants or revokes the administrator role, used by the `grant_admin`
+    /// command since no account can grant it from the API.
+    pub fn set_admin(
+        user_email: &str,
+        is_admin: bool,
+        connection: &PgConnection,
+    ) -> Result<User, MyStoreError> {
+        Ok(diesel::update(users::table.filter(email.eq(user_email)))
+            .set(users::admin.eq(is_admin))
+            .get_result(connection)?)
+    }
+
+    /// Stores a fresh secret for the user, two factor authentication is not
+    /// required until the secret is confirmed with a valid code.
+    pub fn enroll_totp(
+        user_id: i32,
+        connection: &PgConnection,
+    ) -> Result<TotpEnrollment, MyStoreError> {
+        let user: User = users::table.find(user_id).first(connection)?;
+        if user.totp_enabled {
+            return Err(MyStoreError::TwoFactorError(
+                "Two factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let secret = totp::generate_secret();
+        diesel::update(users::table.find(user.id))
+            .set((totp_secret.eq(&secret), totp_last_step.eq(None::<i64>)))
+            .execute(connection)?;
+
+        Ok(TotpEnrollment {
+            otpauth_uri: totp::otpauth_uri(&secret, &user.email, TOTP_ISSUER),
+            secret,
+        })
+    }
+
+    /// Enables two factor authentication and returns the backup codes.
+    pub fn confirm_totp(
+        user_id: i32,
+        code: &str,
+        connection: &PgConnection,
+    ) -> Result<Vec<String>, MyStoreError> {
+        let user: User = users::table.find(user_id).first(connection)?;
+        if user.totp_enabled {
+            return Err(MyStoreError::TwoFactorError(
+                "Two factor authentication is already enabled".to_string(),
+            ));
+        }
+        let secret = user.totp_secret.ok_or_else(|| {
+            MyStoreError::TwoFactorError("Two factor enrollment was not started".to_string())
+        })?;
+        let step = totp::verify(&secret, code, Local::now().timestamp())
+            .ok_or_else(|| MyStoreError::WrongPassword("Wrong authentication code".to_string()))?;
+
+        diesel::update(users::table.find(user.id))
+            .set((totp_enabled.eq(true), totp_last_step.eq(step)))
+            .execute(connection)?;
+
+        BackupCode::regenerate(user.id, connection)
+    }
+
+    pub fn disable_totp(
+        user_id: i32,
+        code: &str,
+        connection: &PgConnection,
+    ) -> Result<(), MyStoreError> {
+        let user: User = users::table.find(user_id).first(connection)?;
+        if !user.verify_second_factor(code, connection)? {
+            return Err(MyStoreError::WrongPassword(
+                "Wrong authentication code".to_string(),
+            ));
+        }
+
+        diesel::update(users::table.find(user.id))
+            .set((
+                totp_enabled.eq(false),
+                totp_secret.eq(None::<String>),
+                totp_last_step.eq(None::<i64>),
+            ))
+            .execute(connection)?;
+        BackupCode::delete_all(user.id, connection)
+    }
+
+    /// Accepts either a TOTP code not used before or an unused backup code.
+    fn verify_second_factor(
+        &self,
+        code: &str,
+        connection: &PgConnection,
+    ) -> Result<bool, MyStoreError> {
+        if let Some(secret) = &self.totp_secret {
+            if let Some(step) = totp::verify(secret, code, Local::now().timestamp()) {
+                // A single conditional update, so concurrent logins can't
+                // both spend the same code.
+                let updated_rows = diesel::update(
+                    users::table
+                        .find(self.id)
+                        .filter(totp_last_step.is_null().or(totp_last_step.lt(step))),
+                )
+                .set(totp_last_step.eq(step))
+                .execute(connection)?;
+                if updated_rows == 1 {
+                    return Ok(true);
+                }
+            }
+        }
+        BackupCode::consume(self.id, code, connection)
+    }
+
+    pub fn check_not_locked(&self, now: NaiveDateTime) -> Result<(), MyStoreError> {
+        match self.locked_until {
+            Some(until) if until > now => Err(MyStoreError::AccountLocked(until)),
+            _ => Ok(()),
+        }
+    }
+
+    fn login_succeeded(
+        &self,
+        ip_address: &str,
+        connection: &PgConnection,
+    ) -> Result<User, MyStoreError> {
+        LoginAttempt::record(&self.email, ip_address, true, connection)?;
+        Ok(diesel::update(users::table.find(self.id))
+            .set((
+                failed_login_attempts.eq(0),
+                locked_until.eq(None::<NaiveDateTime>),
+            ))
+            .get_result(connection)?)
+    }
+
+    /// Records the failure and returns the error the client should see.
+    fn login_failed(
+        &self,
+        ip_address: &str,
+        now: NaiveDateTime,
+        error: MyStoreError,
+        connection: &PgConnection,
+    ) -> Result<MyStoreError, MyStoreError> {
+        LoginAttempt::record(&self.email, ip_address, false, connection)?;
+
+        // Incremented by the database, so concurrent failures all count.
+        let attempts: i32 = diesel::update(users::table.find(self.id))
+            .set(failed_login_attempts.eq(failed_login_attempts + 1))
+            .returning(failed_login_attempts)
+            .get_result(connection)?;
+        let lock = if attempts >= MAX_FAILED_ATTEMPTS {
+            let factor = 1 << (attempts - MAX_FAILED_ATTEMPTS).min(6);
+            Some(now + Duration::minutes(LOCKOUT_MINUTES * factor))
+        } else {
+            None
+        };
+
+        if lock.is_some() {
+            diesel::update(users::table.find(self.id))
+                .set(locked_until.eq(lock))
+                .execute(connection)?;
+        }
+
+        Ok(match lock {
+            Some(until) => MyStoreError::AccountLocked(until),
+            None => error,
+        })
+    }
+}
+
+fn check_ip_address(
+    ip_address: &str,
+    now: NaiveDateTime,
+    connection: &PgConnection,
+) -> Result<(), MyStoreError> {
+    let failures_from_ip = LoginAttempt::failures_from_ip_since(
+        ip_address,
+        now - Duration::minutes(LOCKOUT_MINUTES),
+        connection,
+    )?;
+    if failures_from_ip >= MAX_FAILED_ATTEMPTS_PER_IP {
+        Err(MyStoreError::TooManyAttempts(
+            "Too many failed login attempts, try again later".to_string(),
+        ))
+    } else {
+        Ok(())
+    }
 }
 
 #[derive(Deserialize)]
@@ -79,21 +313,83 @@ pub struct AuthUser {
     pub password: String,
 }
 
+#[derive(Deserialize)]
+pub struct UnlockUser {
+    pub email: String,
+}
+
 impl AuthUser {
-    pub fn login(&self, connection: &PgConnection) -> Result<User, MyStoreError> {
+    /// Checks the password, users with two factor authentication enabled
+    /// still have to answer a `TwoFactorChallenge` before getting a token.
+    pub fn login(&self, connection: &PgConnection, ip_address: &str) -> Result<User, MyStoreError> {
+        let now = Local::now().naive_local();
+        check_ip_address(ip_address, now, connection)?;
+
         let mut records = users::table
             .filter(email.eq(&self.email))
             .load::<User>(connection)?;
 
-        let user = records
-            .pop()
-            .ok_or(MyStoreError::DBError(diesel::result::Error::NotFound))?;
+        let user = match records.pop() {
+            Some(user) => user,
+            None => {
+                LoginAttempt::record(&self.email, ip_address, false, connection)?;
+                return Err(MyStoreError::DBError(diesel::result::Error::NotFound));
+            }
+        };
+        user.check_not_locked(now)?;
+
         if verify(&self.password, &user.password)? {
-            Ok(user)
+            // Failures are only cleared once the second factor is verified too,
+            // otherwise a known password would allow guessing codes forever.
+            if user.totp_enabled {
+                Ok(user)
+            } else {
+                user.login_succeeded(ip_address, connection)
+            }
         } else {
-            Err(MyStoreError::WrongPassword(
-                "Wrong password, check again please".to_string(),
-            ))
+            Err(user.login_failed(
+                ip_address,
+                now,
+                MyStoreError::WrongPassword("Wrong password, check again please".to_string()),
+                connection,
+            )?)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorChallenge {
+    pub challenge: String,
+    pub code: String,
+}
+
+impl TwoFactorChallenge {
+    pub fn verify(
+        &self,
+        user_id: i32,
+        connection: &PgConnection,
+        ip_address: &str,
+    ) -> Result<User, MyStoreError> {
+        let now = Local::now().naive_local();
+        check_ip_address(ip_address, now, connection)?;
+
+        let user: User = users::table.find(user_id).first(connection)?;
+        user.check_not_locked(now)?;
+
+        if user.verify_second_factor(&self.code, connection)? {
+            user.login_succeeded(ip_address, connection)
+        } else {
+            Err(user.login_failed(
+                ip_address,
+                now,
+                MyStoreError::WrongPassword("Wrong authentication code".to_string()),
+                connection,
+            )?)
         }
     }
 }
+
+#[derive(Deserialize)]
+pub struct TotpCode {
+    pub code: String,
+}
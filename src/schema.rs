@@ -1,8 +1,146 @@
+table! {
+    api_keys (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        prefix -> Varchar,
+        key_hash -> Varchar,
+        scopes -> Array<Text>,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    attachments (id) {
+        id -> Int4,
+        user_id -> Int4,
+        product_id -> Nullable<Int4>,
+        sale_id -> Nullable<Int4>,
+        filename -> Varchar,
+        content_type -> Varchar,
+        size -> Int4,
+        storage_key -> Varchar,
+        url -> Varchar,
+        thumbnail_key -> Nullable<Varchar>,
+        thumbnail_url -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    audit_events (id) {
+        id -> Int4,
+        user_id -> Int4,
+        company -> Varchar,
+        operation -> Varchar,
+        entity -> Varchar,
+        entity_id -> Int4,
+        before -> Nullable<Jsonb>,
+        after -> Nullable<Jsonb>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    backup_codes (id) {
+        id -> Int4,
+        user_id -> Int4,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    categories (id) {
+        id -> Int4,
+        name -> Varchar,
+        parent_id -> Nullable<Int4>,
+        user_id -> Int4,
+    }
+}
+
+table! {
+    customer_groups (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        price_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    customers (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        email -> Nullable<Varchar>,
+        customer_group_id -> Nullable<Int4>,
+        price_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    locations (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+    }
+}
+
+table! {
+    login_attempts (id) {
+        id -> Int4,
+        email -> Varchar,
+        ip_address -> Varchar,
+        succeeded -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    lots (id) {
+        id -> Int4,
+        product_id -> Int4,
+        user_id -> Int4,
+        lot_number -> Varchar,
+        expires_on -> Nullable<Date>,
+        stock -> Float8,
+    }
+}
+
+table! {
+    price_changes (id) {
+        id -> Int4,
+        user_id -> Int4,
+        price_id -> Int4,
+        product_id -> Int4,
+        old_amount -> Nullable<Int4>,
+        new_amount -> Nullable<Int4>,
+        changed_at -> Timestamp,
+    }
+}
+
+table! {
+    price_tiers (id) {
+        id -> Int4,
+        user_id -> Int4,
+        price_product_id -> Int4,
+        min_quantity -> Float8,
+        amount -> Int4,
+    }
+}
+
 table! {
     prices (id) {
         id -> Int4,
         name -> Varchar,
         user_id -> Int4,
+        archived_at -> Nullable<Timestamp>,
+        active_from -> Nullable<Date>,
+        active_to -> Nullable<Date>,
+        is_default -> Bool,
     }
 }
 
@@ -16,12 +154,82 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::Int4;
+    use diesel::sql_types::VarChar;
+    use crate::models::product_barcode::BarcodeSymbologyMapping;
+    product_barcodes (id) {
+        id -> Int4,
+        product_id -> Int4,
+        user_id -> Int4,
+        code -> VarChar,
+        symbology -> BarcodeSymbologyMapping,
+    }
+}
+
+table! {
+    product_components (id) {
+        id -> Int4,
+        kit_id -> Int4,
+        component_id -> Int4,
+        user_id -> Int4,
+        quantity -> Float8,
+    }
+}
+
+table! {
+    product_options (id) {
+        id -> Int4,
+        product_id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        option_values -> Array<Text>,
+        position -> Int4,
+    }
+}
+
+table! {
+    product_stocks (id) {
+        id -> Int4,
+        product_id -> Int4,
+        location_id -> Int4,
+        user_id -> Int4,
+        stock -> Float8,
+        min_stock -> Nullable<Float8>,
+        reorder_point -> Nullable<Float8>,
+        reorder_quantity -> Nullable<Float8>,
+    }
+}
+
+table! {
+    product_units (id) {
+        id -> Int4,
+        product_id -> Int4,
+        unit_id -> Int4,
+        user_id -> Int4,
+        factor -> Float8,
+        for_sale -> Bool,
+        for_purchase -> Bool,
+    }
+}
+
+table! {
+    product_variant_values (id) {
+        id -> Int4,
+        variant_id -> Int4,
+        product_option_id -> Int4,
+        value -> Varchar,
+    }
+}
+
 table! {
     use diesel_full_text_search::TsVector;
     use diesel::sql_types::Int4;
     use diesel::sql_types::VarChar;
     use diesel::sql_types::Float8;
     use diesel::sql_types::Nullable;
+    use diesel::sql_types::Bool;
+    use diesel::sql_types::Timestamp;
     products (id) {
         id -> Int4,
         name -> VarChar,
@@ -31,6 +239,17 @@ table! {
         text_searchable_product_col -> TsVector,
         product_rank -> Nullable<Float8>,
         user_id -> Int4,
+        category_id -> Nullable<Int4>,
+        sku -> Nullable<VarChar>,
+        parent_id -> Nullable<Int4>,
+        unit_id -> Nullable<Int4>,
+        track_lots -> Bool,
+        serialized -> Bool,
+        min_stock -> Nullable<Float8>,
+        reorder_point -> Nullable<Float8>,
+        reorder_quantity -> Nullable<Float8>,
+        supplier_id -> Nullable<Int4>,
+        archived_at -> Nullable<Timestamp>,
     }
 }
 
@@ -44,6 +263,25 @@ table! {
         tax -> Int4,
         price -> Int4,
         total -> Float8,
+        unit_id -> Nullable<Int4>,
+        lot_id -> Nullable<Int4>,
+        serials -> Nullable<Array<Varchar>>,
+    }
+}
+
+table! {
+    use diesel::sql_types::Int4;
+    use diesel::sql_types::Timestamp;
+    use crate::models::sale_state::EventMapping;
+    use crate::models::sale_state::SaleStateMapping;
+    sale_state_transitions (id) {
+        id -> Int4,
+        sale_id -> Int4,
+        from_state -> SaleStateMapping,
+        to_state -> SaleStateMapping,
+        event -> EventMapping,
+        user_id -> Int4,
+        created_at -> Timestamp,
     }
 }
 
@@ -53,6 +291,7 @@ table! {
     use diesel::sql_types::Float8;
     use diesel::sql_types::Nullable;
     use diesel::sql_types::Date;
+    use diesel::sql_types::Timestamp;
     use crate::models::sale_state::SaleStateMapping;
     sales (id) {
         id -> Int4,
@@ -61,6 +300,133 @@ table! {
         total -> Float8,
         bill_number -> Nullable<VarChar>,
         state -> SaleStateMapping,
+        location_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+        customer_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    scheduled_prices (id) {
+        id -> Int4,
+        user_id -> Int4,
+        price_product_id -> Int4,
+        amount -> Int4,
+        starts_on -> Date,
+        created_at -> Timestamp,
+        applied_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::Int4;
+    use diesel::sql_types::Float8;
+    use diesel::sql_types::Nullable;
+    use diesel::sql_types::Timestamp;
+    use crate::models::stock_movement::AdjustmentReasonMapping;
+    stock_count_lines (id) {
+        id -> Int4,
+        stock_count_id -> Int4,
+        product_id -> Int4,
+        expected -> Float8,
+        counted -> Nullable<Float8>,
+        reason -> Nullable<AdjustmentReasonMapping>,
+        counted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::Int4;
+    use diesel::sql_types::Nullable;
+    use diesel::sql_types::Timestamp;
+    use crate::models::stock_count::StockCountStateMapping;
+    stock_counts (id) {
+        id -> Int4,
+        user_id -> Int4,
+        location_id -> Nullable<Int4>,
+        state -> StockCountStateMapping,
+        created_at -> Timestamp,
+        confirmed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::Int4;
+    use diesel::sql_types::Float8;
+    use diesel::sql_types::Nullable;
+    use diesel::sql_types::Timestamp;
+    use crate::models::stock_movement::AdjustmentReasonMapping;
+    use crate::models::stock_movement::StockMovementKindMapping;
+    stock_movements (id) {
+        id -> Int4,
+        user_id -> Int4,
+        product_id -> Int4,
+        location_id -> Nullable<Int4>,
+        lot_id -> Nullable<Int4>,
+        quantity -> Float8,
+        kind -> StockMovementKindMapping,
+        sale_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+        transfer_id -> Nullable<Int4>,
+        reason -> Nullable<AdjustmentReasonMapping>,
+        stock_count_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    suppliers (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        lead_time_days -> Int4,
+    }
+}
+
+table! {
+    transfer_products (id) {
+        id -> Int4,
+        transfer_id -> Int4,
+        product_id -> Int4,
+        lot_id -> Nullable<Int4>,
+        quantity -> Float8,
+        received_quantity -> Nullable<Float8>,
+    }
+}
+
+table! {
+    use diesel::sql_types::Int4;
+    use diesel::sql_types::Nullable;
+    use diesel::sql_types::Timestamp;
+    use crate::models::transfer::TransferStateMapping;
+    transfers (id) {
+        id -> Int4,
+        user_id -> Int4,
+        source_location_id -> Int4,
+        destination_location_id -> Int4,
+        state -> TransferStateMapping,
+        created_at -> Timestamp,
+        sent_at -> Nullable<Timestamp>,
+        received_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    units (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+    }
+}
+
+table! {
+    serial_numbers (id) {
+        id -> Int4,
+        product_id -> Int4,
+        user_id -> Int4,
+        serial -> Varchar,
+        sale_product_id -> Nullable<Int4>,
+        received_at -> Timestamp,
+        location_id -> Nullable<Int4>,
     }
 }
 
@@ -71,23 +437,124 @@ table! {
         company -> Varchar,
         password -> Varchar,
         created_at -> Timestamp,
+        failed_login_attempts -> Int4,
+        locked_until -> Nullable<Timestamp>,
+        admin -> Bool,
+        totp_secret -> Nullable<Varchar>,
+        totp_enabled -> Bool,
+        totp_last_step -> Nullable<Int8>,
+        search_config -> Varchar,
     }
 }
 
+joinable!(api_keys -> users (user_id));
+joinable!(attachments -> products (product_id));
+joinable!(attachments -> sales (sale_id));
+joinable!(attachments -> users (user_id));
+joinable!(audit_events -> users (user_id));
+joinable!(backup_codes -> users (user_id));
+joinable!(categories -> users (user_id));
+joinable!(customer_groups -> prices (price_id));
+joinable!(customer_groups -> users (user_id));
+joinable!(customers -> customer_groups (customer_group_id));
+joinable!(customers -> prices (price_id));
+joinable!(customers -> users (user_id));
+joinable!(locations -> users (user_id));
+joinable!(lots -> products (product_id));
+joinable!(lots -> users (user_id));
+joinable!(price_changes -> prices (price_id));
+joinable!(price_changes -> products (product_id));
+joinable!(price_changes -> users (user_id));
+joinable!(price_tiers -> prices_products (price_product_id));
+joinable!(price_tiers -> users (user_id));
 joinable!(prices -> users (user_id));
 joinable!(prices_products -> prices (price_id));
 joinable!(prices_products -> products (product_id));
 joinable!(prices_products -> users (user_id));
+joinable!(product_barcodes -> products (product_id));
+joinable!(product_barcodes -> users (user_id));
+joinable!(product_components -> products (component_id));
+joinable!(product_components -> users (user_id));
+joinable!(product_options -> products (product_id));
+joinable!(product_options -> users (user_id));
+joinable!(product_stocks -> locations (location_id));
+joinable!(product_stocks -> products (product_id));
+joinable!(product_stocks -> users (user_id));
+joinable!(product_units -> products (product_id));
+joinable!(product_units -> units (unit_id));
+joinable!(product_units -> users (user_id));
+joinable!(product_variant_values -> product_options (product_option_id));
+joinable!(product_variant_values -> products (variant_id));
+joinable!(products -> categories (category_id));
+joinable!(products -> suppliers (supplier_id));
+joinable!(products -> units (unit_id));
 joinable!(products -> users (user_id));
+joinable!(sale_products -> lots (lot_id));
 joinable!(sale_products -> products (product_id));
 joinable!(sale_products -> sales (sale_id));
+joinable!(sale_products -> units (unit_id));
+joinable!(sale_state_transitions -> sales (sale_id));
+joinable!(sale_state_transitions -> users (user_id));
+joinable!(sales -> customers (customer_id));
+joinable!(sales -> locations (location_id));
 joinable!(sales -> users (user_id));
+joinable!(scheduled_prices -> prices_products (price_product_id));
+joinable!(scheduled_prices -> users (user_id));
+joinable!(serial_numbers -> locations (location_id));
+joinable!(serial_numbers -> products (product_id));
+joinable!(serial_numbers -> sale_products (sale_product_id));
+joinable!(serial_numbers -> users (user_id));
+joinable!(stock_count_lines -> products (product_id));
+joinable!(stock_count_lines -> stock_counts (stock_count_id));
+joinable!(stock_counts -> locations (location_id));
+joinable!(stock_counts -> users (user_id));
+joinable!(stock_movements -> locations (location_id));
+joinable!(stock_movements -> lots (lot_id));
+joinable!(stock_movements -> products (product_id));
+joinable!(stock_movements -> sales (sale_id));
+joinable!(stock_movements -> stock_counts (stock_count_id));
+joinable!(stock_movements -> transfers (transfer_id));
+joinable!(stock_movements -> users (user_id));
+joinable!(suppliers -> users (user_id));
+joinable!(transfer_products -> lots (lot_id));
+joinable!(transfer_products -> products (product_id));
+joinable!(transfer_products -> transfers (transfer_id));
+joinable!(transfers -> users (user_id));
+joinable!(units -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
+    api_keys,
+    attachments,
+    audit_events,
+    backup_codes,
+    categories,
+    customer_groups,
+    customers,
+    locations,
+    login_attempts,
+    lots,
+    price_changes,
+    price_tiers,
     prices,
     prices_products,
+    product_barcodes,
+    product_components,
+    product_options,
+    product_stocks,
+    product_units,
+    product_variant_values,
     products,
     sale_products,
+    sale_state_transitions,
     sales,
+    scheduled_prices,
+    serial_numbers,
+    stock_count_lines,
+    stock_counts,
+    stock_movements,
+    suppliers,
+    transfer_products,
+    transfers,
+    units,
     users,
 );
@@ -0,0 +1,78 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Where uploaded files are kept, chosen with `STORAGE_BACKEND`.
+pub trait Storage: Send + Sync {
+    /// Saves `bytes` under `key` and returns the URL they are served from.
+    fn put(&self, key: &str, content_type: &str, bytes: &[u8]) -> io::Result<String>;
+
+    /// Reads back what was saved under `key`.
+    fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+
+    /// Removing a key that doesn't exist is not an error.
+    fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// Keeps the files in a directory of the server (`STORAGE_PATH`, `uploads`
+/// by default). Their URLs start with `STORAGE_URL` (`/uploads`), where
+/// `handlers::attachment::download` serves them to their owner.
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    pub root: PathBuf,
+    pub base_url: String,
+}
+
+impl LocalStorage {
+    pub fn from_env() -> LocalStorage {
+        LocalStorage {
+            root: PathBuf::from(env::var("STORAGE_PATH").unwrap_or_else(|_| "uploads".to_string())),
+            base_url: env::var("STORAGE_URL").unwrap_or_else(|_| "/uploads".to_string()),
+        }
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        if key.is_empty() || key.starts_with('/') || key.split('/').any(|part| part == "..") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid storage key {}", key),
+            ));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put(&self, key: &str, _content_type: &str, bytes: &[u8]) -> io::Result<String> {
+        let path = self.path(key)?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, bytes)?;
+        Ok(format!("{}/{}", self.base_url.trim_end_matches('/'), key))
+    }
+
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(key)?)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(key)?) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// The backend configured in `STORAGE_BACKEND`, only `local` for now.
+pub fn from_env() -> io::Result<Box<dyn Storage>> {
+    match env::var("STORAGE_BACKEND") {
+        Err(_) => Ok(Box::new(LocalStorage::from_env())),
+        Ok(ref backend) if backend == "local" => Ok(Box::new(LocalStorage::from_env())),
+        Ok(backend) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unknown storage backend {}", backend),
+        )),
+    }
+}
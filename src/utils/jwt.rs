@@ -10,10 +10,20 @@ struct Claims {
     exp: usize,
 }
 
+// Issued after the password step for users with two factor authentication,
+// it can't be decoded as `Claims` so it is useless as a session token.
+#[derive(Debug, Serialize, Deserialize)]
+struct ChallengeClaims {
+    sub: i32,
+    challenge: bool,
+    exp: usize,
+}
+
 pub struct SlimUser {
     pub id: i32,
     pub email: String,
     pub company: String,
+    pub scopes: Option<Vec<String>>,
 }
 
 impl From<Claims> for SlimUser {
@@ -22,6 +32,7 @@ impl From<Claims> for SlimUser {
             id: claims.sub,
             email: claims.name,
             company: claims.company,
+            scopes: None,
         }
     }
 }
@@ -57,6 +68,30 @@ pub fn decode_token(token: &str) -> Result<SlimUser, HttpResponse> {
     .map_err(|e| HttpResponse::Unauthorized().json(e.to_string()))
 }
 
+pub fn create_challenge_token(id: i32) -> Result<String, HttpResponse> {
+    let claims = ChallengeClaims {
+        sub: id,
+        challenge: true,
+        exp: (Local::now() + Duration::minutes(5)).timestamp() as usize,
+    };
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(get_secret()),
+    )
+    .map_err(|e| HttpResponse::InternalServerError().json(e.to_string()))
+}
+
+pub fn decode_challenge_token(token: &str) -> Result<i32, HttpResponse> {
+    decode::<ChallengeClaims>(
+        token,
+        &DecodingKey::from_secret(get_secret()),
+        &Validation::default(),
+    )
+    .map(|data| data.claims.sub)
+    .map_err(|e| HttpResponse::Unauthorized().json(e.to_string()))
+}
+
 fn get_secret<'a>() -> &'a [u8] {
     dotenv!("JWT_SECRET").as_bytes()
 }
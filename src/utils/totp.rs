@@ -0,0 +1,123 @@
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+type HmacSha1 = Hmac<Sha1>;
+
+const SECRET_ALPHABET: Alphabet = Alphabet::RFC4648 { padding: false };
+const SECRET_BYTES: usize = 20;
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+// Accept codes from the previous and next time step to tolerate clock drift.
+const ALLOWED_DRIFT: i64 = 1;
+
+pub fn generate_secret() -> String {
+    let bytes: [u8; SECRET_BYTES] = rand::random();
+    base32::encode(SECRET_ALPHABET, &bytes)
+}
+
+pub fn otpauth_uri(secret: &str, account: &str, issuer: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+        issuer = encode_component(issuer),
+        account = encode_component(account),
+        secret = secret,
+        digits = DIGITS,
+        period = STEP_SECONDS,
+    )
+}
+
+/// Returns the time step the code belongs to, so callers can refuse to
+/// accept the same code twice.
+pub fn verify(secret: &str, code: &str, timestamp: i64) -> Option<i64> {
+    let key = base32::decode(SECRET_ALPHABET, secret)?;
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let current_step = timestamp / STEP_SECONDS;
+
+    (current_step - ALLOWED_DRIFT..=current_step + ALLOWED_DRIFT)
+        .find(|step| *step >= 0 && hotp(&key, *step as u64) == code)
+}
+
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac = HmacSha1::new_varkey(key).expect("HMAC accepts keys of any size");
+    mac.input(&counter.to_be_bytes());
+    let hash = mac.result().code();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = (u32::from(hash[offset]) & 0x7f) << 24
+        | u32::from(hash[offset + 1]) << 16
+        | u32::from(hash[offset + 2]) << 8
+        | u32::from(hash[offset + 3]);
+
+    binary % 10u32.pow(DIGITS)
+}
+
+fn encode_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "12345678901234567890", the key of the RFC 4226 and RFC 6238 vectors.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn hotp_matches_rfc_4226() {
+        let key = base32::decode(SECRET_ALPHABET, RFC_SECRET).unwrap();
+        let expected = [
+            755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(&key, counter as u64), *code);
+        }
+    }
+
+    #[test]
+    fn verify_matches_rfc_6238_sha1() {
+        // Last six digits of the eight digit SHA-1 codes.
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+        for (timestamp, code) in vectors.iter() {
+            assert_eq!(
+                verify(RFC_SECRET, code, *timestamp),
+                Some(timestamp / STEP_SECONDS)
+            );
+        }
+    }
+
+    #[test]
+    fn verify_tolerates_one_step_of_drift() {
+        assert_eq!(verify(RFC_SECRET, "287082", 59 + STEP_SECONDS), Some(1));
+        assert_eq!(verify(RFC_SECRET, "287082", 59 - STEP_SECONDS), Some(1));
+        assert_eq!(verify(RFC_SECRET, "287082", 59 + 2 * STEP_SECONDS), None);
+    }
+
+    #[test]
+    fn verify_requires_six_ascii_digits() {
+        assert_eq!(verify(RFC_SECRET, " 287082 ", 59), Some(1));
+        assert_eq!(verify(RFC_SECRET, "0287082", 59), None);
+        assert_eq!(verify(RFC_SECRET, "+287082", 59), None);
+        assert_eq!(verify(RFC_SECRET, "81804", 1111111109), None);
+        assert_eq!(verify(RFC_SECRET, "28708a", 59), None);
+    }
+}
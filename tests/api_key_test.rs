@@ -0,0 +1,237 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use serde_json::{json, Value};
+    use std::cell::RefMut;
+    use std::str;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::{send_request, server_test};
+
+    use ::mystore_lib::models::user::{NewUser, User};
+
+    #[actix_rt::test]
+    async fn test() {
+        create_user();
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        let query = r#"
+            {
+                "query": "
+                    mutation CreateSupplier($form: FormSupplier!) {
+                        createSupplier(form: $form) {
+                            name
+                        }
+                    }
+                ",
+                "variables": {
+                    "form": {
+                        "name": "Acme"
+                    }
+                }
+            }"#
+        .replace("\n", "");
+        send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+
+        let created =
+            create_an_api_key(srv.borrow_mut(), csrf_token.clone(), request_cookie.clone()).await;
+        let created = created.get("data").unwrap().get("createApiKey").unwrap();
+        let key = created.get("key").unwrap().as_str().unwrap().to_string();
+        let api_key_id = created
+            .get("apiKey")
+            .unwrap()
+            .get("id")
+            .unwrap()
+            .as_i64()
+            .unwrap();
+        assert_eq!(
+            created.get("apiKey").unwrap().get("scopes").unwrap(),
+            &json!(["read"])
+        );
+
+        // The key reads without a cookie or a csrf token.
+        let (status, response) = send_with_key(
+            srv.borrow_mut(),
+            &key,
+            r#"{ "query": "{ listSupplier { data { name } } }" }"#.to_string(),
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::OK);
+        assert_eq!(
+            response.unwrap(),
+            json!({ "data": { "listSupplier": { "data": [{ "name": "Acme" }] } } })
+        );
+
+        // But it can't write without the write scope.
+        let (status, response) = send_with_key(
+            srv.borrow_mut(),
+            &key,
+            r#"{ "query": "mutation { createSupplier(form: { name: \"Other\" }) { name } }" }"#
+                .to_string(),
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::OK);
+        assert_eq!(error_message(&response.unwrap()), "Missing write scope");
+
+        // Nor manage keys, that needs a logged in user.
+        let (_, response) = send_with_key(
+            srv.borrow_mut(),
+            &key,
+            r#"{ "query": "{ listApiKey { data { name } } }" }"#.to_string(),
+        )
+        .await;
+        assert_eq!(
+            error_message(&response.unwrap()),
+            "API keys can't perform this operation"
+        );
+
+        let query = format!(
+            r#"{{ "query": "mutation {{ revokeApiKey(apiKeyId: {}) }}" }}"#,
+            api_key_id
+        );
+        let revoked = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(revoked, json!({ "data": { "revokeApiKey": true } }));
+
+        let (status, _) = send_with_key(
+            srv.borrow_mut(),
+            &key,
+            r#"{ "query": "{ listSupplier { data { name } } }" }"#.to_string(),
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn create_user() -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: "jhon@doe.com".to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    async fn create_an_api_key(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+    ) -> Value {
+        let query = r#"
+            {
+                "query": "
+                    mutation CreateApiKey($form: FormApiKey!) {
+                        createApiKey(form: $form) {
+                            apiKey {
+                                id
+                                scopes
+                            }
+                            key
+                        }
+                    }
+                ",
+                "variables": {
+                    "form": {
+                        "name": "E-commerce sync",
+                        "scopes": ["read"]
+                    }
+                }
+            }"#
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    /// Sends the query with the key as a bearer token, returns the status
+    /// and the body when the request went through.
+    async fn send_with_key(
+        srv: RefMut<'_, TestServer>,
+        key: &str,
+        query: String,
+    ) -> (http::StatusCode, Option<Value>) {
+        let mut response = srv
+            .post("/graphql")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {}", key))
+            .timeout(std_duration::from_secs(600))
+            .send_body(query)
+            .await
+            .unwrap();
+
+        if !response.status().is_success() {
+            return (response.status(), None);
+        }
+        let bytes = response.body().await.unwrap();
+        let body = str::from_utf8(&bytes).unwrap();
+        (response.status(), Some(serde_json::from_str(body).unwrap()))
+    }
+
+    fn error_message(response: &Value) -> &str {
+        response.get("errors").unwrap()[0]
+            .get("message")
+            .unwrap()
+            .as_str()
+            .unwrap()
+    }
+}
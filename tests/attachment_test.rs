@@ -0,0 +1,194 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use std::cell::RefMut;
+    use std::sync::Arc;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::server_test;
+
+    use ::mystore_lib::models::attachment::Attachment;
+    use ::mystore_lib::models::price::FormPriceProductsToUpdate;
+    use ::mystore_lib::models::product::{FormProduct, Product};
+    use ::mystore_lib::models::user::{NewUser, User};
+    use ::mystore_lib::models::Context;
+    use ::mystore_lib::storage::LocalStorage;
+
+    const MANUAL: &[u8] = b"%PDF-1.4\n% a product manual\n%%EOF\n";
+
+    #[actix_rt::test]
+    async fn test() {
+        delete_users();
+        let user = create_user("jhon@doe.com");
+        create_user("jane@doe.com");
+
+        let srv = server_test();
+
+        let attachment = attach_a_manual(user.id);
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut(), "jhon@doe.com").await;
+        let (status, content_type, body) = download(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            &attachment.url,
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::OK);
+        assert_eq!(content_type.unwrap(), "application/pdf");
+        assert_eq!(body, MANUAL.to_vec());
+
+        // Other accounts can't tell the file exists.
+        let (csrf_token, request_cookie) = login(srv.borrow_mut(), "jane@doe.com").await;
+        let (status, _, _) = download(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            &attachment.url,
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::NOT_FOUND);
+
+        // Nor can anybody without a session.
+        let response = srv.borrow_mut().get(&attachment.url).send().await.unwrap();
+        assert!(!response.status().is_success());
+
+        Attachment::destroy(&context(user.id), attachment.id).unwrap();
+    }
+
+    async fn login<'a>(srv: RefMut<'a, TestServer>, email: &str) -> (HeaderValue, Cookie<'a>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(format!(r#"{{"email":"{}","password":"12345678"}}"#, email))
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn delete_users() {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+    }
+
+    fn create_user(email: &str) -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: email.to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    fn context(user_id: i32) -> Context {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+        Context {
+            user_id,
+            conn: Arc::new(pg_pool),
+            scopes: None,
+        }
+    }
+
+    fn attach_a_manual(user_id: i32) -> Attachment {
+        let context = context(user_id);
+        let shoe = Product::create(
+            &context,
+            FormProduct {
+                id: None,
+                name: Some("Shoe".to_string()),
+                stock: Some(10.0),
+                cost: Some(1892),
+                description: None,
+                user_id: Some(user_id),
+                category_id: None,
+                sku: None,
+                unit_id: None,
+                track_lots: None,
+                serialized: None,
+                min_stock: None,
+                reorder_point: None,
+                reorder_quantity: None,
+                supplier_id: None,
+            },
+            FormPriceProductsToUpdate { data: vec![] },
+        )
+        .unwrap()
+        .product;
+
+        Attachment::create(
+            &context,
+            &LocalStorage::from_env(),
+            Some(shoe.id),
+            None,
+            "manual.pdf".to_string(),
+            MANUAL,
+        )
+        .unwrap()
+    }
+
+    /// Status, content type and body of the response.
+    async fn download(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        url: &str,
+    ) -> (http::StatusCode, Option<String>, Vec<u8>) {
+        let mut response = srv
+            .get(url)
+            .header("x-csrf-token", csrf_token.to_str().unwrap())
+            .cookie(request_cookie)
+            .timeout(std_duration::from_secs(600))
+            .send()
+            .await
+            .unwrap();
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_string());
+        let bytes = response.body().await.unwrap();
+        (response.status(), content_type, bytes.to_vec())
+    }
+}
@@ -0,0 +1,509 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use serde_json::{json, Value};
+    use std::cell::{RefCell, RefMut};
+    use std::sync::Arc;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::{send_request, server_test};
+
+    use ::mystore_lib::models::price::FormPriceProductsToUpdate;
+    use ::mystore_lib::models::product::{FormProduct, Product};
+    use ::mystore_lib::models::user::{NewUser, User};
+    use ::mystore_lib::models::Context;
+
+    #[actix_rt::test]
+    async fn test() {
+        let user = create_user();
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        let shoes = create_a_category(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            "Shoes",
+            None,
+        )
+        .await;
+        let running = create_a_category(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            "Running",
+            Some(shoes),
+        )
+        .await;
+        let trail = create_a_category(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            "Trail",
+            Some(running),
+        )
+        .await;
+
+        // A category can't end up inside its own subtree.
+        let response = update_a_category(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            shoes,
+            format!(r#""parentId": {}"#, trail),
+        )
+        .await;
+        assert_eq!(
+            error_message(&response),
+            "A category can't be moved inside itself"
+        );
+
+        let runner = create_product(user.id, "Runner", trail);
+        let sandal = create_product(user.id, "Sandal", shoes);
+        sell(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            runner,
+            3.0,
+            20,
+        )
+        .await;
+        sell(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            sandal,
+            1.0,
+            15,
+        )
+        .await;
+
+        // Sales of the subcategories roll up to the top level.
+        let report = category_sales(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            None,
+        )
+        .await;
+        assert_eq!(
+            report,
+            json!({
+                "data": {
+                    "categorySales": [
+                        { "name": "Shoes", "amount": 4.0, "total": 75.0 }
+                    ]
+                }
+            })
+        );
+
+        let report = category_sales(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            Some(shoes),
+        )
+        .await;
+        assert_eq!(
+            report,
+            json!({
+                "data": {
+                    "categorySales": [
+                        { "name": "Running", "amount": 3.0, "total": 60.0 }
+                    ]
+                }
+            })
+        );
+
+        // Categories with subcategories aren't deleted.
+        let response = destroy_a_category(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            running,
+        )
+        .await;
+        assert_eq!(
+            error_message(&response),
+            "Running has subcategories, move or delete them first"
+        );
+
+        let response = update_a_category(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            trail,
+            r#""name": "Trail""#.to_string(),
+        )
+        .await;
+        assert_eq!(
+            response.get("data").unwrap().get("updateCategory").unwrap(),
+            &json!({ "name": "Trail", "parentId": running })
+        );
+
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation UpdateCategory($form: FormCategory!) {{
+                        updateCategory(form: $form, toTopLevel: true) {{
+                            name
+                            parentId
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "id": {}
+                    }}
+                }}
+            }}"#,
+            trail
+        )
+        .replace("\n", "");
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(
+            response.get("data").unwrap().get("updateCategory").unwrap(),
+            &json!({ "name": "Trail", "parentId": null })
+        );
+
+        let response = destroy_a_category(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            running,
+        )
+        .await;
+        assert_eq!(response, json!({ "data": { "destroyCategory": true } }));
+
+        let report = category_sales(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            None,
+        )
+        .await;
+        assert_eq!(
+            report,
+            json!({
+                "data": {
+                    "categorySales": [
+                        { "name": "Trail", "amount": 3.0, "total": 60.0 },
+                        { "name": "Shoes", "amount": 1.0, "total": 15.0 }
+                    ]
+                }
+            })
+        );
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn create_user() -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: "jhon@doe.com".to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    fn create_product(user_id: i32, name: &str, category_id: i32) -> i32 {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+        let context = Context {
+            user_id,
+            conn: Arc::new(pg_pool),
+            scopes: None,
+        };
+        Product::create(
+            &context,
+            FormProduct {
+                id: None,
+                name: Some(name.to_string()),
+                stock: Some(10.0),
+                cost: Some(1000),
+                description: None,
+                user_id: Some(user_id),
+                category_id: Some(category_id),
+                sku: None,
+                unit_id: None,
+                track_lots: None,
+                serialized: None,
+                min_stock: None,
+                reorder_point: None,
+                reorder_quantity: None,
+                supplier_id: None,
+            },
+            FormPriceProductsToUpdate { data: vec![] },
+        )
+        .unwrap()
+        .product
+        .id
+    }
+
+    async fn create_a_category(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        name: &str,
+        parent_id: Option<i32>,
+    ) -> i32 {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateCategory($form: FormCategory!) {{
+                        createCategory(form: $form) {{
+                            id
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "name": "{}",
+                        "parentId": {}
+                    }}
+                }}
+            }}"#,
+            name,
+            parent_id.map_or("null".to_string(), |id| id.to_string())
+        )
+        .replace("\n", "");
+
+        let response = send_request(srv, csrf_token, request_cookie, query).await;
+        serde_json::from_value(
+            response
+                .get("data")
+                .unwrap()
+                .get("createCategory")
+                .unwrap()
+                .get("id")
+                .unwrap()
+                .clone(),
+        )
+        .unwrap()
+    }
+
+    async fn update_a_category(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        id: i32,
+        fields: String,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation UpdateCategory($form: FormCategory!) {{
+                        updateCategory(form: $form) {{
+                            name
+                            parentId
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "id": {},
+                        {}
+                    }}
+                }}
+            }}"#,
+            id, fields
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    async fn destroy_a_category(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        id: i32,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation DestroyCategory($categoryId: Int!) {{
+                        destroyCategory(categoryId: $categoryId)
+                    }}
+                ",
+                "variables": {{
+                    "categoryId": {}
+                }}
+            }}"#,
+            id
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    async fn category_sales(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        parent_id: Option<i32>,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    query CategorySales($parentId: Int) {{
+                        categorySales(parentId: $parentId) {{
+                            name
+                            amount
+                            total
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "parentId": {}
+                }}
+            }}"#,
+            parent_id.map_or("null".to_string(), |id| id.to_string())
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    /// Creates a sale of a single product and approves it.
+    async fn sell(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        amount: f64,
+        price: i32,
+    ) {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateSale($form: FormSale!, $formSaleProducts: FormSaleProducts!) {{
+                        createSale(form: $form, formSaleProducts: $formSaleProducts) {{
+                            sale {{
+                                id
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "saleDate": "2019-11-12",
+                        "total": {total}
+                    }},
+                    "formSaleProducts": {{
+                        "data":
+                            [{{
+                                "product": {{ }},
+                                "saleProduct": {{
+                                    "amount": {amount},
+                                    "discount": 0,
+                                    "price": {price},
+                                    "productId": {product_id},
+                                    "tax": 0,
+                                    "total": {total}
+                                }}
+                            }}]
+                    }}
+                }}
+            }}"#,
+            amount = amount,
+            price = price,
+            product_id = product_id,
+            total = amount * f64::from(price)
+        )
+        .replace("\n", "");
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        let sale_id = response
+            .get("data")
+            .unwrap()
+            .get("createSale")
+            .unwrap()
+            .get("sale")
+            .unwrap()
+            .get("id")
+            .unwrap()
+            .clone();
+
+        let query = format!(
+            r#"{{ "query": "mutation {{ approveSale(saleId: {}) }}" }}"#,
+            sale_id
+        );
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        assert_eq!(response, json!({ "data": { "approveSale": true } }));
+    }
+
+    fn error_message(response: &Value) -> &str {
+        response.get("errors").unwrap()[0]
+            .get("message")
+            .unwrap()
+            .as_str()
+            .unwrap()
+    }
+}
@@ -11,7 +11,7 @@ use actix_identity::{CookieIdentityPolicy, IdentityService};
 use actix_service::map_config;
 use actix_web::dev::AppConfig;
 use actix_web::http::header;
-use actix_web::App;
+use actix_web::{web, App};
 use chrono::Duration;
 use csrf_token::CsrfTokenGenerator;
 use serde_json::Value;
@@ -60,7 +60,12 @@ pub fn server_test() -> RefCell<TestServer> {
                     .service(graphql)
                     .service(graphiql)
                     .service(::mystore_lib::handlers::authentication::login)
-                    .service(::mystore_lib::handlers::authentication::logout),
+                    .service(::mystore_lib::handlers::authentication::logout)
+                    .service(::mystore_lib::handlers::authentication::unlock)
+                    .route(
+                        "/uploads/{key:.*}",
+                        web::get().to(::mystore_lib::handlers::attachment::download),
+                    ),
                 |_| AppConfig::default(),
             ))
             .tcp()
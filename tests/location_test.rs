@@ -0,0 +1,357 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use serde_json::{json, Value};
+    use std::cell::{RefCell, RefMut};
+    use std::sync::Arc;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::{send_request, server_test};
+
+    use ::mystore_lib::models::price::FormPriceProductsToUpdate;
+    use ::mystore_lib::models::product::{FormProduct, Product};
+    use ::mystore_lib::models::user::{NewUser, User};
+    use ::mystore_lib::models::Context;
+
+    #[actix_rt::test]
+    async fn test() {
+        let user = create_user();
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        let north =
+            create_a_location(&srv, csrf_token.clone(), request_cookie.clone(), "North").await;
+        let south =
+            create_a_location(&srv, csrf_token.clone(), request_cookie.clone(), "South").await;
+
+        let shoe = create_product(user.id, "Shoe", 0.0);
+        receive_stock(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            shoe,
+            north,
+            8.0,
+        )
+        .await;
+        receive_stock(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            shoe,
+            south,
+            5.0,
+        )
+        .await;
+
+        // The sale only takes stock out of the shop it was made in.
+        let sale_id = create_a_sale(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            north,
+            shoe,
+            3.0,
+        )
+        .await;
+        let query = format!(
+            r#"{{ "query": "mutation {{ approveSale(saleId: {}) }}" }}"#,
+            sale_id
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(response, json!({ "data": { "approveSale": true } }));
+
+        let query = format!(
+            r#"{{ "query": "{{ showProduct(productId: {}) {{ product {{ stock }} stocks {{ locationId stock }} }} }}" }}"#,
+            shoe
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        let show_product = response.get("data").unwrap().get("showProduct").unwrap();
+        assert_eq!(
+            show_product.get("product").unwrap(),
+            &json!({ "stock": 10.0 })
+        );
+        let mut stocks = show_product
+            .get("stocks")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .clone();
+        stocks.sort_by_key(|stock| stock.get("locationId").unwrap().as_i64());
+        assert_eq!(
+            stocks,
+            vec![
+                json!({ "locationId": north, "stock": 5.0 }),
+                json!({ "locationId": south, "stock": 5.0 })
+            ]
+        );
+
+        let query = format!(
+            r#"{{ "query": "{{ locationStock(locationId: {}) {{ productId stock }} }}" }}"#,
+            north
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({ "data": { "locationStock": [{ "productId": shoe, "stock": 5.0 }] } })
+        );
+
+        // A shop still holding stock can't be removed.
+        let query = format!(
+            r#"{{ "query": "mutation {{ destroyLocation(locationId: {}) }}" }}"#,
+            south
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert!(response.get("errors").is_some());
+        assert_eq!(response.get("data").unwrap(), &json!(null));
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn create_user() -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: "jhon@doe.com".to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    fn create_product(user_id: i32, name: &str, stock: f64) -> i32 {
+        Product::create(
+            &context(user_id),
+            FormProduct {
+                id: None,
+                name: Some(name.to_string()),
+                stock: Some(stock),
+                cost: Some(1000),
+                description: None,
+                user_id: Some(user_id),
+                category_id: None,
+                sku: None,
+                unit_id: None,
+                track_lots: None,
+                serialized: None,
+                min_stock: None,
+                reorder_point: None,
+                reorder_quantity: None,
+                supplier_id: None,
+            },
+            FormPriceProductsToUpdate { data: vec![] },
+        )
+        .unwrap()
+        .product
+        .id
+    }
+
+    fn context(user_id: i32) -> Context {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+        Context {
+            user_id,
+            conn: Arc::new(pg_pool),
+            scopes: None,
+        }
+    }
+
+    async fn create_a_location(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        name: &str,
+    ) -> i32 {
+        let query = format!(
+            r#"{{ "query": "mutation {{ createLocation(form: {{ name: \"{}\" }}) {{ id }} }}" }}"#,
+            name
+        );
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        serde_json::from_value(
+            response
+                .get("data")
+                .unwrap()
+                .get("createLocation")
+                .unwrap()
+                .get("id")
+                .unwrap()
+                .clone(),
+        )
+        .unwrap()
+    }
+
+    async fn receive_stock(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        location_id: i32,
+        quantity: f64,
+    ) {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation ReceiveStock($form: FormStockReceipt!) {{
+                        receiveStock(form: $form) {{
+                            locationId
+                            quantity
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "productId": {},
+                        "locationId": {},
+                        "quantity": {}
+                    }}
+                }}
+            }}"#,
+            product_id, location_id, quantity
+        )
+        .replace("\n", "");
+
+        let response = send_request(srv, csrf_token, request_cookie, query).await;
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "receiveStock": { "locationId": location_id, "quantity": quantity }
+                }
+            })
+        );
+    }
+
+    /// Creates a sale of the product in the location, returns its id.
+    async fn create_a_sale(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        location_id: i32,
+        product_id: i32,
+        amount: f64,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateSale($form: FormSale!, $formSaleProducts: FormSaleProducts!) {{
+                        createSale(form: $form, formSaleProducts: $formSaleProducts) {{
+                            sale {{
+                                id
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "saleDate": "2019-11-12",
+                        "locationId": {location_id},
+                        "total": {total}
+                    }},
+                    "formSaleProducts": {{
+                        "data":
+                            [{{
+                                "product": {{ }},
+                                "saleProduct": {{
+                                    "amount": {amount},
+                                    "discount": 0,
+                                    "price": 10,
+                                    "productId": {product_id},
+                                    "tax": 0,
+                                    "total": {total}
+                                }}
+                            }}]
+                    }}
+                }}
+            }}"#,
+            location_id = location_id,
+            amount = amount,
+            product_id = product_id,
+            total = amount * 10.0
+        )
+        .replace("\n", "");
+
+        let response = send_request(srv, csrf_token, request_cookie, query).await;
+        response
+            .get("data")
+            .unwrap()
+            .get("createSale")
+            .unwrap()
+            .get("sale")
+            .unwrap()
+            .get("id")
+            .unwrap()
+            .clone()
+    }
+}
@@ -0,0 +1,400 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use chrono::NaiveDate;
+    use http::header::HeaderValue;
+
+    use serde_json::{json, Value};
+    use std::cell::{RefCell, RefMut};
+    use std::sync::Arc;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::{send_request, server_test};
+
+    use ::mystore_lib::models::price::FormPriceProductsToUpdate;
+    use ::mystore_lib::models::product::{FormProduct, Product};
+    use ::mystore_lib::models::user::{NewUser, User};
+    use ::mystore_lib::models::Context;
+
+    #[actix_rt::test]
+    async fn test() {
+        let user = create_user();
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        let aspirin = create_product(user.id, "Aspirin");
+        let today = Local::now().naive_local().date();
+
+        let late = create_a_lot(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            aspirin,
+            "A-LATE",
+            today + Duration::days(60),
+        )
+        .await;
+        let soon = create_a_lot(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            aspirin,
+            "A-SOON",
+            today + Duration::days(10),
+        )
+        .await;
+        let expired = create_a_lot(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            aspirin,
+            "A-EXPIRED",
+            today - Duration::days(1),
+        )
+        .await;
+        for lot_id in &[late, soon, expired] {
+            receive_into_lot(
+                srv.borrow_mut(),
+                csrf_token.clone(),
+                request_cookie.clone(),
+                aspirin,
+                *lot_id,
+            )
+            .await;
+        }
+
+        // The expired lot is skipped, the one expiring next is suggested.
+        let sale_id = create_a_sale(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            aspirin,
+            3.0,
+        )
+        .await
+        .get("data")
+        .unwrap()
+        .get("createSale")
+        .unwrap()
+        .get("sale")
+        .unwrap()
+        .get("id")
+        .unwrap()
+        .clone();
+        let query = format!(
+            r#"{{ "query": "mutation {{ approveSale(saleId: {}) }}" }}"#,
+            sale_id
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(response, json!({ "data": { "approveSale": true } }));
+
+        let query = format!(
+            r#"{{ "query": "{{ lotSales(lotId: {}) {{ id }} }}" }}"#,
+            soon
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({ "data": { "lotSales": [{ "id": sale_id }] } })
+        );
+
+        let query = format!(
+            r#"{{ "query": "{{ listLot(productId: {}) {{ data {{ lotNumber stock }} }} }}" }}"#,
+            aspirin
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "listLot": {
+                        "data": [
+                            { "lotNumber": "A-EXPIRED", "stock": 5.0 },
+                            { "lotNumber": "A-SOON", "stock": 2.0 },
+                            { "lotNumber": "A-LATE", "stock": 5.0 }
+                        ]
+                    }
+                }
+            })
+        );
+
+        let query =
+            r#"{ "query": "{ expiringLots(days: 30) { lot { lotNumber } } }" }"#.to_string();
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "expiringLots": [
+                        { "lot": { "lotNumber": "A-EXPIRED" } },
+                        { "lot": { "lotNumber": "A-SOON" } }
+                    ]
+                }
+            })
+        );
+
+        // No unexpired lot holds six alone.
+        let response = create_a_sale(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            aspirin,
+            6.0,
+        )
+        .await;
+        assert_eq!(
+            response.get("errors").unwrap()[0].get("message").unwrap(),
+            &json!(format!(
+                "No lot of product {} has enough stock, pick one",
+                aspirin
+            ))
+        );
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn create_user() -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: "jhon@doe.com".to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    fn create_product(user_id: i32, name: &str) -> i32 {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+        let context = Context {
+            user_id,
+            conn: Arc::new(pg_pool),
+            scopes: None,
+        };
+        Product::create(
+            &context,
+            FormProduct {
+                id: None,
+                name: Some(name.to_string()),
+                stock: Some(0.0),
+                cost: Some(300),
+                description: None,
+                user_id: Some(user_id),
+                category_id: None,
+                sku: None,
+                unit_id: None,
+                track_lots: Some(true),
+                serialized: None,
+                min_stock: None,
+                reorder_point: None,
+                reorder_quantity: None,
+                supplier_id: None,
+            },
+            FormPriceProductsToUpdate { data: vec![] },
+        )
+        .unwrap()
+        .product
+        .id
+    }
+
+    async fn create_a_lot(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        lot_number: &str,
+        expires_on: NaiveDate,
+    ) -> i32 {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateLot($form: FormLot!) {{
+                        createLot(form: $form) {{
+                            id
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "productId": {},
+                        "lotNumber": "{}",
+                        "expiresOn": "{}"
+                    }}
+                }}
+            }}"#,
+            product_id, lot_number, expires_on
+        )
+        .replace("\n", "");
+
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        serde_json::from_value(
+            response
+                .get("data")
+                .unwrap()
+                .get("createLot")
+                .unwrap()
+                .get("id")
+                .unwrap()
+                .clone(),
+        )
+        .unwrap()
+    }
+
+    /// Receives five units of the product into the lot.
+    async fn receive_into_lot(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        lot_id: i32,
+    ) {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation ReceiveStock($form: FormStockReceipt!) {{
+                        receiveStock(form: $form) {{
+                            lotId
+                            quantity
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "productId": {},
+                        "lotId": {},
+                        "quantity": 5.0
+                    }}
+                }}
+            }}"#,
+            product_id, lot_id
+        )
+        .replace("\n", "");
+
+        let response = send_request(srv, csrf_token, request_cookie, query).await;
+        assert_eq!(
+            response,
+            json!({ "data": { "receiveStock": { "lotId": lot_id, "quantity": 5.0 } } })
+        );
+    }
+
+    /// Creates a sale of the product leaving the lot to the suggestion.
+    async fn create_a_sale(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        amount: f64,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateSale($form: FormSale!, $formSaleProducts: FormSaleProducts!) {{
+                        createSale(form: $form, formSaleProducts: $formSaleProducts) {{
+                            sale {{
+                                id
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "saleDate": "2019-11-12",
+                        "total": {total}
+                    }},
+                    "formSaleProducts": {{
+                        "data":
+                            [{{
+                                "product": {{ }},
+                                "saleProduct": {{
+                                    "amount": {amount},
+                                    "discount": 0,
+                                    "price": 10,
+                                    "productId": {product_id},
+                                    "tax": 0,
+                                    "total": {total}
+                                }}
+                            }}]
+                    }}
+                }}
+            }}"#,
+            amount = amount,
+            product_id = product_id,
+            total = amount * 10.0
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+}
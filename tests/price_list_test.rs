@@ -0,0 +1,369 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use serde_json::{json, Value};
+    use std::cell::{RefCell, RefMut};
+    use std::sync::Arc;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::{send_request, server_test};
+
+    use ::mystore_lib::models::price::{
+        FormPriceProduct, FormPriceProductsToUpdate, PriceProductToUpdate,
+    };
+    use ::mystore_lib::models::product::{FormProduct, Product};
+    use ::mystore_lib::models::user::{NewUser, User};
+    use ::mystore_lib::models::Context;
+
+    #[actix_rt::test]
+    async fn test() {
+        let user = create_user();
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        let retail = create_a_price(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            "Retail",
+            true,
+        )
+        .await;
+        let vip = create_a_price(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            "VIP",
+            false,
+        )
+        .await;
+        let wholesale = create_a_price(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            "Wholesale",
+            false,
+        )
+        .await;
+
+        let shoe = create_product(
+            user.id,
+            "Shoe",
+            vec![(retail, 100), (vip, 80), (wholesale, 60)],
+        );
+        let sock = create_product(user.id, "Sock", vec![(retail, 20)]);
+        let hat = create_product(user.id, "Hat", vec![]);
+
+        let query = format!(
+            r#"{{ "query": "mutation {{ createCustomerGroup(form: {{ name: \"Wholesalers\", priceId: {} }}) {{ id }} }}" }}"#,
+            wholesale
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        let wholesalers = response
+            .get("data")
+            .unwrap()
+            .get("createCustomerGroup")
+            .unwrap()
+            .get("id")
+            .unwrap()
+            .clone();
+
+        let ann = create_a_customer(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            format!(
+                r#"name: \"Ann\", priceId: {}, customerGroupId: {}"#,
+                vip, wholesalers
+            ),
+        )
+        .await;
+        let bob = create_a_customer(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            format!(r#"name: \"Bob\", customerGroupId: {}"#, wholesalers),
+        )
+        .await;
+
+        // The customer's own list goes before the one of its group.
+        let response = sell_without_price(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            ann,
+            shoe,
+        )
+        .await;
+        assert_eq!(sale_price(&response), 80);
+
+        let response = sell_without_price(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            bob,
+            shoe,
+        )
+        .await;
+        assert_eq!(sale_price(&response), 60);
+
+        // Neither list prices socks, the default one does.
+        let response = sell_without_price(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            ann,
+            sock,
+        )
+        .await;
+        assert_eq!(sale_price(&response), 20);
+
+        let response = sell_without_price(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            ann,
+            hat,
+        )
+        .await;
+        assert_eq!(
+            response.get("errors").unwrap()[0].get("message").unwrap(),
+            &json!(format!(
+                "Product {} has no price on {}",
+                hat,
+                Local::now().naive_local().date()
+            ))
+        );
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn create_user() -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: "jhon@doe.com".to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    /// Creates the product with an amount in each of the price lists.
+    fn create_product(user_id: i32, name: &str, amounts: Vec<(i32, i32)>) -> i32 {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+        let context = Context {
+            user_id,
+            conn: Arc::new(pg_pool),
+            scopes: None,
+        };
+        Product::create(
+            &context,
+            FormProduct {
+                id: None,
+                name: Some(name.to_string()),
+                stock: Some(10.0),
+                cost: Some(10),
+                description: None,
+                user_id: Some(user_id),
+                category_id: None,
+                sku: None,
+                unit_id: None,
+                track_lots: None,
+                serialized: None,
+                min_stock: None,
+                reorder_point: None,
+                reorder_quantity: None,
+                supplier_id: None,
+            },
+            FormPriceProductsToUpdate {
+                data: amounts
+                    .into_iter()
+                    .map(|(price_id, amount)| PriceProductToUpdate {
+                        price_product: FormPriceProduct {
+                            id: None,
+                            price_id,
+                            product_id: None,
+                            user_id: Some(user_id),
+                            amount: Some(amount),
+                        },
+                        to_delete: false,
+                    })
+                    .collect(),
+            },
+        )
+        .unwrap()
+        .product
+        .id
+    }
+
+    async fn create_a_price(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        name: &str,
+        is_default: bool,
+    ) -> i32 {
+        let query = format!(
+            r#"{{ "query": "mutation {{ createPrice(form: {{ name: \"{}\", isDefault: {} }}) {{ id }} }}" }}"#,
+            name, is_default
+        );
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        serde_json::from_value(
+            response
+                .get("data")
+                .unwrap()
+                .get("createPrice")
+                .unwrap()
+                .get("id")
+                .unwrap()
+                .clone(),
+        )
+        .unwrap()
+    }
+
+    async fn create_a_customer(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        fields: String,
+    ) -> i32 {
+        let query = format!(
+            r#"{{ "query": "mutation {{ createCustomer(form: {{ {} }}) {{ id }} }}" }}"#,
+            fields
+        );
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        serde_json::from_value(
+            response
+                .get("data")
+                .unwrap()
+                .get("createCustomer")
+                .unwrap()
+                .get("id")
+                .unwrap()
+                .clone(),
+        )
+        .unwrap()
+    }
+
+    /// Creates a sale of the product for the customer today, leaving the
+    /// price of the line out.
+    async fn sell_without_price(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        customer_id: i32,
+        product_id: i32,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateSale($form: FormSale!, $formSaleProducts: FormSaleProducts!) {{
+                        createSale(form: $form, formSaleProducts: $formSaleProducts) {{
+                            saleProducts {{
+                                saleProduct {{
+                                    price
+                                }}
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "saleDate": "{}",
+                        "customerId": {},
+                        "total": 0.0
+                    }},
+                    "formSaleProducts": {{
+                        "data": [{{
+                            "product": {{ }},
+                            "saleProduct": {{
+                                "productId": {},
+                                "amount": 1.0,
+                                "discount": 0,
+                                "tax": 0,
+                                "total": 0.0
+                            }}
+                        }}]
+                    }}
+                }}
+            }}"#,
+            Local::now().naive_local().date(),
+            customer_id,
+            product_id
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    fn sale_price(response: &Value) -> i64 {
+        response
+            .get("data")
+            .unwrap()
+            .get("createSale")
+            .unwrap()
+            .get("saleProducts")
+            .unwrap()[0]
+            .get("saleProduct")
+            .unwrap()
+            .get("price")
+            .unwrap()
+            .as_i64()
+            .unwrap()
+    }
+}
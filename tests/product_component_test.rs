@@ -0,0 +1,336 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use serde_json::{json, Value};
+    use std::cell::{RefCell, RefMut};
+    use std::sync::Arc;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::{send_request, server_test};
+
+    use ::mystore_lib::models::price::FormPriceProductsToUpdate;
+    use ::mystore_lib::models::product::{FormProduct, Product};
+    use ::mystore_lib::models::user::{NewUser, User};
+    use ::mystore_lib::models::Context;
+
+    #[actix_rt::test]
+    async fn test() {
+        let user = create_user();
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        let shoe = create_product(user.id, "Shoe", 10.0);
+        let sock = create_product(user.id, "Sock", 20.0);
+        let gift_box = create_product(user.id, "Gift box", 0.0);
+
+        add_a_component(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            gift_box,
+            shoe,
+            1.0,
+        )
+        .await;
+        add_a_component(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            gift_box,
+            sock,
+            2.0,
+        )
+        .await;
+
+        // The kit can't end up inside one of its components.
+        let response = add_a_component(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            shoe,
+            gift_box,
+            1.0,
+        )
+        .await;
+        assert_eq!(
+            response.get("errors").unwrap()[0].get("message").unwrap(),
+            "A kit can't contain itself"
+        );
+
+        assert_eq!(
+            available_stock(
+                srv.borrow_mut(),
+                csrf_token.clone(),
+                request_cookie.clone(),
+                gift_box
+            )
+            .await,
+            10.0
+        );
+
+        // Selling kits takes their components out of the stock.
+        sell(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            gift_box,
+            3.0,
+        )
+        .await;
+
+        assert_eq!(
+            available_stock(
+                srv.borrow_mut(),
+                csrf_token.clone(),
+                request_cookie.clone(),
+                shoe
+            )
+            .await,
+            7.0
+        );
+        assert_eq!(
+            available_stock(
+                srv.borrow_mut(),
+                csrf_token.clone(),
+                request_cookie.clone(),
+                sock
+            )
+            .await,
+            14.0
+        );
+        assert_eq!(
+            available_stock(
+                srv.borrow_mut(),
+                csrf_token.clone(),
+                request_cookie.clone(),
+                gift_box
+            )
+            .await,
+            7.0
+        );
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn create_user() -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: "jhon@doe.com".to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    fn context(user_id: i32) -> Context {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+        Context {
+            user_id,
+            conn: Arc::new(pg_pool),
+            scopes: None,
+        }
+    }
+
+    fn create_product(user_id: i32, name: &str, stock: f64) -> i32 {
+        Product::create(
+            &context(user_id),
+            FormProduct {
+                id: None,
+                name: Some(name.to_string()),
+                stock: Some(stock),
+                cost: Some(1000),
+                description: None,
+                user_id: Some(user_id),
+                category_id: None,
+                sku: None,
+                unit_id: None,
+                track_lots: None,
+                serialized: None,
+                min_stock: None,
+                reorder_point: None,
+                reorder_quantity: None,
+                supplier_id: None,
+            },
+            FormPriceProductsToUpdate { data: vec![] },
+        )
+        .unwrap()
+        .product
+        .id
+    }
+
+    /// Creates a sale of a single product and approves it.
+    async fn sell(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        amount: f64,
+    ) {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateSale($form: FormSale!, $formSaleProducts: FormSaleProducts!) {{
+                        createSale(form: $form, formSaleProducts: $formSaleProducts) {{
+                            sale {{
+                                id
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "saleDate": "2019-11-12",
+                        "total": {total}
+                    }},
+                    "formSaleProducts": {{
+                        "data":
+                            [{{
+                                "product": {{ }},
+                                "saleProduct": {{
+                                    "amount": {amount},
+                                    "discount": 0,
+                                    "price": 10,
+                                    "productId": {product_id},
+                                    "tax": 0,
+                                    "total": {total}
+                                }}
+                            }}]
+                    }}
+                }}
+            }}"#,
+            amount = amount,
+            product_id = product_id,
+            total = amount * 10.0
+        )
+        .replace("\n", "");
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        let sale_id = response
+            .get("data")
+            .unwrap()
+            .get("createSale")
+            .unwrap()
+            .get("sale")
+            .unwrap()
+            .get("id")
+            .unwrap()
+            .clone();
+
+        let query = format!(
+            r#"{{ "query": "mutation {{ approveSale(saleId: {}) }}" }}"#,
+            sale_id
+        );
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        assert_eq!(response, json!({ "data": { "approveSale": true } }));
+    }
+
+    async fn add_a_component(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        kit_id: i32,
+        component_id: i32,
+        quantity: f64,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateProductComponent($form: FormProductComponent!) {{
+                        createProductComponent(form: $form) {{
+                            quantity
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "kitId": {},
+                        "componentId": {},
+                        "quantity": {}
+                    }}
+                }}
+            }}"#,
+            kit_id, component_id, quantity
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    /// Stock of the product, or how many kits its components make up.
+    async fn available_stock(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+    ) -> f64 {
+        let query = format!(
+            r#"{{ "query": "{{ showProduct(productId: {}) {{ availableStock }} }}" }}"#,
+            product_id
+        );
+        let response = send_request(srv, csrf_token, request_cookie, query).await;
+        response
+            .get("data")
+            .unwrap()
+            .get("showProduct")
+            .unwrap()
+            .get("availableStock")
+            .unwrap()
+            .as_f64()
+            .unwrap()
+    }
+}
@@ -14,7 +14,7 @@ mod test{
 
     use serde_json::{json, Value};
     use std::time::Duration as std_duration;
-    use std::cell::RefMut;
+    use std::cell::{RefCell, RefMut};
 
     use crate::common::db_connection::establish_connection;
     use crate::common::{server_test, send_request};
@@ -42,7 +42,16 @@ mod test{
             stock: Some(10.4),
             cost: Some(1892),
             description: Some("not just your regular shoes, this one will make you jump".to_string()),
-            user_id: None
+            user_id: None,
+            category_id: None,
+            sku: None,
+            unit_id: None,
+            track_lots: None,
+            serialized: None,
+            min_stock: None,
+            reorder_point: None,
+            reorder_quantity: None,
+            supplier_id: None
         };
 
         let hat = FormProduct {
@@ -51,7 +60,16 @@ mod test{
             stock: Some(15.0),
             cost: Some(2045),
             description: Some("Just a regular hat".to_string()),
-            user_id: None
+            user_id: None,
+            category_id: None,
+            sku: None,
+            unit_id: None,
+            track_lots: None,
+            serialized: None,
+            min_stock: None,
+            reorder_point: None,
+            reorder_quantity: None,
+            supplier_id: None
         };
 
         let pants = FormProduct {
@@ -60,11 +78,20 @@ mod test{
             stock: Some(25.0),
             cost: Some(3025),
             description: Some("beautiful black pants that will make you look thin".to_string()),
-            user_id: None
+            user_id: None,
+            category_id: None,
+            sku: None,
+            unit_id: None,
+            track_lots: None,
+            serialized: None,
+            min_stock: None,
+            reorder_point: None,
+            reorder_quantity: None,
+            supplier_id: None
         };
 
-        let new_price_discount = FormPrice { id: None, name: Some("Discount".to_string()), user_id: None };
-        let new_price_normal = FormPrice { id: None, name: Some("Normal".to_string()), user_id: None };
+        let new_price_discount = FormPrice { id: None, name: Some("Discount".to_string()), user_id: None, active_from: None, active_to: None, is_default: None };
+        let new_price_normal = FormPrice { id: None, name: Some("Normal".to_string()), user_id: None, active_from: None, active_to: None, is_default: Some(true) };
 
         let price_discount = create_a_price(srv.borrow_mut(),
                                             csrf_token.clone(),
@@ -133,6 +160,116 @@ mod test{
         let pants_db = response_pants_db.get("data").unwrap().get("createProduct").unwrap();
         let pants_id: i32 = serde_json::from_value(pants_db.get("product").unwrap().get("id").unwrap().clone()).unwrap();
 
+        let history = price_history(srv.borrow_mut(),
+                                    csrf_token.clone(),
+                                    request_cookie.clone(),
+                                    shoe_id,
+                                    price_discount_id).await;
+        assert_eq!(history, json!({
+            "data": {
+                "priceHistory": [{
+                    "priceId": price_discount_id,
+                    "productId": shoe_id,
+                    "oldAmount": null,
+                    "newAmount": 10
+                }]
+            }
+        }));
+
+        let audit_log = product_audit_log(srv.borrow_mut(),
+                                          csrf_token.clone(),
+                                          request_cookie.clone()).await;
+        let created_events: Vec<&Value> = audit_log
+            .get("data").unwrap()
+            .get("auditLog").unwrap()
+            .get("data").unwrap()
+            .as_array().unwrap()
+            .iter()
+            .filter(|event| event.get("operation").unwrap() == &json!("createProduct"))
+            .collect();
+        assert_eq!(created_events.len(), 3);
+        let shoe_event = created_events.iter()
+            .find(|event| event.get("entityId").unwrap() == &json!(shoe_id))
+            .unwrap();
+        assert_eq!(shoe_event.get("entity").unwrap(), &json!("product"));
+        assert_eq!(shoe_event.get("before").unwrap(), &json!(null));
+        let shoe_after: Value =
+            serde_json::from_str(shoe_event.get("after").unwrap().as_str().unwrap()).unwrap();
+        assert_eq!(shoe_after.get("product").unwrap().get("name").unwrap(), &json!("Shoe"));
+
+        set_price_tiers(srv.borrow_mut(),
+                        csrf_token.clone(),
+                        request_cookie.clone(),
+                        shoe_id,
+                        price_normal_id).await;
+        let single = resolve_price(srv.borrow_mut(),
+                                   csrf_token.clone(),
+                                   request_cookie.clone(),
+                                   shoe_id,
+                                   1.0).await;
+        assert_eq!(single, json!({
+            "data": {
+                "resolvePrice": {
+                    "priceId": price_normal_id,
+                    "unitAmount": 15,
+                    "tierMinQuantity": null,
+                    "total": 15.0
+                }
+            }
+        }));
+        let wholesale = resolve_price(srv.borrow_mut(),
+                                      csrf_token.clone(),
+                                      request_cookie.clone(),
+                                      shoe_id,
+                                      12.0).await;
+        assert_eq!(wholesale, json!({
+            "data": {
+                "resolvePrice": {
+                    "priceId": price_normal_id,
+                    "unitAmount": 12,
+                    "tierMinQuantity": 10.0,
+                    "total": 144.0
+                }
+            }
+        }));
+        let sale_price = sell_without_price(srv.borrow_mut(),
+                                            csrf_token.clone(),
+                                            request_cookie.clone(),
+                                            shoe_id,
+                                            12.0).await;
+        assert_eq!(sale_price, json!({
+            "data": {
+                "createSale": {
+                    "saleProducts": [{
+                        "saleProduct": {
+                            "price": 12
+                        }
+                    }]
+                }
+            }
+        }));
+
+        let receipt = receive_in_boxes(&srv,
+                                       csrf_token.clone(),
+                                       request_cookie.clone(),
+                                       pants_id,
+                                       true).await;
+        assert_eq!(receipt, json!({
+            "data": {
+                "receiveStock": {
+                    "productId": pants_id,
+                    "quantity": 24.0,
+                    "kind": "RECEIPT"
+                }
+            }
+        }));
+        let refused = receive_in_boxes(&srv,
+                                       csrf_token.clone(),
+                                       request_cookie.clone(),
+                                       hat_id,
+                                       false).await;
+        assert_eq!(refused.get("data").unwrap(), &json!(null));
+
         show_a_product(srv.borrow_mut(), 
                        csrf_token.clone(), 
                        request_cookie.clone(), 
@@ -145,7 +282,16 @@ mod test{
             stock: Some(30.0),
             cost: Some(3025),
             description: Some("A hat with particular color, a dark black shining and beautiful".to_string()),
-            user_id: None
+            user_id: None,
+            category_id: None,
+            sku: None,
+            unit_id: None,
+            track_lots: None,
+            serialized: None,
+            min_stock: None,
+            reorder_point: None,
+            reorder_quantity: None,
+            supplier_id: None
         };
 
         update_a_product(srv.borrow_mut(), 
@@ -205,6 +351,81 @@ mod test{
             }
         });
 
+        search_products(srv.borrow_mut(), 
+                        csrf_token.clone(), 
+                        request_cookie.clone(), 
+                        data_for_searching.clone()).await;
+
+        let data_for_filtering = json!({
+            "data": {
+                "listProduct": {
+                    "data": [{
+                        "product": {
+                            "id": hat_id
+                        }
+                    }],
+                    "highlights": [{
+                        "productId": hat_id,
+                        "name": "<b>Hat</b>",
+                        "description": "Just a regular <b>hat</b>"
+                    }],
+                    "facets": {
+                        "total": 1,
+                        "inStock": 1,
+                        "categories": [{
+                            "categoryId": null,
+                            "count": 1
+                        }],
+                        "priceLists": [
+                            {
+                                "name": "Discount",
+                                "count": 1
+                            },
+                            {
+                                "name": "Normal",
+                                "count": 1
+                            }
+                        ],
+                        "priceRanges": [{
+                            "minPrice": 10,
+                            "maxPrice": 10,
+                            "count": 1
+                        }]
+                    }
+                }
+            }
+        });
+
+        filter_products(srv.borrow_mut(), 
+                        csrf_token.clone(), 
+                        request_cookie.clone(), 
+                        price_discount_id,
+                        data_for_filtering).await;
+
+        let response_archived =
+            archive_a_product(srv.borrow_mut(),
+                              csrf_token.clone(),
+                              request_cookie.clone(),
+                              "archiveProduct",
+                              &hat_id).await;
+        let archived_at = response_archived
+            .get("data").unwrap()
+            .get("archiveProduct").unwrap()
+            .get("product").unwrap()
+            .get("archivedAt").unwrap();
+        assert!(!archived_at.is_null());
+
+        search_products(srv.borrow_mut(), 
+                        csrf_token.clone(), 
+                        request_cookie.clone(), 
+                        json!({ "data": { "listProduct": { "data": [] } } })).await;
+
+        archive_a_product(srv.borrow_mut(),
+                          csrf_token.clone(),
+                          request_cookie.clone(),
+                          "unarchiveProduct",
+                          &hat_id).await;
+
         search_products(srv.borrow_mut(), 
                         csrf_token, 
                         request_cookie, 
@@ -469,6 +690,33 @@ mod test{
         send_request(srv, csrf_token, request_cookie, query).await
     }
 
+    async fn archive_a_product(srv: RefMut<'_, TestServer>,
+                               csrf_token: HeaderValue,
+                               request_cookie: Cookie<'_>,
+                               mutation: &str,
+                               id: &i32) -> Value {
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    mutation ArchiveAProduct($productId: Int!) {{
+                        {}(productId: $productId) {{
+                            product {{
+                                id
+                                archivedAt
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "productId": {}
+                }}
+            }}
+        "#, mutation, id).replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
     async fn search_products(srv: RefMut<'_, TestServer>,
                              csrf_token: HeaderValue,
                              request_cookie: Cookie<'_>,
@@ -511,6 +759,320 @@ mod test{
         assert_eq!(data_for_searching, response_sales);
     }
 
+    async fn filter_products(srv: RefMut<'_, TestServer>,
+                             csrf_token: HeaderValue,
+                             request_cookie: Cookie<'_>,
+                             price_id: i32,
+                             data_for_filtering: Value) {
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    query ListProduct($search: String!, $limit: Int!, $rank: Float!, $filter: ProductFilter) {{
+                        listProduct(search: $search, limit: $limit, rank: $rank, filter: $filter) {{
+                            data {{
+                                product {{
+                                    id
+                                }}
+                            }}
+                            highlights {{
+                                productId
+                                name
+                                description
+                            }}
+                            facets {{
+                                total
+                                inStock
+                                categories {{
+                                    categoryId
+                                    count
+                                }}
+                                priceLists {{
+                                    name
+                                    count
+                                }}
+                                priceRanges {{
+                                    minPrice
+                                    maxPrice
+                                    count
+                                }}
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "search": "hat",
+                    "limit": 10,
+                    "rank": 1.0,
+                    "filter": {{
+                        "priceId": {},
+                        "minPrice": 5,
+                        "maxPrice": 12,
+                        "inStock": true
+                    }}
+                }}
+            }}
+        "#, price_id).replace("\n", "");
+
+        let response_products: Value = send_request(srv, csrf_token, request_cookie, query).await;
+        assert_eq!(data_for_filtering, response_products);
+    }
+
+    async fn price_history(srv: RefMut<'_, TestServer>,
+                           csrf_token: HeaderValue,
+                           request_cookie: Cookie<'_>,
+                           product_id: i32,
+                           price_id: i32) -> Value {
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    query PriceHistory($productId: Int!, $priceId: Int) {{
+                        priceHistory(productId: $productId, priceId: $priceId) {{
+                            priceId
+                            productId
+                            oldAmount
+                            newAmount
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "productId": {},
+                    "priceId": {}
+                }}
+            }}
+        "#, product_id, price_id).replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    /// Receives two boxes of 12 units of the product, the box being
+    /// enabled for purchase or not. The product is counted in pieces.
+    async fn receive_in_boxes(srv: &RefCell<TestServer>,
+                              csrf_token: HeaderValue,
+                              request_cookie: Cookie<'_>,
+                              product_id: i32,
+                              for_purchase: bool) -> Value {
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    mutation CreatePiece {{
+                        createUnit(form: {{ name: \"Piece {}\" }}) {{
+                            id
+                        }}
+                    }}
+                "
+            }}
+        "#, product_id).replace("\n", "");
+        let piece = send_request(srv.borrow_mut(), csrf_token.clone(), request_cookie.clone(), query).await;
+        let piece_id = piece.get("data").unwrap().get("createUnit").unwrap().get("id").unwrap().clone();
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    mutation SetBaseUnit {{
+                        updateProduct(form: {{ id: {}, unitId: {} }}, formPriceProducts: {{ data: [] }}) {{
+                            product {{
+                                unitId
+                            }}
+                        }}
+                    }}
+                "
+            }}
+        "#, product_id, piece_id).replace("\n", "");
+        let base_unit = send_request(srv.borrow_mut(), csrf_token.clone(), request_cookie.clone(), query).await;
+        assert_eq!(base_unit, json!({ "data": { "updateProduct": { "product": { "unitId": piece_id } } } }));
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    mutation CreateBox {{
+                        createUnit(form: {{ name: \"Box {}\" }}) {{
+                            id
+                        }}
+                    }}
+                "
+            }}
+        "#, product_id).replace("\n", "");
+        let unit = send_request(srv.borrow_mut(), csrf_token.clone(), request_cookie.clone(), query).await;
+        let unit_id = unit.get("data").unwrap().get("createUnit").unwrap().get("id").unwrap().clone();
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    mutation CreateProductUnit($form: FormProductUnit!) {{
+                        createProductUnit(form: $form) {{
+                            id
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "productId": {},
+                        "unitId": {},
+                        "factor": 12.0,
+                        "forSale": false,
+                        "forPurchase": {}
+                    }}
+                }}
+            }}
+        "#, product_id, unit_id, for_purchase).replace("\n", "");
+        send_request(srv.borrow_mut(), csrf_token.clone(), request_cookie.clone(), query).await;
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    mutation ReceiveStock($form: FormStockReceipt!) {{
+                        receiveStock(form: $form) {{
+                            productId
+                            quantity
+                            kind
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "productId": {},
+                        "unitId": {},
+                        "quantity": 2.0
+                    }}
+                }}
+            }}
+        "#, product_id, unit_id).replace("\n", "");
+
+        send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await
+    }
+
+    async fn product_audit_log(srv: RefMut<'_, TestServer>,
+                               csrf_token: HeaderValue,
+                               request_cookie: Cookie<'_>) -> Value {
+
+        let query = r#"
+            {
+                "query": "
+                    query AuditLog($search: SearchAuditEvent) {
+                        auditLog(search: $search, limit: 10, offset: 0) {
+                            data {
+                                operation
+                                entity
+                                entityId
+                                before
+                                after
+                            }
+                            total
+                        }
+                    }
+                ",
+                "variables": {
+                    "search": { "entity": "product" }
+                }
+            }
+        "#.replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    async fn set_price_tiers(srv: RefMut<'_, TestServer>,
+                             csrf_token: HeaderValue,
+                             request_cookie: Cookie<'_>,
+                             product_id: i32,
+                             price_id: i32) -> Value {
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    mutation SetPriceTiers($form: FormPriceTiers!) {{
+                        setPriceTiers(form: $form) {{
+                            minQuantity
+                            amount
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "priceId": {},
+                        "productId": {},
+                        "tiers": [{{ "minQuantity": 10.0, "amount": 12 }}]
+                    }}
+                }}
+            }}
+        "#, price_id, product_id).replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    async fn resolve_price(srv: RefMut<'_, TestServer>,
+                           csrf_token: HeaderValue,
+                           request_cookie: Cookie<'_>,
+                           product_id: i32,
+                           quantity: f64) -> Value {
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    query ResolvePrice($productId: Int!, $quantity: Float!) {{
+                        resolvePrice(productId: $productId, quantity: $quantity) {{
+                            priceId
+                            unitAmount
+                            tierMinQuantity
+                            total
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "productId": {},
+                    "quantity": {:.1}
+                }}
+            }}
+        "#, product_id, quantity).replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    async fn sell_without_price(srv: RefMut<'_, TestServer>,
+                                csrf_token: HeaderValue,
+                                request_cookie: Cookie<'_>,
+                                product_id: i32,
+                                amount: f64) -> Value {
+
+        let query = format!(r#"
+            {{
+                "query": "
+                    mutation CreateSale($form: FormSale!, $formSaleProducts: FormSaleProducts!) {{
+                        createSale(form: $form, formSaleProducts: $formSaleProducts) {{
+                            saleProducts {{
+                                saleProduct {{
+                                    price
+                                }}
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "saleDate": "2019-11-12",
+                        "total": 0.0
+                    }},
+                    "formSaleProducts": {{
+                        "data": [{{
+                            "product": {{ }},
+                            "saleProduct": {{
+                                "productId": {},
+                                "amount": {:.1},
+                                "discount": 0,
+                                "tax": 0,
+                                "total": 0.0
+                            }}
+                        }}]
+                    }}
+                }}
+            }}
+        "#, product_id, amount).replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
     async fn create_a_price(srv: RefMut<'_, TestServer>,
                             csrf_token: HeaderValue,
                             request_cookie: Cookie<'_>,
@@ -530,11 +1092,13 @@ mod test{
                 ",
                 "variables": {{
                     "form": {{
-                        "name": "{}"
+                        "name": "{}",
+                        "isDefault": {}
                     }}
                 }}
             }}"#,
-            price.clone().name.unwrap())
+            price.clone().name.unwrap(),
+            price.is_default.unwrap_or(false))
             .replace("\n", "");
 
         send_request(srv, csrf_token, request_cookie, query).await
@@ -0,0 +1,366 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use serde_json::{json, Value};
+    use std::cell::{RefCell, RefMut};
+    use std::sync::Arc;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::{send_request, server_test};
+
+    use ::mystore_lib::models::price::FormPriceProductsToUpdate;
+    use ::mystore_lib::models::product::{FormProduct, Product};
+    use ::mystore_lib::models::user::{NewUser, User};
+    use ::mystore_lib::models::Context;
+
+    #[actix_rt::test]
+    async fn test() {
+        let user = create_user();
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        let acme =
+            create_a_supplier(&srv, csrf_token.clone(), request_cookie.clone(), "Acme", 10).await;
+        let bolt =
+            create_a_supplier(&srv, csrf_token.clone(), request_cookie.clone(), "Bolt", 0).await;
+
+        let shoe = create_product(
+            user.id,
+            FormProduct {
+                name: Some("Shoe".to_string()),
+                stock: Some(20.0),
+                reorder_point: Some(5.0),
+                reorder_quantity: Some(10.0),
+                supplier_id: Some(acme),
+                ..new_product(user.id)
+            },
+        );
+        let sock = create_product(
+            user.id,
+            FormProduct {
+                name: Some("Sock".to_string()),
+                stock: Some(3.0),
+                min_stock: Some(5.0),
+                supplier_id: Some(bolt),
+                ..new_product(user.id)
+            },
+        );
+        create_product(
+            user.id,
+            FormProduct {
+                name: Some("Hat".to_string()),
+                stock: Some(50.0),
+                reorder_point: Some(5.0),
+                supplier_id: Some(acme),
+                ..new_product(user.id)
+            },
+        );
+        let cap = create_product(
+            user.id,
+            FormProduct {
+                name: Some("Cap".to_string()),
+                stock: Some(1.0),
+                reorder_point: Some(2.0),
+                ..new_product(user.id)
+            },
+        );
+
+        // Ten shoes sold in ten days, the ten left won't last the ten days
+        // Acme takes to deliver.
+        sell_today(&srv, csrf_token.clone(), request_cookie.clone(), shoe, 10.0).await;
+
+        let query = r#"{ "query": "{ lowStockProducts { productId stock } }" }"#.to_string();
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "lowStockProducts": [
+                        { "productId": cap, "stock": 1.0 },
+                        { "productId": sock, "stock": 3.0 }
+                    ]
+                }
+            })
+        );
+
+        let response = reorder_suggestions(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            10,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "reorderSuggestions": [
+                        {
+                            "supplier": null,
+                            "suggestions": [
+                                { "product": { "productId": cap }, "dailySales": 0.0, "quantity": 1.0 }
+                            ]
+                        },
+                        {
+                            "supplier": { "name": "Acme" },
+                            "suggestions": [
+                                { "product": { "productId": shoe }, "dailySales": 1.0, "quantity": 10.0 }
+                            ]
+                        },
+                        {
+                            "supplier": { "name": "Bolt" },
+                            "suggestions": [
+                                { "product": { "productId": sock }, "dailySales": 0.0, "quantity": 2.0 }
+                            ]
+                        }
+                    ]
+                }
+            })
+        );
+
+        let response = reorder_suggestions(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            0,
+        )
+        .await;
+        assert_eq!(
+            response.get("errors").unwrap()[0].get("message").unwrap(),
+            "The sales period should be at least one day"
+        );
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn create_user() -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: "jhon@doe.com".to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    fn new_product(user_id: i32) -> FormProduct {
+        FormProduct {
+            id: None,
+            name: None,
+            stock: None,
+            cost: Some(1000),
+            description: None,
+            user_id: Some(user_id),
+            category_id: None,
+            sku: None,
+            unit_id: None,
+            track_lots: None,
+            serialized: None,
+            min_stock: None,
+            reorder_point: None,
+            reorder_quantity: None,
+            supplier_id: None,
+        }
+    }
+
+    fn create_product(user_id: i32, form: FormProduct) -> i32 {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+        let context = Context {
+            user_id,
+            conn: Arc::new(pg_pool),
+            scopes: None,
+        };
+        Product::create(&context, form, FormPriceProductsToUpdate { data: vec![] })
+            .unwrap()
+            .product
+            .id
+    }
+
+    async fn create_a_supplier(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        name: &str,
+        lead_time_days: i32,
+    ) -> i32 {
+        let query = format!(
+            r#"{{ "query": "mutation {{ createSupplier(form: {{ name: \"{}\", leadTimeDays: {} }}) {{ id }} }}" }}"#,
+            name, lead_time_days
+        );
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        serde_json::from_value(
+            response
+                .get("data")
+                .unwrap()
+                .get("createSupplier")
+                .unwrap()
+                .get("id")
+                .unwrap()
+                .clone(),
+        )
+        .unwrap()
+    }
+
+    async fn reorder_suggestions(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        days: i32,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    query ReorderSuggestions($days: Int!) {{
+                        reorderSuggestions(days: $days) {{
+                            supplier {{
+                                name
+                            }}
+                            suggestions {{
+                                product {{
+                                    productId
+                                }}
+                                dailySales
+                                quantity
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "days": {}
+                }}
+            }}"#,
+            days
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    /// Creates a sale of a single product dated today and approves it.
+    async fn sell_today(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        amount: f64,
+    ) {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateSale($form: FormSale!, $formSaleProducts: FormSaleProducts!) {{
+                        createSale(form: $form, formSaleProducts: $formSaleProducts) {{
+                            sale {{
+                                id
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "saleDate": "{sale_date}",
+                        "total": {total}
+                    }},
+                    "formSaleProducts": {{
+                        "data":
+                            [{{
+                                "product": {{ }},
+                                "saleProduct": {{
+                                    "amount": {amount},
+                                    "discount": 0,
+                                    "price": 10,
+                                    "productId": {product_id},
+                                    "tax": 0,
+                                    "total": {total}
+                                }}
+                            }}]
+                    }}
+                }}
+            }}"#,
+            sale_date = Local::now().naive_local().date(),
+            amount = amount,
+            product_id = product_id,
+            total = amount * 10.0
+        )
+        .replace("\n", "");
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        let sale_id = response
+            .get("data")
+            .unwrap()
+            .get("createSale")
+            .unwrap()
+            .get("sale")
+            .unwrap()
+            .get("id")
+            .unwrap()
+            .clone();
+
+        let query = format!(
+            r#"{{ "query": "mutation {{ approveSale(saleId: {}) }}" }}"#,
+            sale_id
+        );
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        assert_eq!(response, json!({ "data": { "approveSale": true } }));
+    }
+}
@@ -45,6 +45,15 @@ mod test {
                 "not just your regular shoes, this one will make you jump".to_string(),
             ),
             user_id: Some(user.id),
+            category_id: None,
+            sku: None,
+            unit_id: None,
+            track_lots: None,
+            serialized: None,
+            min_stock: None,
+            reorder_point: None,
+            reorder_quantity: None,
+            supplier_id: None,
         };
 
         let new_hat = FormProduct {
@@ -54,6 +63,15 @@ mod test {
             cost: Some(2045),
             description: Some("Just a regular hat".to_string()),
             user_id: Some(user.id),
+            category_id: None,
+            sku: None,
+            unit_id: None,
+            track_lots: None,
+            serialized: None,
+            min_stock: None,
+            reorder_point: None,
+            reorder_quantity: None,
+            supplier_id: None,
         };
 
         let _new_pants = FormProduct {
@@ -63,6 +81,15 @@ mod test {
             cost: Some(3025),
             description: Some("beautiful black pants that will make you look thin".to_string()),
             user_id: Some(user.id),
+            category_id: None,
+            sku: None,
+            unit_id: None,
+            track_lots: None,
+            serialized: None,
+            min_stock: None,
+            reorder_point: None,
+            reorder_quantity: None,
+            supplier_id: None,
         };
 
         let shoe = create_product(user.id, new_shoe).product;
@@ -75,6 +102,8 @@ mod test {
             total: Some(123.98),
             bill_number: None,
             state: Some(SaleState::Draft),
+            location_id: None,
+            customer_id: None,
         };
 
         let new_sale_product = FormSaleProduct {
@@ -86,6 +115,8 @@ mod test {
             tax: Some(12),
             price: Some(20),
             total: Some(28.0),
+            unit_id: None,
+            lot_id: None,
         };
 
         let response_sale = create_a_sale(
@@ -121,6 +152,8 @@ mod test {
             total: Some(123.98),
             bill_number: None,
             state: Some(SaleState::Draft),
+            location_id: None,
+            customer_id: None,
         };
 
         let new_sale_product_hat = FormSaleProduct {
@@ -132,6 +165,8 @@ mod test {
             tax: Some(12),
             price: Some(30),
             total: Some(150.0),
+            unit_id: None,
+            lot_id: None,
         };
 
         let response_sale = update_a_sale(
@@ -331,6 +366,7 @@ mod test {
         let context = Context {
             user_id,
             conn: Arc::new(pg_pool),
+            scopes: None,
         };
         Product::create(
             &context,
@@ -0,0 +1,402 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use serde_json::{json, Value};
+    use std::cell::RefMut;
+    use std::sync::Arc;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::{send_request, server_test};
+
+    use ::mystore_lib::models::price::FormPriceProductsToUpdate;
+    use ::mystore_lib::models::product::{FormProduct, Product};
+    use ::mystore_lib::models::user::{NewUser, User};
+    use ::mystore_lib::models::Context;
+
+    #[actix_rt::test]
+    async fn test() {
+        let user = create_user();
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        let phone = create_product(user.id, "Phone");
+
+        let response = receive_serials(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            phone,
+            r#"["SN-1", "SN-2", "SN-3"]"#,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "receiveSerialNumbers": [
+                        { "serial": "SN-1" },
+                        { "serial": "SN-2" },
+                        { "serial": "SN-3" }
+                    ]
+                }
+            })
+        );
+
+        let response = receive_serials(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            phone,
+            r#"["SN-3", "SN-4"]"#,
+        )
+        .await;
+        assert_eq!(error_message(&response), "Serial SN-3 was already received");
+
+        let query = r#"{ "query": "mutation { createCustomer(form: { name: \"Ann\" }) { id } }" }"#
+            .to_string();
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        let customer_id = response
+            .get("data")
+            .unwrap()
+            .get("createCustomer")
+            .unwrap()
+            .get("id")
+            .unwrap()
+            .clone();
+
+        let response = create_a_sale(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            &customer_id,
+            phone,
+            r#"["SN-9"]"#,
+        )
+        .await;
+        assert_eq!(
+            error_message(&response),
+            "Serial SN-9 is unknown or was already sold"
+        );
+
+        let response = create_a_sale(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            &customer_id,
+            phone,
+            r#"["SN-1", "SN-1"]"#,
+        )
+        .await;
+        assert_eq!(error_message(&response), "Serial SN-1 is repeated");
+
+        let sale_id = create_a_sale(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            &customer_id,
+            phone,
+            r#"["SN-2"]"#,
+        )
+        .await
+        .get("data")
+        .unwrap()
+        .get("createSale")
+        .unwrap()
+        .get("sale")
+        .unwrap()
+        .get("id")
+        .unwrap()
+        .clone();
+        let query = format!(
+            r#"{{ "query": "mutation {{ approveSale(saleId: {}) }}" }}"#,
+            sale_id
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(response, json!({ "data": { "approveSale": true } }));
+
+        // A sold unit can't be sold twice.
+        let response = create_a_sale(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            &customer_id,
+            phone,
+            r#"["SN-2"]"#,
+        )
+        .await;
+        assert_eq!(
+            error_message(&response),
+            "Serial SN-2 is unknown or was already sold"
+        );
+
+        let response = locate(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            "SN-2",
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "locateSerialNumber": [{
+                        "product": { "name": "Phone" },
+                        "sale": { "id": sale_id, "customerId": customer_id }
+                    }]
+                }
+            })
+        );
+
+        let response = locate(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            "SN-1",
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "locateSerialNumber": [{
+                        "product": { "name": "Phone" },
+                        "sale": null
+                    }]
+                }
+            })
+        );
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn create_user() -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: "jhon@doe.com".to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    fn create_product(user_id: i32, name: &str) -> i32 {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+        let context = Context {
+            user_id,
+            conn: Arc::new(pg_pool),
+            scopes: None,
+        };
+        Product::create(
+            &context,
+            FormProduct {
+                id: None,
+                name: Some(name.to_string()),
+                stock: Some(0.0),
+                cost: Some(40000),
+                description: None,
+                user_id: Some(user_id),
+                category_id: None,
+                sku: None,
+                unit_id: None,
+                track_lots: None,
+                serialized: Some(true),
+                min_stock: None,
+                reorder_point: None,
+                reorder_quantity: None,
+                supplier_id: None,
+            },
+            FormPriceProductsToUpdate { data: vec![] },
+        )
+        .unwrap()
+        .product
+        .id
+    }
+
+    async fn receive_serials(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        serials: &str,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation ReceiveSerialNumbers($productId: Int!, $serials: [String!]!) {{
+                        receiveSerialNumbers(productId: $productId, serials: $serials) {{
+                            serial
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "productId": {},
+                    "serials": {}
+                }}
+            }}"#,
+            product_id, serials
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    /// Creates a sale to the customer of one unit of the product per
+    /// serial.
+    async fn create_a_sale(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        customer_id: &Value,
+        product_id: i32,
+        serials: &str,
+    ) -> Value {
+        let amount = serde_json::from_str::<Vec<String>>(serials).unwrap().len() as f64;
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateSale($form: FormSale!, $formSaleProducts: FormSaleProducts!) {{
+                        createSale(form: $form, formSaleProducts: $formSaleProducts) {{
+                            sale {{
+                                id
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "saleDate": "2019-11-12",
+                        "customerId": {customer_id},
+                        "total": {total}
+                    }},
+                    "formSaleProducts": {{
+                        "data":
+                            [{{
+                                "product": {{ }},
+                                "saleProduct": {{
+                                    "amount": {amount},
+                                    "discount": 0,
+                                    "price": 50000,
+                                    "productId": {product_id},
+                                    "tax": 0,
+                                    "total": {total}
+                                }},
+                                "serialNumbers": {serials}
+                            }}]
+                    }}
+                }}
+            }}"#,
+            customer_id = customer_id,
+            amount = amount,
+            product_id = product_id,
+            serials = serials,
+            total = amount * 50000.0
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    async fn locate(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        serial: &str,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    query LocateSerialNumber($serial: String!) {{
+                        locateSerialNumber(serial: $serial) {{
+                            product {{
+                                name
+                            }}
+                            sale {{
+                                id
+                                customerId
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "serial": "{}"
+                }}
+            }}"#,
+            serial
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    fn error_message(response: &Value) -> &str {
+        response.get("errors").unwrap()[0]
+            .get("message")
+            .unwrap()
+            .as_str()
+            .unwrap()
+    }
+}
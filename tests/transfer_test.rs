@@ -0,0 +1,441 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use serde_json::{json, Value};
+    use std::cell::{RefCell, RefMut};
+    use std::sync::Arc;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::{send_request, server_test};
+
+    use ::mystore_lib::models::price::FormPriceProductsToUpdate;
+    use ::mystore_lib::models::product::{FormProduct, Product};
+    use ::mystore_lib::models::user::{NewUser, User};
+    use ::mystore_lib::models::Context;
+
+    #[actix_rt::test]
+    async fn test() {
+        let user = create_user();
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        let north =
+            create_a_location(&srv, csrf_token.clone(), request_cookie.clone(), "North").await;
+        let south =
+            create_a_location(&srv, csrf_token.clone(), request_cookie.clone(), "South").await;
+
+        let shoe = create_product(user.id, "Shoe", 0.0);
+        receive_stock(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            shoe,
+            north,
+            8.0,
+        )
+        .await;
+
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateTransfer($form: FormTransfer!, $lines: [FormTransferProduct!]!) {{
+                        createTransfer(form: $form, lines: $lines) {{
+                            transfer {{
+                                id
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "sourceLocationId": {},
+                        "destinationLocationId": {}
+                    }},
+                    "lines": [{{ "productId": {}, "quantity": 10.0 }}]
+                }}
+            }}"#,
+            north, south, shoe
+        )
+        .replace("\n", "");
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        let transfer_id = response
+            .get("data")
+            .unwrap()
+            .get("createTransfer")
+            .unwrap()
+            .get("transfer")
+            .unwrap()
+            .get("id")
+            .unwrap()
+            .clone();
+
+        // North only has eight shoes.
+        let response = send_transfer(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            &transfer_id,
+        )
+        .await;
+        assert_eq!(
+            response.get("errors").unwrap()[0].get("message").unwrap(),
+            &json!(format!(
+                "Product {} has 8 in stock at the source location, 10 can't be sent",
+                shoe
+            ))
+        );
+
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation UpdateTransfer($form: FormTransfer!, $lines: [FormTransferProduct!]!) {{
+                        updateTransfer(form: $form, lines: $lines) {{
+                            transfer {{
+                                state
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "id": {}
+                    }},
+                    "lines": [{{ "productId": {}, "quantity": 6.0 }}]
+                }}
+            }}"#,
+            transfer_id, shoe
+        )
+        .replace("\n", "");
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({ "data": { "updateTransfer": { "transfer": { "state": "DRAFT" } } } })
+        );
+
+        let response = send_transfer(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            &transfer_id,
+        )
+        .await;
+        let sent = response.get("data").unwrap().get("sendTransfer").unwrap();
+        assert_eq!(
+            sent.get("transfer").unwrap(),
+            &json!({ "state": "IN_TRANSIT" })
+        );
+        let transfer_product_id = sent.get("transferProducts").unwrap()[0]
+            .get("transferProduct")
+            .unwrap()
+            .get("id")
+            .unwrap()
+            .clone();
+
+        assert_eq!(
+            in_transit_stock(srv.borrow_mut(), csrf_token.clone(), request_cookie.clone()).await,
+            json!({
+                "data": {
+                    "inTransitStock": [{ "productId": shoe, "locationId": south, "quantity": 6.0 }]
+                }
+            })
+        );
+        assert_eq!(
+            location_stock(
+                srv.borrow_mut(),
+                csrf_token.clone(),
+                request_cookie.clone(),
+                north
+            )
+            .await,
+            2.0
+        );
+
+        // One shoe got lost on the way.
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation ReceiveTransfer($transferId: Int!, $received: [FormReceivedTransferProduct!]!) {{
+                        receiveTransfer(transferId: $transferId, received: $received) {{
+                            transfer {{
+                                state
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "transferId": {},
+                    "received": [{{ "transferProductId": {}, "receivedQuantity": 5.0 }}]
+                }}
+            }}"#,
+            transfer_id, transfer_product_id
+        )
+        .replace("\n", "");
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({ "data": { "receiveTransfer": { "transfer": { "state": "RECEIVED" } } } })
+        );
+
+        assert_eq!(
+            location_stock(
+                srv.borrow_mut(),
+                csrf_token.clone(),
+                request_cookie.clone(),
+                south
+            )
+            .await,
+            5.0
+        );
+        assert_eq!(
+            in_transit_stock(srv.borrow_mut(), csrf_token.clone(), request_cookie.clone()).await,
+            json!({ "data": { "inTransitStock": [] } })
+        );
+
+        let query = r#"{ "query": "{ transferDiscrepancies(limit: 10) { transferId quantity receivedQuantity difference } }" }"#.to_string();
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "transferDiscrepancies": [{
+                        "transferId": transfer_id,
+                        "quantity": 6.0,
+                        "receivedQuantity": 5.0,
+                        "difference": -1.0
+                    }]
+                }
+            })
+        );
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn create_user() -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: "jhon@doe.com".to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    fn create_product(user_id: i32, name: &str, stock: f64) -> i32 {
+        Product::create(
+            &context(user_id),
+            FormProduct {
+                id: None,
+                name: Some(name.to_string()),
+                stock: Some(stock),
+                cost: Some(1000),
+                description: None,
+                user_id: Some(user_id),
+                category_id: None,
+                sku: None,
+                unit_id: None,
+                track_lots: None,
+                serialized: None,
+                min_stock: None,
+                reorder_point: None,
+                reorder_quantity: None,
+                supplier_id: None,
+            },
+            FormPriceProductsToUpdate { data: vec![] },
+        )
+        .unwrap()
+        .product
+        .id
+    }
+
+    fn context(user_id: i32) -> Context {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+        Context {
+            user_id,
+            conn: Arc::new(pg_pool),
+            scopes: None,
+        }
+    }
+
+    async fn create_a_location(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        name: &str,
+    ) -> i32 {
+        let query = format!(
+            r#"{{ "query": "mutation {{ createLocation(form: {{ name: \"{}\" }}) {{ id }} }}" }}"#,
+            name
+        );
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        serde_json::from_value(
+            response
+                .get("data")
+                .unwrap()
+                .get("createLocation")
+                .unwrap()
+                .get("id")
+                .unwrap()
+                .clone(),
+        )
+        .unwrap()
+    }
+
+    async fn receive_stock(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        location_id: i32,
+        quantity: f64,
+    ) {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation ReceiveStock($form: FormStockReceipt!) {{
+                        receiveStock(form: $form) {{
+                            locationId
+                            quantity
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "productId": {},
+                        "locationId": {},
+                        "quantity": {}
+                    }}
+                }}
+            }}"#,
+            product_id, location_id, quantity
+        )
+        .replace("\n", "");
+
+        let response = send_request(srv, csrf_token, request_cookie, query).await;
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "receiveStock": { "locationId": location_id, "quantity": quantity }
+                }
+            })
+        );
+    }
+
+    async fn send_transfer(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        transfer_id: &Value,
+    ) -> Value {
+        let query = format!(
+            r#"{{ "query": "mutation {{ sendTransfer(transferId: {}) {{ transfer {{ state }} transferProducts {{ transferProduct {{ id }} }} }} }}" }}"#,
+            transfer_id
+        );
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    async fn in_transit_stock(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+    ) -> Value {
+        let query =
+            r#"{ "query": "{ inTransitStock { productId locationId quantity } }" }"#.to_string();
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    /// Stock of the only product in the location.
+    async fn location_stock(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        location_id: i32,
+    ) -> f64 {
+        let query = format!(
+            r#"{{ "query": "{{ locationStock(locationId: {}) {{ stock }} }}" }}"#,
+            location_id
+        );
+        let response = send_request(srv, csrf_token, request_cookie, query).await;
+        response.get("data").unwrap().get("locationStock").unwrap()[0]
+            .get("stock")
+            .unwrap()
+            .as_f64()
+            .unwrap()
+    }
+}
@@ -0,0 +1,442 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use serde_json::{json, Value};
+    use std::cell::{RefCell, RefMut};
+    use std::sync::Arc;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::{send_request, server_test};
+
+    use ::mystore_lib::models::price::FormPriceProductsToUpdate;
+    use ::mystore_lib::models::product::{FormProduct, Product};
+    use ::mystore_lib::models::user::{NewUser, User};
+    use ::mystore_lib::models::Context;
+
+    #[actix_rt::test]
+    async fn test() {
+        let user = create_user();
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        let piece = create_a_unit(&srv, csrf_token.clone(), request_cookie.clone(), "Piece").await;
+        let box_of_12 = create_a_unit(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            "Box of 12",
+        )
+        .await;
+        let half_dozen = create_a_unit(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            "Half dozen",
+        )
+        .await;
+
+        let egg = create_product(user.id, "Egg", piece);
+        // Eggs are bought by the box and sold by the half dozen.
+        add_a_product_unit(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            egg,
+            box_of_12,
+            12.0,
+            false,
+            true,
+        )
+        .await;
+        add_a_product_unit(
+            &srv,
+            csrf_token.clone(),
+            request_cookie.clone(),
+            egg,
+            half_dozen,
+            6.0,
+            true,
+            false,
+        )
+        .await;
+
+        let response = receive_stock(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            egg,
+            box_of_12,
+            2.0,
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({ "data": { "receiveStock": { "quantity": 24.0, "kind": "RECEIPT" } } })
+        );
+        assert_eq!(
+            product_stock(
+                srv.borrow_mut(),
+                csrf_token.clone(),
+                request_cookie.clone(),
+                egg
+            )
+            .await,
+            24.0
+        );
+
+        let response = receive_stock(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            egg,
+            half_dozen,
+            1.0,
+        )
+        .await;
+        assert_eq!(
+            error_message(&response),
+            format!("Product {} can't be purchased in unit {}", egg, half_dozen)
+        );
+
+        let query = format!(
+            r#"{{ "query": "{{ convertUnits(productId: {}, amount: 3.0, fromUnitId: {}, toUnitId: {}) }}" }}"#,
+            egg, box_of_12, half_dozen
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(response, json!({ "data": { "convertUnits": 6.0 } }));
+
+        // Selling a half dozen takes six eggs out of the stock.
+        let sale_id = create_a_sale(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            egg,
+            half_dozen,
+        )
+        .await
+        .get("data")
+        .unwrap()
+        .get("createSale")
+        .unwrap()
+        .get("sale")
+        .unwrap()
+        .get("id")
+        .unwrap()
+        .clone();
+        let query = format!(
+            r#"{{ "query": "mutation {{ approveSale(saleId: {}) }}" }}"#,
+            sale_id
+        );
+        let response = send_request(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            query,
+        )
+        .await;
+        assert_eq!(response, json!({ "data": { "approveSale": true } }));
+        assert_eq!(
+            product_stock(
+                srv.borrow_mut(),
+                csrf_token.clone(),
+                request_cookie.clone(),
+                egg
+            )
+            .await,
+            18.0
+        );
+
+        let response = create_a_sale(
+            srv.borrow_mut(),
+            csrf_token.clone(),
+            request_cookie.clone(),
+            egg,
+            box_of_12,
+        )
+        .await;
+        assert_eq!(
+            error_message(&response),
+            format!("Product {} can't be sold in unit {}", egg, box_of_12)
+        );
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn create_user() -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: "jhon@doe.com".to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    async fn product_stock(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+    ) -> f64 {
+        let query = format!(
+            r#"{{ "query": "{{ showProduct(productId: {}) {{ product {{ stock }} }} }}" }}"#,
+            product_id
+        );
+        let response = send_request(srv, csrf_token, request_cookie, query).await;
+        response
+            .get("data")
+            .unwrap()
+            .get("showProduct")
+            .unwrap()
+            .get("product")
+            .unwrap()
+            .get("stock")
+            .unwrap()
+            .as_f64()
+            .unwrap()
+    }
+
+    fn create_product(user_id: i32, name: &str, unit_id: i32) -> i32 {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+        let context = Context {
+            user_id,
+            conn: Arc::new(pg_pool),
+            scopes: None,
+        };
+        Product::create(
+            &context,
+            FormProduct {
+                id: None,
+                name: Some(name.to_string()),
+                stock: Some(0.0),
+                cost: Some(15),
+                description: None,
+                user_id: Some(user_id),
+                category_id: None,
+                sku: None,
+                unit_id: Some(unit_id),
+                track_lots: None,
+                serialized: None,
+                min_stock: None,
+                reorder_point: None,
+                reorder_quantity: None,
+                supplier_id: None,
+            },
+            FormPriceProductsToUpdate { data: vec![] },
+        )
+        .unwrap()
+        .product
+        .id
+    }
+
+    async fn create_a_unit(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        name: &str,
+    ) -> i32 {
+        let query = format!(
+            r#"{{ "query": "mutation {{ createUnit(form: {{ name: \"{}\" }}) {{ id }} }}" }}"#,
+            name
+        );
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        serde_json::from_value(
+            response
+                .get("data")
+                .unwrap()
+                .get("createUnit")
+                .unwrap()
+                .get("id")
+                .unwrap()
+                .clone(),
+        )
+        .unwrap()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn add_a_product_unit(
+        srv: &RefCell<TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        unit_id: i32,
+        factor: f64,
+        for_sale: bool,
+        for_purchase: bool,
+    ) {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateProductUnit($form: FormProductUnit!) {{
+                        createProductUnit(form: $form) {{
+                            factor
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "productId": {},
+                        "unitId": {},
+                        "factor": {},
+                        "forSale": {},
+                        "forPurchase": {}
+                    }}
+                }}
+            }}"#,
+            product_id, unit_id, factor, for_sale, for_purchase
+        )
+        .replace("\n", "");
+
+        let response = send_request(srv.borrow_mut(), csrf_token, request_cookie, query).await;
+        assert_eq!(
+            response,
+            json!({ "data": { "createProductUnit": { "factor": factor } } })
+        );
+    }
+
+    async fn receive_stock(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        unit_id: i32,
+        quantity: f64,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation ReceiveStock($form: FormStockReceipt!) {{
+                        receiveStock(form: $form) {{
+                            quantity
+                            kind
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "productId": {},
+                        "unitId": {},
+                        "quantity": {}
+                    }}
+                }}
+            }}"#,
+            product_id, unit_id, quantity
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    /// Creates a sale of one `unit_id` of the product.
+    async fn create_a_sale(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+        product_id: i32,
+        unit_id: i32,
+    ) -> Value {
+        let query = format!(
+            r#"
+            {{
+                "query": "
+                    mutation CreateSale($form: FormSale!, $formSaleProducts: FormSaleProducts!) {{
+                        createSale(form: $form, formSaleProducts: $formSaleProducts) {{
+                            sale {{
+                                id
+                            }}
+                        }}
+                    }}
+                ",
+                "variables": {{
+                    "form": {{
+                        "saleDate": "2019-11-12",
+                        "total": 120
+                    }},
+                    "formSaleProducts": {{
+                        "data":
+                            [{{
+                                "product": {{ }},
+                                "saleProduct": {{
+                                    "amount": 1.0,
+                                    "discount": 0,
+                                    "price": 120,
+                                    "productId": {},
+                                    "unitId": {},
+                                    "tax": 0,
+                                    "total": 120
+                                }}
+                            }}]
+                    }}
+                }}
+            }}"#,
+            product_id, unit_id
+        )
+        .replace("\n", "");
+
+        send_request(srv, csrf_token, request_cookie, query).await
+    }
+
+    fn error_message(response: &Value) -> &str {
+        response.get("errors").unwrap()[0]
+            .get("message")
+            .unwrap()
+            .as_str()
+            .unwrap()
+    }
+}
@@ -0,0 +1,160 @@
+#[macro_use]
+extern crate dotenv_codegen;
+
+mod common;
+
+mod test {
+    use actix_http::cookie::Cookie;
+    use actix_http::httpmessage::HttpMessage;
+    use actix_http_test::TestServer;
+    use actix_web::http;
+    use actix_web::http::header;
+    use chrono::Duration;
+    use chrono::Local;
+    use http::header::HeaderValue;
+
+    use std::cell::RefMut;
+    use std::time::Duration as std_duration;
+
+    use crate::common::db_connection::establish_connection;
+    use crate::common::server_test;
+
+    use ::mystore_lib::models::user::{NewUser, User, MAX_FAILED_ATTEMPTS};
+
+    #[actix_rt::test]
+    async fn test() {
+        delete_users();
+        create_user("jhon@doe.com");
+        create_user("locked@doe.com");
+
+        let srv = server_test();
+
+        let (csrf_token, request_cookie) = login(srv.borrow_mut()).await;
+
+        lock_an_account(srv.borrow_mut()).await;
+
+        // Only administrators can unlock an account.
+        let response =
+            unlock_an_account(srv.borrow_mut(), csrf_token.clone(), request_cookie.clone()).await;
+        assert_eq!(response, http::StatusCode::UNAUTHORIZED);
+
+        grant_admin("jhon@doe.com");
+
+        let response =
+            unlock_an_account(srv.borrow_mut(), csrf_token.clone(), request_cookie.clone()).await;
+        assert_eq!(response, http::StatusCode::OK);
+
+        let response = srv
+            .borrow_mut()
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .send_body(r#"{"email":"locked@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    async fn login(srv: RefMut<'_, TestServer>) -> (HeaderValue, Cookie<'_>) {
+        let request = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .timeout(std_duration::from_secs(600));
+
+        let response = request
+            .send_body(r#"{"email":"jhon@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        let csrf_token = response.headers().get("x-csrf-token").unwrap();
+        let cookies = response.cookies().unwrap();
+        let cookie = cookies[0].clone().into_owned().value().to_string();
+
+        let request_cookie = Cookie::build("mystorejwt", cookie)
+            .domain("localhost")
+            .path("/")
+            .max_age(Duration::days(1).num_seconds())
+            .secure(false)
+            .http_only(false)
+            .finish();
+        (csrf_token.clone(), request_cookie.clone())
+    }
+
+    fn delete_users() {
+        use ::mystore_lib::schema::{login_attempts, users};
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::delete(users::table).execute(&pg_pool).unwrap();
+        // Earlier runs would otherwise count against the address limit.
+        diesel::delete(login_attempts::table)
+            .execute(&pg_pool)
+            .unwrap();
+    }
+
+    fn create_user(email: &str) -> User {
+        use ::mystore_lib::schema::users;
+        use diesel::RunQueryDsl;
+
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        diesel::insert_into(users::table)
+            .values(NewUser {
+                email: email.to_string(),
+                company: "My own personal enterprise".to_string(),
+                password: User::hash_password("12345678".to_string()).unwrap(),
+                created_at: Local::now().naive_local(),
+            })
+            .get_result::<User>(&pg_pool)
+            .unwrap()
+    }
+
+    fn grant_admin(email: &str) {
+        let connection = establish_connection();
+        let pg_pool = connection.get().unwrap();
+
+        User::set_admin(email, true, &pg_pool).unwrap();
+    }
+
+    async fn lock_an_account(srv: RefMut<'_, TestServer>) {
+        for attempt in 1..=MAX_FAILED_ATTEMPTS {
+            let response = srv
+                .post("/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .send_body(r#"{"email":"locked@doe.com","password":"wrong password"}"#)
+                .await
+                .unwrap();
+            if attempt < MAX_FAILED_ATTEMPTS {
+                assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+            } else {
+                assert_eq!(response.status(), http::StatusCode::LOCKED);
+            }
+        }
+
+        // Even the right password is refused while the account is locked.
+        let response = srv
+            .post("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .send_body(r#"{"email":"locked@doe.com","password":"12345678"}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::LOCKED);
+    }
+
+    async fn unlock_an_account(
+        srv: RefMut<'_, TestServer>,
+        csrf_token: HeaderValue,
+        request_cookie: Cookie<'_>,
+    ) -> http::StatusCode {
+        let response = srv
+            .post("/unlock")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-csrf-token", csrf_token.to_str().unwrap())
+            .cookie(request_cookie)
+            .send_body(r#"{"email":"locked@doe.com"}"#)
+            .await
+            .unwrap();
+        response.status()
+    }
+}